@@ -0,0 +1,175 @@
+// A set of board tiles packed into 64 bits, tile (rank, file) is bit rank * 8 + file so a8 is bit 0 and h1 is bit 63
+pub type Bitboard = u64;
+
+// Index of a tile in a bitboard, from 0 (a8) to 63 (h1)
+pub type Square = usize;
+
+// Converts (rank, file) coordinates into a square index
+pub const fn square(rank: usize, file: usize) -> Square {
+    rank * 8 + file
+}
+
+// Converts a square index back into (rank, file) coordinates
+pub const fn coordinates(square: Square) -> (usize, usize) {
+    (square / 8, square % 8)
+}
+
+// Returns a bitboard with only the given square set
+pub const fn square_bit(square: Square) -> Bitboard {
+    1 << square
+}
+
+// Removes the lowest set square from the bitboard and returns it, the bitboard must not be empty
+pub fn pop_square(bitboard: &mut Bitboard) -> Square {
+    let square = bitboard.trailing_zeros() as Square;
+    *bitboard &= *bitboard - 1;
+    square
+}
+
+// Every square from one square to another, both ends included, used for runs of tiles along a rank
+pub fn span(from: Square, to: Square) -> Bitboard {
+    let (low, high) = (from.min(to), from.max(to));
+    (u64::MAX >> (63 - high)) & (u64::MAX << low)
+}
+
+// The tiles of the given rank
+pub const fn rank_mask(rank: usize) -> Bitboard {
+    0xff << (rank * 8)
+}
+
+// Iterates over the squares set in a bitboard, lowest first
+pub fn squares(mut bitboard: Bitboard) -> impl Iterator<Item = Square> {
+    std::iter::from_fn(move || {
+        if bitboard == 0 {
+            None
+        } else {
+            Some(pop_square(&mut bitboard))
+        }
+    })
+}
+
+pub const KNIGHT_OFFSETS: [(i32, i32); 8] = [
+    (2, 1),
+    (2, -1),
+    (-2, 1),
+    (-2, -1),
+    (1, 2),
+    (1, -2),
+    (-1, 2),
+    (-1, -2),
+];
+
+pub const KING_OFFSETS: [(i32, i32); 8] = [
+    (-1, -1),
+    (-1, 0),
+    (-1, 1),
+    (1, -1),
+    (1, 0),
+    (1, 1),
+    (0, 1),
+    (0, -1),
+];
+
+// The tiles a knight on each square attacks
+pub const KNIGHT_ATTACKS: [Bitboard; 64] = leaper_attacks(&KNIGHT_OFFSETS);
+
+// The tiles a king on each square attacks
+pub const KING_ATTACKS: [Bitboard; 64] = leaper_attacks(&KING_OFFSETS);
+
+// The tiles a pawn on each square attacks, indexed by [colour][square] with white pawns moving towards rank index 0
+pub const PAWN_ATTACKS: [[Bitboard; 64]; 2] = [
+    leaper_attacks(&[(-1, -1), (-1, 1)]),
+    leaper_attacks(&[(1, -1), (1, 1)]),
+];
+
+// Builds an attack table for a piece that jumps by fixed (rank, file) offsets
+const fn leaper_attacks(offsets: &[(i32, i32)]) -> [Bitboard; 64] {
+    let mut table = [0; 64];
+    let mut square = 0;
+    while square < 64 {
+        let rank = (square / 8) as i32;
+        let file = (square % 8) as i32;
+        let mut i = 0;
+        while i < offsets.len() {
+            let target_rank = rank + offsets[i].0;
+            let target_file = file + offsets[i].1;
+            if target_rank >= 0 && target_rank < 8 && target_file >= 0 && target_file < 8 {
+                table[square] |= 1 << (target_rank * 8 + target_file);
+            }
+            i += 1;
+        }
+        square += 1;
+    }
+    table
+}
+
+// Sliding directions as (rank, file) steps, the first four head towards higher square indices
+const DIRECTIONS: [(i32, i32); 8] = [
+    (0, 1),
+    (1, -1),
+    (1, 0),
+    (1, 1),
+    (0, -1),
+    (-1, 1),
+    (-1, 0),
+    (-1, -1),
+];
+
+// Every tile from each square to the edge of the board in each direction, indexed by [direction][square]
+const RAYS: [[Bitboard; 64]; 8] = rays();
+
+const fn rays() -> [[Bitboard; 64]; 8] {
+    let mut table = [[0; 64]; 8];
+    let mut direction = 0;
+    while direction < 8 {
+        let mut square = 0;
+        while square < 64 {
+            let mut rank = (square / 8) as i32 + DIRECTIONS[direction].0;
+            let mut file = (square % 8) as i32 + DIRECTIONS[direction].1;
+            while rank >= 0 && rank < 8 && file >= 0 && file < 8 {
+                table[direction][square] |= 1 << (rank * 8 + file);
+                rank += DIRECTIONS[direction].0;
+                file += DIRECTIONS[direction].1;
+            }
+            square += 1;
+        }
+        direction += 1;
+    }
+    table
+}
+
+// The tiles a slider attacks in one direction, stopping at and including the first occupied tile
+fn ray_attacks(direction: usize, square: Square, occupied: Bitboard) -> Bitboard {
+    let ray = RAYS[direction][square];
+    let blockers = ray & occupied;
+    if blockers == 0 {
+        return ray;
+    }
+    let first_blocker = if direction < 4 {
+        blockers.trailing_zeros() as Square
+    } else {
+        63 - blockers.leading_zeros() as Square
+    };
+    ray ^ RAYS[direction][first_blocker]
+}
+
+// The tiles a rook on the square attacks given every occupied tile
+pub fn rook_attacks(square: Square, occupied: Bitboard) -> Bitboard {
+    ray_attacks(0, square, occupied)
+        | ray_attacks(2, square, occupied)
+        | ray_attacks(4, square, occupied)
+        | ray_attacks(6, square, occupied)
+}
+
+// The tiles a bishop on the square attacks given every occupied tile
+pub fn bishop_attacks(square: Square, occupied: Bitboard) -> Bitboard {
+    ray_attacks(1, square, occupied)
+        | ray_attacks(3, square, occupied)
+        | ray_attacks(5, square, occupied)
+        | ray_attacks(7, square, occupied)
+}
+
+// The tiles a queen on the square attacks given every occupied tile
+pub fn queen_attacks(square: Square, occupied: Bitboard) -> Bitboard {
+    rook_attacks(square, occupied) | bishop_attacks(square, occupied)
+}
@@ -1,81 +1,600 @@
+use std::sync::OnceLock;
+
 use crossterm::style::Stylize;
+use rand::Rng;
+use regex::Regex;
 
+use crate::bitboard::{
+    bishop_attacks, coordinates, queen_attacks, rank_mask, rook_attacks, span, square, square_bit,
+    squares, Bitboard, Square, KING_ATTACKS, KNIGHT_ATTACKS, PAWN_ATTACKS,
+};
 use crate::screen::Text;
+use crate::zobrist;
 
-#[derive(Clone, Copy, Debug)]
+#[derive(Clone, Debug)]
 pub struct Board {
-    pub pieces: [[Piece; 8]; 8],
+    // One bitboard per colour and piece type, indexed by [colour][piece index] with white first
+    bitboards: [[Bitboard; 6]; 2],
+    // Every tile each colour has a piece on, white first
+    occupancy: [Bitboard; 2],
+    // The type of piece standing on each square, for looking up what a move lands on
+    squares: [ChessPieces; 64],
+    // Zobrist hash of the position, kept up to date as pieces and rights change
+    hash: u64,
     pub selected_piece: Option<(usize, usize)>,
     pub white_move: bool,
     pub moving: bool,
+    pub castling: CastlingRights,
+    // Whether the game follows Chess960 rules, where castling is shown as the king moving onto its own rook
+    pub chess960: bool,
+    // The rules the game is played under
+    pub variant: Variant,
+    // How many checks each side has given, white first, which only matters in Three-check
+    pub checks_given: [u32; 2],
+    // The pieces each side has captured and may drop back onto the board in Crazyhouse, indexed by [colour][pocket index]
+    pockets: [[u8; 5]; 2],
+    // Pieces that were pawns before promoting, which go back to being pawns when captured in Crazyhouse
+    promoted: Bitboard,
+    pub en_passant: Option<(usize, usize)>,
+    pub halfmove_clock: u32,
+    pub fullmove_number: u32,
+    // Hashes of every position reached so far in the game, the current one included
+    pub history: Vec<u64>,
+    pub claimed_draw: Option<DrawReason>,
+    // The position the game started from and every move played since
+    pub start_fen: String,
+    pub moves: Vec<Move>,
+    // What each played move overwrote, in step with moves, so that moves can be taken back
+    undo_stack: Vec<UndoInfo>,
+    // Moves that were taken back, the most recent last, ready to be played again
+    pub redo_stack: Vec<Move>,
+}
+
+// The standard starting position
+pub const STARTING_FEN: &str = "rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR w KQkq - 0 1";
+
+// Where the two knights go among the five tiles left once the bishops and queen are placed, in Chess960 numbering order
+const CHESS960_KNIGHTS: [(usize, usize); 10] = [
+    (0, 1),
+    (0, 2),
+    (0, 3),
+    (0, 4),
+    (1, 2),
+    (1, 3),
+    (1, 4),
+    (2, 3),
+    (2, 4),
+    (3, 4),
+];
+
+// The pieces a pawn may promote to, best first
+const PROMOTION_PIECES: [ChessPieces; 4] = [
+    ChessPieces::Queen,
+    ChessPieces::Rook,
+    ChessPieces::Bishop,
+    ChessPieces::Knight,
+];
+
+// The pieces that can be held in a Crazyhouse pocket, in the order they are written and shown
+pub const POCKET_PIECES: [ChessPieces; 5] = [
+    ChessPieces::Queen,
+    ChessPieces::Rook,
+    ChessPieces::Bishop,
+    ChessPieces::Knight,
+    ChessPieces::Pawn,
+];
+
+// In Antichess the king is an ordinary piece, so pawns may promote to one too
+const ANTICHESS_PROMOTION_PIECES: [ChessPieces; 5] = [
+    ChessPieces::Queen,
+    ChessPieces::Rook,
+    ChessPieces::Bishop,
+    ChessPieces::Knight,
+    ChessPieces::King,
+];
+
+// The four centre tiles a king has to reach in King of the Hill
+const HILL: Bitboard = square_bit(square(3, 3))
+    | square_bit(square(3, 4))
+    | square_bit(square(4, 3))
+    | square_bit(square(4, 4));
+
+// Every kind of piece that can stand on a tile
+const PIECE_TYPES: [ChessPieces; 6] = [
+    ChessPieces::King,
+    ChessPieces::Queen,
+    ChessPieces::Rook,
+    ChessPieces::Bishop,
+    ChessPieces::Knight,
+    ChessPieces::Pawn,
+];
+
+// The position of a colour in the bitboard arrays
+fn colour_index(white: bool) -> usize {
+    if white {
+        0
+    } else {
+        1
+    }
 }
 
 impl Board {
-    pub fn new() -> Self {
+    // Sets up the starting position of the given variant
+    pub fn with_variant(variant: Variant) -> Self {
+        Board::from_variant_fen(variant.starting_fen(), variant).unwrap()
+    }
+
+    // Converts a given Forsyth-Edwards Notation string to a chess board, the two move clocks may be left off
+    pub fn from_fen(fen: &str) -> Result<Self, FenError> {
+        Board::from_variant_fen(fen, Variant::Standard)
+    }
+
+    // Converts a FEN string to a board played under the given variant
+    // Three-check positions may end with the checks each side has given, such as "+1+0"
+    // Crazyhouse positions may end the piece placement with the pockets, such as "[Qnp]", and mark promoted pieces with a '~'
+    pub fn from_variant_fen(fen: &str, variant: Variant) -> Result<Self, FenError> {
+        let mut fields = fen.split_whitespace().collect::<Vec<_>>();
+        let mut checks_given = [0, 0];
+        if variant == Variant::ThreeCheck
+            && fields.last().is_some_and(|field| field.starts_with('+'))
+        {
+            let field = fields.pop().unwrap();
+            checks_given = field
+                .strip_prefix('+')
+                .and_then(|counts| counts.split_once('+'))
+                .and_then(|(white, black)| Some([white.parse().ok()?, black.parse().ok()?]))
+                .filter(|counts: &[u32; 2]| counts.iter().all(|count| *count <= 3))
+                .ok_or_else(|| FenError::InvalidCheckCount(field.to_string()))?;
+        }
+        if !(4..=6).contains(&fields.len()) {
+            return Err(FenError::WrongFieldCount(fields.len()));
+        }
+
+        let (placement, pocket_field) = match fields[0].split_once('[') {
+            Some((placement, pockets)) if variant == Variant::Crazyhouse => {
+                let pockets = pockets
+                    .strip_suffix(']')
+                    .ok_or_else(|| FenError::InvalidPocket(pockets.to_string()))?;
+                (placement, Some(pockets))
+            }
+            _ => (fields[0], None),
+        };
+
+        let lines = placement.split('/').collect::<Vec<_>>();
+        if lines.len() != 8 {
+            return Err(FenError::WrongRankCount(lines.len()));
+        }
+
+        let mut board = Self {
+            checks_given,
+            ..Board::empty(variant)
+        };
+
+        for (rank_index, line) in lines.iter().enumerate() {
+            let mut file_index = 0;
+            let mut after_blanks = false;
+            for char in line.chars() {
+                if let Some(blank_count) = char.to_digit(10).filter(|count| (1..=8).contains(count))
+                {
+                    // A run of empty tiles is written as one number, so "44" is not a way to write 8
+                    if after_blanks {
+                        return Err(FenError::AdjacentBlanks {
+                            rank: 8 - rank_index,
+                        });
+                    }
+                    after_blanks = true;
+                    file_index += blank_count as usize;
+                    continue;
+                }
+                after_blanks = false;
+                if char == '~' && variant == Variant::Crazyhouse && (1..=8).contains(&file_index) {
+                    let tile = square(rank_index, file_index - 1);
+                    if board.squares[tile] != ChessPieces::None {
+                        board.promoted |= square_bit(tile);
+                        continue;
+                    }
+                }
+
+                let symbol = ChessPieces::from_fen(char).ok_or(FenError::UnknownPiece(char))?;
+                if file_index < 8 {
+                    board.put_piece(square(rank_index, file_index), symbol, char.is_uppercase());
+                }
+                file_index += 1;
+            }
+
+            if file_index != 8 {
+                return Err(FenError::WrongRankLength {
+                    rank: 8 - rank_index,
+                    length: file_index,
+                });
+            }
+        }
+
+        for char in pocket_field.unwrap_or("").chars() {
+            match ChessPieces::from_fen(char) {
+                Some(symbol) if symbol != ChessPieces::King => {
+                    board.pockets[colour_index(char.is_uppercase())][symbol.pocket_index()] += 1;
+                }
+                _ => return Err(FenError::InvalidPocket(pocket_field.unwrap().to_string())),
+            }
+        }
+
+        board.white_move = match fields[1] {
+            "w" => true,
+            "b" => false,
+            side => return Err(FenError::InvalidSideToMove(side.to_string())),
+        };
+
+        board.castling = board
+            .castling_from_fen(fields[2])
+            .ok_or_else(|| FenError::InvalidCastling(fields[2].to_string()))?;
+        board.chess960 = !board.has_standard_castling();
+
+        board.en_passant = match fields[3] {
+            "-" => None,
+            field => {
+                let expected_rank = if board.white_move { 2 } else { 5 };
+                match tile_from_algebraic(field) {
+                    Some(tile) if tile.0 == expected_rank => Some(tile),
+                    _ => return Err(FenError::InvalidEnPassant(field.to_string())),
+                }
+            }
+        };
+
+        if let Some(field) = fields.get(4) {
+            board.halfmove_clock = field
+                .parse()
+                .map_err(|_| FenError::InvalidHalfmoveClock(field.to_string()))?;
+        }
+        if let Some(field) = fields.get(5) {
+            board.fullmove_number = field
+                .parse()
+                .ok()
+                .filter(|number| *number > 0)
+                .ok_or_else(|| FenError::InvalidFullmoveNumber(field.to_string()))?;
+        }
+
+        board.validate()?;
+        board.hash = board.compute_hash();
+        board.history.push(board.hash);
+        board.start_fen = board.to_fen();
+        Ok(board)
+    }
+
+    // Sets up a standard chess position with just the given pieces, each as (square, type, is white), and nobody able to castle
+    // A piece given for a tile that is already taken is left off
+    pub fn from_pieces(
+        pieces: &[(Square, ChessPieces, bool)],
+        white_move: bool,
+    ) -> Result<Self, FenError> {
+        let mut board = Board::empty(Variant::Standard);
+        for &(tile, symbol, white) in pieces {
+            if board.squares[tile] == ChessPieces::None {
+                board.put_piece(tile, symbol, white);
+            }
+        }
+        board.white_move = white_move;
+
+        board.validate()?;
+        board.hash = board.compute_hash();
+        board.history.push(board.hash);
+        board.start_fen = board.to_fen();
+        Ok(board)
+    }
+
+    // A board of the variant with nothing on it, white to move
+    fn empty(variant: Variant) -> Self {
         Self {
-            pieces: Board::from_fen("rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR w KQkq - 0 1"),
+            bitboards: [[0; 6]; 2],
+            occupancy: [0; 2],
+            squares: [ChessPieces::None; 64],
+            hash: 0,
             selected_piece: None,
             white_move: true,
             moving: false,
+            castling: CastlingRights::none(),
+            chess960: false,
+            variant,
+            checks_given: [0, 0],
+            pockets: [[0; 5]; 2],
+            promoted: 0,
+            en_passant: None,
+            halfmove_clock: 0,
+            fullmove_number: 1,
+            history: vec![],
+            claimed_draw: None,
+            start_fen: String::new(),
+            moves: vec![],
+            undo_stack: vec![],
+            redo_stack: vec![],
+        }
+    }
+
+    // Builds the Chess960 starting position with the given number, from 0 to 959 where 518 is the standard starting position
+    pub fn chess960(index: usize) -> Option<Self> {
+        if index >= 960 {
+            return None;
+        }
+
+        let mut back_rank = [ChessPieces::None; 8];
+        let empty_files = |back_rank: &[ChessPieces; 8]| {
+            (0..8)
+                .filter(|file| back_rank[*file] == ChessPieces::None)
+                .collect::<Vec<_>>()
+        };
+
+        let mut number = index;
+        back_rank[number % 4 * 2 + 1] = ChessPieces::Bishop;
+        number /= 4;
+        back_rank[number % 4 * 2] = ChessPieces::Bishop;
+        number /= 4;
+        back_rank[empty_files(&back_rank)[number % 6]] = ChessPieces::Queen;
+        number /= 6;
+        let (first_knight, second_knight) = CHESS960_KNIGHTS[number];
+        let knight_files = empty_files(&back_rank);
+        back_rank[knight_files[first_knight]] = ChessPieces::Knight;
+        back_rank[knight_files[second_knight]] = ChessPieces::Knight;
+        let rest = [ChessPieces::Rook, ChessPieces::King, ChessPieces::Rook];
+        for (file, symbol) in empty_files(&back_rank).into_iter().zip(rest) {
+            back_rank[file] = symbol;
+        }
+
+        let black_pieces = back_rank
+            .iter()
+            .filter_map(|symbol| {
+                Piece {
+                    symbol: *symbol,
+                    file: 0,
+                    rank: 0,
+                    white: false,
+                }
+                .to_fen()
+            })
+            .collect::<String>();
+        let fen = format!(
+            "{}/pppppppp/8/8/8/8/PPPPPPPP/{} w KQkq - 0 1",
+            black_pieces,
+            black_pieces.to_uppercase()
+        );
+        let mut board = Board::from_fen(&fen).ok()?;
+        board.chess960 = true;
+        Some(board)
+    }
+
+    // Builds one of the 960 starting positions at random
+    pub fn random_chess960() -> Self {
+        Board::chess960(rand::thread_rng().gen_range(0..960)).unwrap()
+    }
+
+    // Converts the board into a Forsyth-Edwards Notation string
+    pub fn to_fen(&self) -> String {
+        let mut piece_data = String::with_capacity(72);
+        for rank in 0..8 {
+            if rank > 0 {
+                piece_data.push('/');
+            }
+            let mut blank_count = 0;
+            for file in 0..8 {
+                match self.piece_at(rank, file).to_fen() {
+                    Some(char) => {
+                        if blank_count > 0 {
+                            piece_data.extend(char::from_digit(blank_count, 10));
+                            blank_count = 0;
+                        }
+                        piece_data.push(char);
+                        if self.promoted & square_bit(square(rank, file)) != 0 {
+                            piece_data.push('~');
+                        }
+                    }
+                    None => blank_count += 1,
+                }
+            }
+            if blank_count > 0 {
+                piece_data.extend(char::from_digit(blank_count, 10));
+            }
+        }
+
+        let en_passant = match self.en_passant {
+            Some((rank, file)) => tile_to_algebraic(rank, file),
+            None => "-".to_string(),
+        };
+
+        let pockets = if self.variant == Variant::Crazyhouse {
+            let mut pockets = String::from("[");
+            for white in [true, false] {
+                for symbol in POCKET_PIECES {
+                    let piece = Piece {
+                        symbol,
+                        file: 0,
+                        rank: 0,
+                        white,
+                    };
+                    for _ in 0..self.pocket_count(white, symbol) {
+                        pockets.extend(piece.to_fen());
+                    }
+                }
+            }
+            pockets.push(']');
+            pockets
+        } else {
+            String::new()
+        };
+
+        let mut fen = format!(
+            "{}{} {} {} {} {} {}",
+            piece_data,
+            pockets,
+            if self.white_move { "w" } else { "b" },
+            self.castling_to_fen(),
+            en_passant,
+            self.halfmove_clock,
+            self.fullmove_number
+        );
+        if self.variant == Variant::ThreeCheck {
+            fen.push_str(&format!(
+                " +{}+{}",
+                self.checks_given[0], self.checks_given[1]
+            ));
+        }
+        fen
+    }
+
+    // Rejects positions that could never come up in a game of the variant being played
+    fn validate(&self) -> Result<(), FenError> {
+        for white in [true, false] {
+            let expected_kings = match self.variant {
+                // Any number of kings is fine when the king is an ordinary piece
+                Variant::Antichess => continue,
+                Variant::Horde if white => 0,
+                _ => 1,
+            };
+            let kings = self.pieces_of(white, ChessPieces::King).count_ones();
+            if kings < expected_kings {
+                return Err(FenError::MissingKing { white });
+            } else if kings > expected_kings {
+                return Err(FenError::TooManyKings { white });
+            }
+        }
+
+        let back_ranks: Bitboard = 0xff | 0xff << 56;
+        let mut pawns =
+            self.pieces_of(true, ChessPieces::Pawn) | self.pieces_of(false, ChessPieces::Pawn);
+        if self.variant == Variant::Horde {
+            // The horde starts with pawns on white's first rank
+            pawns &= !(self.pieces_of(true, ChessPieces::Pawn) & rank_mask(7));
         }
+        if let Some(pawn) = squares(pawns & back_ranks).next() {
+            let (rank, file) = coordinates(pawn);
+            return Err(FenError::PawnOnBackRank(tile_to_algebraic(rank, file)));
+        }
+
+        if self.is_in_check(!self.white_move) {
+            return Err(FenError::OpponentInCheck);
+        }
+
+        Ok(())
+    }
+
+    // Finds the file of the king of the given colour, if it stands on its home rank
+    fn home_king_file(&self, white: bool) -> Option<usize> {
+        let home_rank = if white { 7 } else { 0 };
+        squares(self.pieces_of(white, ChessPieces::King) & rank_mask(home_rank))
+            .next()
+            .map(|king| king % 8)
+    }
+
+    // Lists the files of the rooks of the given colour standing on their home rank
+    fn home_rook_files(&self, white: bool) -> Vec<usize> {
+        let home_rank = if white { 7 } else { 0 };
+        squares(self.pieces_of(white, ChessPieces::Rook) & rank_mask(home_rank))
+            .map(|rook| rook % 8)
+            .collect()
     }
 
-    // Converts a given Forsyth-Edwards Notation string to a chess board
-    fn from_fen(fen: &'static str) -> [[Piece; 8]; 8] {
-        let piece_data = fen.split(" ").collect::<Vec<_>>()[0];
-        let lines = piece_data.split("/");
-        let mut final_board = [[Piece::none(); 8]; 8];
-        for (rank_index, line) in lines.enumerate() {
-            let mut rank: [Piece; 8] = [Piece::none(); 8];
-            let mut new_line: String = line.to_string();
-            for (i, char) in line.char_indices() {
-                if let Some(blank_count) = char.to_digit(10) {
-                    let split = line.split_at(i);
-                    new_line = split.0.to_string();
-                    new_line.push_str(&"T".repeat(blank_count as usize));
-                    let end_line: String = split.1.chars().collect::<Vec<char>>()[1..]
+    // Reads the castling field of a FEN string, such as "KQkq", the X-FEN "KQkf" or the Shredder-FEN "HAha"
+    // K and Q castle with the outermost rook on their side of the king, a file letter castles with the rook on that file
+    fn castling_from_fen(&self, field: &str) -> Option<CastlingRights> {
+        let mut rights = CastlingRights::none();
+        if field == "-" {
+            return Some(rights);
+        }
+
+        for char in field.chars() {
+            let white = char.is_ascii_uppercase();
+            let king_file = self.home_king_file(white)?;
+            let rook_files = self.home_rook_files(white);
+            let (side, rook_file) = match char.to_ascii_lowercase() {
+                'k' => (
+                    CastleSide::KingSide,
+                    rook_files
                         .iter()
-                        .map(|c| c.to_string())
-                        .collect();
-                    new_line.push_str(&end_line);
+                        .copied()
+                        .filter(|file| *file > king_file)
+                        .max()?,
+                ),
+                'q' => (
+                    CastleSide::QueenSide,
+                    rook_files
+                        .iter()
+                        .copied()
+                        .filter(|file| *file < king_file)
+                        .min()?,
+                ),
+                letter @ 'a'..='h' => {
+                    let file = letter as usize - 'a' as usize;
+                    if !rook_files.contains(&file) {
+                        return None;
+                    }
+                    if file > king_file {
+                        (CastleSide::KingSide, file)
+                    } else {
+                        (CastleSide::QueenSide, file)
+                    }
                 }
+                _ => return None,
+            };
+
+            let right = rights.side_mut(white, side);
+            if right.is_some() {
+                return None;
             }
+            *right = Some(rook_file);
+        }
+        Some(rights)
+    }
 
-            for (file_index, char) in new_line.char_indices() {
-                rank[file_index] = Piece {
-                    symbol: ChessPieces::from_fen(char),
-                    file: file_index,
-                    rank: rank_index,
-                    white: char.is_uppercase(),
+    // Writes the castling rights as X-FEN, which matches plain FEN for standard chess and only names files for rooks that aren't outermost
+    fn castling_to_fen(&self) -> String {
+        let mut field = String::new();
+        for white in [true, false] {
+            let rook_files = self.home_rook_files(white);
+            for side in [CastleSide::KingSide, CastleSide::QueenSide] {
+                let rook_file = match self.castling.for_side(white, side) {
+                    Some(file) => file,
+                    None => continue,
+                };
+                let outermost = match side {
+                    CastleSide::KingSide => rook_files.iter().all(|file| *file <= rook_file),
+                    CastleSide::QueenSide => rook_files.iter().all(|file| *file >= rook_file),
                 };
+                let letter = match (outermost, side) {
+                    (true, CastleSide::KingSide) => 'k',
+                    (true, CastleSide::QueenSide) => 'q',
+                    (false, _) => (b'a' + rook_file as u8) as char,
+                };
+                field.push(if white {
+                    letter.to_ascii_uppercase()
+                } else {
+                    letter
+                });
             }
-            final_board[rank_index] = rank;
         }
-        final_board
+
+        if field.is_empty() {
+            "-".to_string()
+        } else {
+            field
+        }
+    }
+
+    // Checks whether every castling right has the king on the e-file and the rook in its corner
+    fn has_standard_castling(&self) -> bool {
+        [true, false].iter().all(|white| {
+            let (king_side, queen_side) = self.castling.for_colour(*white);
+            (king_side.is_none() && queen_side.is_none() || self.home_king_file(*white) == Some(4))
+                && king_side.is_none_or(|file| file == 7)
+                && queen_side.is_none_or(|file| file == 0)
+        })
     }
 
     // Returns the baord as a 2d vector of Text objects
     pub fn display_board(&self) -> Vec<Vec<Text>> {
         let mut board_rows: Vec<Vec<Text>> = vec![vec![]; 8];
 
-        for (rank_index, rank) in self.pieces.iter().enumerate() {
-            for (file_index, piece) in rank.iter().enumerate() {
-                let checker_index = (piece.file + piece.rank) % 2;
-                let piece_text = if checker_index == 0 {
-                    piece.get_symbol()
-                } else {
-                    piece
-                        .get_symbol()
-                        .on(crossterm::style::Color::AnsiValue(237))
-                        .to_string()
-                };
-
-                board_rows[rank_index].push(Text::new(
-                    piece_text,
-                    file_index * 2,
-                    rank_index,
-                    None,
-                ));
+        for (rank_index, row) in board_rows.iter_mut().enumerate() {
+            for file_index in 0..8 {
+                let piece_text = self.query_board(rank_index, file_index).1;
+                row.push(Text::new(piece_text, file_index * 2, rank_index, None));
             }
         }
 
@@ -83,14 +602,13 @@ impl Board {
     }
 
     // Sets the selected piece for use in piece movement
-    pub fn set_selected(&mut self, rank: usize, file: usize) -> Self {
+    pub fn set_selected(&mut self, rank: usize, file: usize) {
         self.selected_piece = Some((rank, file));
-        *self
     }
 
     // Returns the piece at the given position and its printable text, including ANSI sequences
     pub fn query_board(&self, rank: usize, file: usize) -> (Piece, String) {
-        let piece = self.pieces[rank][file];
+        let piece = self.piece_at(rank, file);
         let piece_checker = (rank + file) % 2;
         let piece_text = if piece_checker == 0 {
             piece.get_symbol()
@@ -103,523 +621,1625 @@ impl Board {
         (piece, piece_text)
     }
 
-    // Gets the valid positions to the left of a piece
-    pub fn valid_positions_left(
-        &self,
-        rank: usize,
-        file: usize,
-        white: bool,
-    ) -> Vec<(usize, usize)> {
-        let mut left_moves = vec![];
-        let piece_rank = self.pieces[rank];
-
-        let whole_rank_left = &mut piece_rank.clone()[..file];
-        whole_rank_left.reverse();
-        let mut dist_left = whole_rank_left.len();
-        for (dist, piece) in whole_rank_left.iter().enumerate() {
-            if piece.symbol != ChessPieces::None {
-                dist_left = dist;
-                break;
-            }
-        }
-        if whole_rank_left.len() > 0 && dist_left < whole_rank_left.len() {
-            let last_piece = whole_rank_left[dist_left];
-            if last_piece.white != white {
-                dist_left += 1;
-            }
+    // Looks up the piece standing at (rank, file), an empty tile gives a ChessPieces::None piece
+    pub fn piece_at(&self, rank: usize, file: usize) -> Piece {
+        let tile = square(rank, file);
+        let symbol = self.squares[tile];
+        Piece {
+            symbol,
+            rank,
+            file,
+            white: symbol == ChessPieces::None || self.occupancy[0] & square_bit(tile) != 0,
         }
+    }
 
-        for i in 0..dist_left {
-            left_moves.push((rank, file - i - 1));
-        }
+    // Returns the bitboard of every piece of the given colour and type
+    pub fn pieces_of(&self, white: bool, symbol: ChessPieces) -> Bitboard {
+        self.bitboards[colour_index(white)][symbol.index()]
+    }
 
-        left_moves
+    // Returns the bitboard of every tile with a piece of the given colour on it
+    pub fn pieces_of_colour(&self, white: bool) -> Bitboard {
+        self.occupancy[colour_index(white)]
     }
 
-    // Gets the valid positions to the right of a piece
-    pub fn valid_positions_right(
-        &self,
-        rank: usize,
-        file: usize,
-        white: bool,
-    ) -> Vec<(usize, usize)> {
-        let mut right_moves = vec![];
-        let piece_rank = self.pieces[rank];
+    // Returns the bitboard of every occupied tile
+    pub fn occupied(&self) -> Bitboard {
+        self.occupancy[0] | self.occupancy[1]
+    }
 
-        let whole_rank_right = &mut piece_rank.clone()[file + 1..];
-        let mut dist_right = whole_rank_right.len();
-        for (dist, piece) in whole_rank_right.iter().enumerate() {
-            if piece.symbol != ChessPieces::None {
-                dist_right = dist;
-                break;
-            }
-        }
+    // Returns how many pieces of the given type the given colour holds in its Crazyhouse pocket
+    pub fn pocket_count(&self, white: bool, symbol: ChessPieces) -> u8 {
+        self.pockets[colour_index(white)][symbol.pocket_index()]
+    }
+
+    // Returns the Zobrist hash of the position, which covers the pieces, side to move, castling rights and en passant file
+    pub fn hash(&self) -> u64 {
+        self.hash
+    }
 
-        if whole_rank_right.len() > 0 && dist_right < whole_rank_right.len() {
-            let last_piece = whole_rank_right[dist_right];
-            if last_piece.white != white {
-                dist_right += 1;
+    // Works out the Zobrist hash from scratch, make_move keeps it up to date incrementally instead
+    fn compute_hash(&self) -> u64 {
+        let mut hash = 0;
+        for white in [true, false] {
+            for symbol in PIECE_TYPES {
+                for square in squares(self.pieces_of(white, symbol)) {
+                    hash ^= zobrist::piece_key(colour_index(white), symbol.index(), square);
+                }
             }
         }
-
-        for i in 0..dist_right {
-            right_moves.push((rank, file + i + 1));
+        if !self.white_move {
+            hash ^= zobrist::side_key();
         }
-
-        right_moves
+        hash ^ self.castling.hash()
+            ^ self.en_passant_hash()
+            ^ self.checks_hash()
+            ^ self.pockets_hash()
     }
 
-    // Gets the valid positions above the piece
-    pub fn valid_positions_up(&self, rank: usize, file: usize, white: bool) -> Vec<(usize, usize)> {
-        let mut up_moves = vec![];
-        let piece_file = self.pieces.map(|rank| rank[file]);
-
-        let whole_file_up = &mut piece_file.clone()[..rank];
-        whole_file_up.reverse();
-        let mut dist_up = whole_file_up.len();
-        for (dist, piece) in whole_file_up.iter().enumerate() {
-            if piece.symbol != ChessPieces::None {
-                dist_up = dist;
-                break;
+    // The Crazyhouse part of the hash, covering how many of each piece both pockets hold
+    fn pockets_hash(&self) -> u64 {
+        let mut hash = 0;
+        for (colour, pocket) in self.pockets.iter().enumerate() {
+            for (piece, count) in pocket.iter().enumerate() {
+                hash ^= zobrist::pocket_key(colour, piece, *count);
             }
         }
+        hash
+    }
 
-        if whole_file_up.len() > 0 && dist_up < whole_file_up.len() {
-            let last_piece = whole_file_up[dist_up];
-            if last_piece.white != white {
-                dist_up += 1;
+    // The Three-check part of the hash, so that positions with different check counts are told apart
+    fn checks_hash(&self) -> u64 {
+        zobrist::checks_key(0, self.checks_given[0]) ^ zobrist::checks_key(1, self.checks_given[1])
+    }
+
+    // The en passant part of the hash, only counted when a pawn of the side to move stands ready to capture
+    fn en_passant_hash(&self) -> u64 {
+        match self.en_passant {
+            Some((rank, file))
+                if PAWN_ATTACKS[colour_index(!self.white_move)][square(rank, file)]
+                    & self.pieces_of(self.white_move, ChessPieces::Pawn)
+                    != 0 =>
+            {
+                zobrist::en_passant_key(file)
             }
+            _ => 0,
         }
+    }
 
-        for i in 0..dist_up {
-            up_moves.push((rank - i - 1, file));
-        }
+    // Puts a piece down on an empty square
+    fn put_piece(&mut self, square: Square, symbol: ChessPieces, white: bool) {
+        let bit = square_bit(square);
+        self.bitboards[colour_index(white)][symbol.index()] |= bit;
+        self.occupancy[colour_index(white)] |= bit;
+        self.squares[square] = symbol;
+        self.hash ^= zobrist::piece_key(colour_index(white), symbol.index(), square);
+    }
 
-        up_moves
+    // Takes the piece off an occupied square, returning its type and colour
+    fn remove_piece(&mut self, square: Square) -> (ChessPieces, bool) {
+        let bit = square_bit(square);
+        let symbol = self.squares[square];
+        let white = self.occupancy[0] & bit != 0;
+        self.bitboards[colour_index(white)][symbol.index()] &= !bit;
+        self.occupancy[colour_index(white)] &= !bit;
+        self.squares[square] = ChessPieces::None;
+        self.hash ^= zobrist::piece_key(colour_index(white), symbol.index(), square);
+        (symbol, white)
     }
 
-    // Gets the valid positions below the piece
-    pub fn valid_positions_down(
-        &self,
-        rank: usize,
-        file: usize,
-        white: bool,
-    ) -> Vec<(usize, usize)> {
-        let mut down_moves = vec![];
-        let piece_file = self.pieces.map(|rank| rank[file]);
+    // Moves a piece from one square to an empty square
+    fn shift_piece(&mut self, from: Square, to: Square) {
+        let (symbol, white) = self.remove_piece(from);
+        self.put_piece(to, symbol, white);
+    }
+
+    // Moves the king and rook for castling, both are lifted before either is put down since in Chess960 they can swap tiles
+    fn move_castling_pieces(&mut self, king: (Square, Square), rook: (Square, Square)) {
+        let (_, white) = self.remove_piece(king.0);
+        self.remove_piece(rook.0);
+        self.put_piece(king.1, ChessPieces::King, white);
+        self.put_piece(rook.1, ChessPieces::Rook, white);
+    }
+
+    // Checks whether any piece of the given colour attacks the square, by looking outwards from it with each piece's attacks
+    fn square_attacked(&self, square: Square, by_white: bool) -> bool {
+        let occupied = self.occupied();
+        let attackers = |symbol: ChessPieces| self.pieces_of(by_white, symbol);
+        let queens = attackers(ChessPieces::Queen);
+
+        PAWN_ATTACKS[colour_index(!by_white)][square] & attackers(ChessPieces::Pawn) != 0
+            || KNIGHT_ATTACKS[square] & attackers(ChessPieces::Knight) != 0
+            || self.variant.kings_capture()
+                && KING_ATTACKS[square] & attackers(ChessPieces::King) != 0
+            || bishop_attacks(square, occupied) & (attackers(ChessPieces::Bishop) | queens) != 0
+            || rook_attacks(square, occupied) & (attackers(ChessPieces::Rook) | queens) != 0
+    }
 
-        let whole_file_down = &mut piece_file.clone()[rank + 1..];
-        let mut dist_down = whole_file_down.len();
-        for (dist, piece) in whole_file_down.iter().enumerate() {
-            if piece.symbol != ChessPieces::None {
-                dist_down = dist;
-                break;
+    // Checks whether the king of the given colour is currently attacked, which never happens in Antichess
+    // In Atomic a king next to the enemy king is safe, since taking it would blow up the taker's own king
+    pub fn is_in_check(&self, white: bool) -> bool {
+        if self.variant == Variant::Antichess {
+            return false;
+        }
+        match squares(self.pieces_of(white, ChessPieces::King)).next() {
+            Some(king)
+                if self.variant == Variant::Atomic
+                    && KING_ATTACKS[king] & self.pieces_of(!white, ChessPieces::King) != 0 =>
+            {
+                false
             }
+            Some(king) => self.square_attacked(king, !white),
+            None => false,
         }
+    }
 
-        if whole_file_down.len() > 0 && dist_down < whole_file_down.len() {
-            let last_piece = whole_file_down[dist_down];
-            if last_piece.white != white {
-                dist_down += 1;
+    // Lists the tiles the given piece can legally move to, including castling for kings
+    pub fn filter_possible_moves(&self, origin_piece: Piece) -> Vec<(usize, usize)> {
+        let mut tiles = vec![];
+        for chess_move in self.legal_moves() {
+            let tile = self.target_tile(&chess_move);
+            if chess_move.from == (origin_piece.rank, origin_piece.file)
+                && !chess_move.drop
+                && !tiles.contains(&tile)
+            {
+                tiles.push(tile);
             }
         }
+        tiles
+    }
 
-        for i in 0..dist_down {
-            down_moves.push((rank + i + 1, file));
-        }
+    // Lists the tiles a piece of the given type can legally be dropped on from the mover's pocket
+    pub fn drop_tiles(&self, symbol: ChessPieces) -> Vec<(usize, usize)> {
+        self.legal_moves()
+            .into_iter()
+            .filter(|chess_move| chess_move.drop && chess_move.piece == symbol)
+            .map(|chess_move| chess_move.to)
+            .collect()
+    }
 
-        down_moves
+    // The tile a player picks to make the move, which for castling in Chess960 is the rook the king castles with
+    fn target_tile(&self, chess_move: &Move) -> (usize, usize) {
+        match chess_move.castle {
+            Some(side) if self.chess960 => match self.castling.for_side(self.white_move, side) {
+                Some(rook_file) => (chess_move.from.0, rook_file),
+                None => chess_move.to,
+            },
+            _ => chess_move.to,
+        }
     }
 
-    // Gets the valid positions in a y=x line going through the piece
-    pub fn valid_positions_diagonal_positive(
+    // Describes a move from one square to another, noting whatever stands on the target square as captured
+    fn new_move(
         &self,
-        rank: usize,
-        file: usize,
-        white: bool,
-    ) -> Vec<(usize, usize)> {
-        let mut moves = vec![];
-        let mut diagonal_top: Vec<(usize, usize)> = vec![];
-        let mut diagonal_bottom: Vec<(usize, usize)> = vec![];
-        for (i, _) in self.pieces.iter().enumerate() {
-            let offset = rank as i32 - i as i32;
-            let diagonal_file = file as i32 + offset;
-            if diagonal_file >= 0 && diagonal_file < 8 {
-                if i < rank {
-                    diagonal_top.push((i, diagonal_file as usize));
-                } else if i > rank {
-                    diagonal_bottom.push((i, diagonal_file as usize));
-                }
-            }
+        from: Square,
+        to: Square,
+        piece: ChessPieces,
+        promotion: Option<ChessPieces>,
+    ) -> Move {
+        let target = self.squares[to];
+        Move {
+            from: coordinates(from),
+            to: coordinates(to),
+            piece,
+            capture: (target != ChessPieces::None).then_some(target),
+            promotion,
+            castle: None,
+            en_passant: false,
+            drop: false,
         }
+    }
 
-        diagonal_top.reverse();
-        for tile in diagonal_top {
-            let query = self.query_board(tile.0, tile.1).0;
-            if query.symbol == ChessPieces::None {
-                moves.push(tile);
-            } else {
-                if query.white != white {
-                    moves.push(tile);
+    // Lists every move for the side to move that obeys how the pieces move, without checking whether it leaves the king in check
+    fn pseudo_legal_moves(&self) -> Vec<Move> {
+        let mut moves = Vec::with_capacity(64);
+        let own = self.pieces_of_colour(self.white_move);
+        let occupied = self.occupied();
+
+        for from in squares(own) {
+            let symbol = self.squares[from];
+            let targets = match symbol {
+                ChessPieces::Pawn => {
+                    self.add_pawn_moves(from, &mut moves);
+                    continue;
                 }
-                break;
+                ChessPieces::Knight => KNIGHT_ATTACKS[from],
+                ChessPieces::Bishop => bishop_attacks(from, occupied),
+                ChessPieces::Rook => rook_attacks(from, occupied),
+                ChessPieces::Queen => queen_attacks(from, occupied),
+                ChessPieces::King if !self.variant.kings_capture() => {
+                    KING_ATTACKS[from] & !occupied
+                }
+                ChessPieces::King => KING_ATTACKS[from],
+                ChessPieces::None => 0,
+            };
+            for to in squares(targets & !own) {
+                moves.push(self.new_move(from, to, symbol, None));
             }
         }
 
-        for tile in diagonal_bottom {
-            let query = self.query_board(tile.0, tile.1).0;
-            if query.symbol == ChessPieces::None {
-                moves.push(tile);
-            } else {
-                if query.white != white {
-                    moves.push(tile);
-                }
-                break;
-            }
+        if self.variant != Variant::Antichess {
+            self.add_castling_moves(&mut moves);
+        }
+        if self.variant == Variant::Crazyhouse {
+            self.add_drops(&mut moves);
         }
         moves
     }
 
-    // Gets the valid pieces in a y=-x line going through the piece
-    pub fn piece_moves_diagonal_negative(
-        &self,
-        rank: usize,
-        file: usize,
-        white: bool,
-    ) -> Vec<(usize, usize)> {
-        let mut moves = vec![];
-        let mut diagonal_top: Vec<(usize, usize)> = vec![];
-        let mut diagonal_bottom: Vec<(usize, usize)> = vec![];
-
-        let offset = (file as i32 - 3) + (3 - rank as i32);
-        for (i, _) in self.pieces.iter().enumerate() {
-            let tile_file = i as i32 + offset;
-            let tile_rank = i;
-            if tile_file >= 0 && tile_file < 8 {
-                if tile_rank < rank {
-                    diagonal_top.push((tile_rank as usize, tile_file as usize));
-                }
-                if tile_rank > rank {
-                    diagonal_bottom.push((tile_rank as usize, tile_file as usize));
-                }
+    // Adds a drop onto every empty tile for each piece in the mover's pocket, pawns may not be dropped on the first or last rank
+    fn add_drops(&self, moves: &mut Vec<Move>) {
+        let empty = !self.occupied();
+        for symbol in POCKET_PIECES {
+            if self.pocket_count(self.white_move, symbol) == 0 {
+                continue;
+            }
+            let targets = match symbol {
+                ChessPieces::Pawn => empty & !(rank_mask(0) | rank_mask(7)),
+                _ => empty,
+            };
+            for to in squares(targets) {
+                moves.push(Move {
+                    drop: true,
+                    ..self.new_move(to, to, symbol, None)
+                });
             }
         }
+    }
 
-        diagonal_top.reverse();
-        for tile in diagonal_top {
-            let query = self.query_board(tile.0, tile.1).0;
-            if query.symbol == ChessPieces::None {
-                moves.push(tile);
-            } else {
-                if query.white != white {
-                    moves.push(tile);
-                }
-                break;
+    // Adds the pushes, captures, en passant captures and promotions of the pawn on the given square
+    fn add_pawn_moves(&self, from: Square, moves: &mut Vec<Move>) {
+        let white = self.white_move;
+        let occupied = self.occupied();
+        let (start_rank, promotion_rank, en_passant_rank) =
+            if white { (6, 0, 2) } else { (1, 7, 5) };
+        // Pawns never stand on the back ranks, so one step forward stays on the board
+        let step = |square: Square| if white { square - 8 } else { square + 8 };
+
+        // Horde pawns on white's first rank may also step two tiles
+        let double_step =
+            from / 8 == start_rank || self.variant == Variant::Horde && white && from / 8 == 7;
+
+        let mut targets = PAWN_ATTACKS[colour_index(white)][from] & self.pieces_of_colour(!white);
+        let one_step = step(from);
+        if occupied & square_bit(one_step) == 0 {
+            targets |= square_bit(one_step);
+            if double_step && occupied & square_bit(step(one_step)) == 0 {
+                targets |= square_bit(step(one_step));
             }
         }
 
-        for tile in diagonal_bottom {
-            let query = self.query_board(tile.0, tile.1).0;
-            if query.symbol == ChessPieces::None {
-                moves.push(tile);
-            } else {
-                if query.white != white {
-                    moves.push(tile);
+        for to in squares(targets) {
+            if to / 8 == promotion_rank {
+                for promotion in self.variant.promotion_pieces() {
+                    moves.push(self.new_move(from, to, ChessPieces::Pawn, Some(*promotion)));
                 }
-                break;
+            } else {
+                moves.push(self.new_move(from, to, ChessPieces::Pawn, None));
             }
         }
 
-        moves
+        if let Some((rank, file)) = self.en_passant {
+            if rank == en_passant_rank
+                && PAWN_ATTACKS[colour_index(white)][from] & square_bit(square(rank, file)) != 0
+            {
+                moves.push(Move {
+                    capture: Some(ChessPieces::Pawn),
+                    en_passant: true,
+                    ..self.new_move(from, square(rank, file), ChessPieces::Pawn, None)
+                });
+            }
+        }
     }
 
-    // Calculates all possible moves for any given piece, returns a vector of coordinates
-    pub fn possible_moves(&self, origin_piece: Piece) -> Vec<(usize, usize)> {
-        match origin_piece.symbol {
-            ChessPieces::Pawn => {
-                let mut moves = vec![];
-
-                if origin_piece.white {
-                    let query_front_tile =
-                        self.query_board(origin_piece.rank - 1, origin_piece.file).0;
-                    if query_front_tile.symbol == ChessPieces::None {
-                        moves.push((origin_piece.rank - 1, origin_piece.file));
-                        if origin_piece.rank == 6 {
-                            let query_far_front_tile =
-                                self.query_board(origin_piece.rank - 2, origin_piece.file).0;
-                            if query_far_front_tile.symbol == ChessPieces::None {
-                                moves.push((origin_piece.rank - 2, origin_piece.file));
-                            }
-                        }
-                    }
-
-                    if origin_piece.rank > 0 {
-                        if origin_piece.file > 0 {
-                            let query_left_diagonal_tile = self
-                                .query_board(origin_piece.rank - 1, origin_piece.file - 1)
-                                .0;
-                            if query_left_diagonal_tile.white != origin_piece.white
-                                && query_left_diagonal_tile.symbol != ChessPieces::None
-                            {
-                                moves.push((origin_piece.rank - 1, origin_piece.file - 1));
-                            }
-                        }
-                        if origin_piece.file < 7 {
-                            let query_right_diagonal_tile = self
-                                .query_board(origin_piece.rank - 1, origin_piece.file + 1)
-                                .0;
-                            if query_right_diagonal_tile.white != origin_piece.white
-                                && query_right_diagonal_tile.symbol != ChessPieces::None
-                            {
-                                moves.push((origin_piece.rank - 1, origin_piece.file + 1));
-                            }
-                        }
-                    }
-                } else {
-                    let query_front_tile =
-                        self.query_board(origin_piece.rank + 1, origin_piece.file).0;
-                    if query_front_tile.symbol == ChessPieces::None {
-                        moves.push((origin_piece.rank + 1, origin_piece.file));
-                        if origin_piece.rank == 1 {
-                            let query_far_front_tile =
-                                self.query_board(origin_piece.rank + 2, origin_piece.file).0;
-                            if query_far_front_tile.symbol == ChessPieces::None {
-                                moves.push((origin_piece.rank + 2, origin_piece.file));
-                            }
-                        }
-                    }
-
-                    if origin_piece.rank < 7 {
-                        if origin_piece.file > 0 {
-                            let query_left_diagonal_tile = self
-                                .query_board(origin_piece.rank + 1, origin_piece.file - 1)
-                                .0;
-                            if query_left_diagonal_tile.white != origin_piece.white
-                                && query_left_diagonal_tile.symbol != ChessPieces::None
-                            {
-                                moves.push((origin_piece.rank + 1, origin_piece.file - 1));
-                            }
-                        }
-                        if origin_piece.file < 7 {
-                            let query_right_diagonal_tile = self
-                                .query_board(origin_piece.rank + 1, origin_piece.file + 1)
-                                .0;
-                            if query_right_diagonal_tile.white != origin_piece.white
-                                && query_right_diagonal_tile.symbol != ChessPieces::None
-                            {
-                                moves.push((origin_piece.rank + 1, origin_piece.file + 1));
-                            }
-                        }
-                    }
-                }
+    // Adds castling moves, the king may not start in or pass through an attacked tile, landing in check is left to the legality check
+    // In Chess960 the king and rook may start anywhere on the home rank but always end up where they would in standard chess
+    fn add_castling_moves(&self, moves: &mut Vec<Move>) {
+        let white = self.white_move;
+        let home_rank = if white { 7 } else { 0 };
+        let (king_side, queen_side) = self.castling.for_colour(white);
+        if king_side.is_none() && queen_side.is_none() {
+            return;
+        }
+        let king_square = match self.home_king_file(white) {
+            Some(file) => square(home_rank, file),
+            None => return,
+        };
+        if self.square_attacked(king_square, !white) {
+            return;
+        }
 
-                moves
+        let occupied = self.occupied();
+        for (side, rook_file) in [
+            (CastleSide::KingSide, king_side),
+            (CastleSide::QueenSide, queen_side),
+        ] {
+            let rook_square = match rook_file {
+                Some(file) => square(home_rank, file),
+                None => continue,
+            };
+            if self.pieces_of(white, ChessPieces::Rook) & square_bit(rook_square) == 0 {
+                continue;
             }
-            ChessPieces::Rook => {
-                let mut moves = vec![];
-
-                let mut left_moves = self.valid_positions_left(
-                    origin_piece.rank,
-                    origin_piece.file,
-                    origin_piece.white,
-                );
-                let mut right_moves = self.valid_positions_right(
-                    origin_piece.rank,
-                    origin_piece.file,
-                    origin_piece.white,
-                );
-                let mut up_moves = self.valid_positions_up(
-                    origin_piece.rank,
-                    origin_piece.file,
-                    origin_piece.white,
-                );
-                let mut down_moves = self.valid_positions_down(
-                    origin_piece.rank,
-                    origin_piece.file,
-                    origin_piece.white,
-                );
-
-                moves.append(&mut left_moves);
-                moves.append(&mut right_moves);
-                moves.append(&mut up_moves);
-                moves.append(&mut down_moves);
-
-                moves
-            }
-            ChessPieces::Knight => {
-                let mut moves = vec![];
-                let mut offsets: Vec<(i32, i32)> = vec![];
-                offsets.push((2, 1));
-                offsets.push((2, -1));
-                offsets.push((-2, 1));
-                offsets.push((-2, -1));
-
-                offsets.push((1, 2));
-                offsets.push((1, -2));
-                offsets.push((-1, 2));
-                offsets.push((-1, -2));
-
-                for (off_x, off_y) in offsets {
-                    let file = origin_piece.file as i32 + off_x;
-                    let rank = origin_piece.rank as i32 + off_y;
-                    if file >= 0 && rank >= 0 && file < 8 && rank < 8 {
-                        moves.push((rank as usize, file as usize));
-                    }
-                }
 
-                moves
+            let king_target = square(home_rank, side.king_file());
+            let rook_target = square(home_rank, side.rook_file());
+            let castling_pieces = square_bit(king_square) | square_bit(rook_square);
+            let path = span(king_square, king_target) | span(rook_square, rook_target);
+            if occupied & path & !castling_pieces != 0 {
+                continue;
             }
-            ChessPieces::Bishop => {
-                let mut moves = vec![];
-
-                let mut moves_positive_diag = self.valid_positions_diagonal_positive(
-                    origin_piece.rank,
-                    origin_piece.file,
-                    origin_piece.white,
-                );
-                let mut moves_negative_diag = self.piece_moves_diagonal_negative(
-                    origin_piece.rank,
-                    origin_piece.file,
-                    origin_piece.white,
-                );
 
-                moves.append(&mut moves_positive_diag);
-                moves.append(&mut moves_negative_diag);
-
-                moves
+            let passed_tiles = span(king_square, king_target)
+                & !square_bit(king_square)
+                & !square_bit(king_target);
+            if squares(passed_tiles).any(|tile| self.square_attacked(tile, !white)) {
+                continue;
             }
-            ChessPieces::Queen => {
-                let mut moves = vec![];
 
-                let mut left_moves = self.valid_positions_left(
-                    origin_piece.rank,
-                    origin_piece.file,
-                    origin_piece.white,
-                );
-                let mut right_moves = self.valid_positions_right(
-                    origin_piece.rank,
-                    origin_piece.file,
-                    origin_piece.white,
-                );
-                let mut up_moves = self.valid_positions_up(
-                    origin_piece.rank,
-                    origin_piece.file,
-                    origin_piece.white,
-                );
-                let mut down_moves = self.valid_positions_down(
-                    origin_piece.rank,
-                    origin_piece.file,
-                    origin_piece.white,
-                );
+            moves.push(Move {
+                from: coordinates(king_square),
+                to: coordinates(king_target),
+                piece: ChessPieces::King,
+                capture: None,
+                promotion: None,
+                castle: Some(side),
+                en_passant: false,
+                drop: false,
+            });
+        }
+    }
 
-                moves.append(&mut left_moves);
-                moves.append(&mut right_moves);
-                moves.append(&mut up_moves);
-                moves.append(&mut down_moves);
+    // Copies the position without the game history, for trying out moves cheaply
+    fn position_copy(&self) -> Self {
+        Self {
+            history: vec![],
+            start_fen: String::new(),
+            moves: vec![],
+            undo_stack: vec![],
+            redo_stack: vec![],
+            ..*self
+        }
+    }
 
-                let mut moves_positive_diag = self.valid_positions_diagonal_positive(
-                    origin_piece.rank,
-                    origin_piece.file,
-                    origin_piece.white,
-                );
-                let mut moves_negative_diag = self.piece_moves_diagonal_negative(
-                    origin_piece.rank,
-                    origin_piece.file,
-                    origin_piece.white,
-                );
+    // Describes moving the piece to (new_rank, new_file) as a Move, without checking that the move is legal
+    pub fn build_move(
+        &self,
+        piece: Piece,
+        new_rank: usize,
+        new_file: usize,
+        promotion: Option<ChessPieces>,
+    ) -> Move {
+        let target = self.piece_at(new_rank, new_file);
+        if let Some(side) = self.castle_side(piece, target) {
+            return Move {
+                from: (piece.rank, piece.file),
+                to: (piece.rank, side.king_file()),
+                piece: piece.symbol,
+                capture: None,
+                promotion: None,
+                castle: Some(side),
+                en_passant: false,
+                drop: false,
+            };
+        }
 
-                moves.append(&mut moves_positive_diag);
-                moves.append(&mut moves_negative_diag);
+        let en_passant = piece.symbol == ChessPieces::Pawn
+            && piece.file != new_file
+            && self.en_passant == Some((new_rank, new_file));
+        let capture = if en_passant {
+            Some(ChessPieces::Pawn)
+        } else if target.symbol != ChessPieces::None {
+            Some(target.symbol)
+        } else {
+            None
+        };
 
-                moves
-            }
-            ChessPieces::King => {
-                let mut moves = vec![];
-                let mut offsets = vec![];
-                offsets.push((-1, -1));
-                offsets.push((-1, 0));
-                offsets.push((-1, 1));
+        Move {
+            from: (piece.rank, piece.file),
+            to: (new_rank, new_file),
+            piece: piece.symbol,
+            capture,
+            promotion,
+            castle: None,
+            en_passant,
+            drop: false,
+        }
+    }
 
-                offsets.push((1, -1));
-                offsets.push((1, 0));
-                offsets.push((1, 1));
+    // Describes dropping a piece from the mover's pocket onto (rank, file), without checking that the drop is legal
+    pub fn build_drop(&self, symbol: ChessPieces, rank: usize, file: usize) -> Move {
+        Move {
+            from: (rank, file),
+            to: (rank, file),
+            piece: symbol,
+            capture: None,
+            promotion: None,
+            castle: None,
+            en_passant: false,
+            drop: true,
+        }
+    }
 
-                offsets.push((0, 1));
-                offsets.push((0, -1));
+    // Works out whether moving the piece to the target tile means castling, which in Chess960 is done by moving the king onto its own rook
+    // and otherwise by moving the king two tiles sideways
+    fn castle_side(&self, piece: Piece, target: Piece) -> Option<CastleSide> {
+        if piece.symbol != ChessPieces::King || piece.rank != target.rank {
+            return None;
+        }
 
-                for (off_x, off_y) in offsets {
-                    let file = origin_piece.file as i32 + off_x;
-                    let rank = origin_piece.rank as i32 + off_y;
-                    if file >= 0 && rank >= 0 && file < 8 && rank < 8 {
-                        moves.push((rank as usize, file as usize));
-                    }
-                }
-                moves
+        if self.chess960 {
+            if target.symbol != ChessPieces::Rook || target.white != piece.white {
+                return None;
+            }
+            let (king_side, queen_side) = self.castling.for_colour(piece.white);
+            if king_side == Some(target.file) {
+                Some(CastleSide::KingSide)
+            } else if queen_side == Some(target.file) {
+                Some(CastleSide::QueenSide)
+            } else {
+                None
             }
-            ChessPieces::None => {
-                vec![]
+        } else if piece.file.abs_diff(target.file) == 2 {
+            if target.file > piece.file {
+                Some(CastleSide::KingSide)
+            } else {
+                Some(CastleSide::QueenSide)
             }
+        } else {
+            None
         }
     }
 
-    // Filters down the possible moves so that they don't include your own pieces
-    pub fn filter_possible_moves(&self, origin_piece: Piece) -> Vec<(usize, usize)> {
-        let mut filtered_moves = vec![];
-        let possible_moves = self.possible_moves(origin_piece);
-        for tile in possible_moves {
-            let piece = self.query_board(tile.0, tile.1).0;
-            if piece.symbol == ChessPieces::None || piece.white != origin_piece.white {
-                filtered_moves.push(tile);
-            }
-        }
-        filtered_moves
-    }
-
-    // Moves a given piece from its original location to (new_rank, new_file), returns whether or not the piece captured a king
-    pub fn move_piece(&mut self, piece: Piece, new_rank: usize, new_file: usize) -> bool {
-        let possible_moves = self.filter_possible_moves(piece);
-        if possible_moves.contains(&(new_rank, new_file)) {
-            let piece_at_position = self.query_board(new_rank, new_file).0;
-            let old_rank = piece.rank;
-            let old_file = piece.file;
-            let moved_piece = Piece {
-                symbol: piece.symbol,
-                rank: new_rank,
-                file: new_file,
-                white: piece.white,
-            };
-            self.pieces[new_rank][new_file] = moved_piece;
-            self.pieces[old_rank][old_file] = Piece {
-                symbol: ChessPieces::None,
-                rank: old_rank,
-                file: old_file,
-                white: true,
-            };
-            self.white_move = !self.white_move;
-            return piece_at_position.symbol == ChessPieces::King;
-        }
-        self.moving = false;
-        return false;
-    }
-}
+    // Plays a move without checking it, bringing the rook along when castling, removing pawns taken en passant, promoting pawns and updating castling rights
+    // Also drops and pockets Crazyhouse pieces, sets off Atomic explosions and counts Three-check checks
+    // Returns what the move overwrote so that unmake_move can take it back
+    pub fn make_move(&mut self, chess_move: Move) -> UndoInfo {
+        let mut undo = UndoInfo {
+            castling: self.castling,
+            en_passant: self.en_passant,
+            halfmove_clock: self.halfmove_clock,
+            hash: self.hash,
+            checks_given: self.checks_given,
+            exploded: [None; 8],
+            pockets: self.pockets,
+            promoted: self.promoted,
+        };
+        let from = square(chess_move.from.0, chess_move.from.1);
+        let to = square(chess_move.to.0, chess_move.to.1);
 
-#[derive(Clone, Copy, Debug)]
-pub struct Piece {
-    pub symbol: ChessPieces,
-    pub file: usize,
-    pub rank: usize,
-    pub white: bool,
-}
+        let castling_rook = chess_move
+            .castle
+            .and_then(|side| self.castling.for_side(self.white_move, side));
 
-impl Piece {
+        self.hash ^= self.castling.hash() ^ self.en_passant_hash();
+        if chess_move.piece == ChessPieces::King {
+            self.castling.remove_for_colour(self.white_move);
+        }
+        self.castling
+            .remove_for_tile(chess_move.from.0, chess_move.from.1);
+        self.castling
+            .remove_for_tile(chess_move.to.0, chess_move.to.1);
+
+        if chess_move.piece == ChessPieces::Pawn || chess_move.capture.is_some() {
+            self.halfmove_clock = 0;
+        } else {
+            self.halfmove_clock += 1;
+        }
+        if !self.white_move {
+            self.fullmove_number += 1;
+        }
+
+        if self.variant == Variant::Crazyhouse {
+            self.update_pockets(&chess_move, from, to);
+        }
+
+        self.en_passant = None;
+        if chess_move.en_passant {
+            self.remove_piece(square(chess_move.from.0, chess_move.to.1));
+        } else if chess_move.capture.is_some() {
+            self.remove_piece(to);
+        }
+
+        match (chess_move.castle, castling_rook) {
+            (Some(side), Some(rook_file)) => {
+                let rank = chess_move.from.0;
+                self.move_castling_pieces(
+                    (from, to),
+                    (square(rank, rook_file), square(rank, side.rook_file())),
+                );
+            }
+            _ if chess_move.drop => self.put_piece(to, chess_move.piece, self.white_move),
+            _ => self.shift_piece(from, to),
+        }
+        if let Some(promotion) = chess_move.promotion {
+            self.remove_piece(to);
+            self.put_piece(to, promotion, self.white_move);
+        }
+
+        if self.variant == Variant::Atomic && chess_move.capture.is_some() {
+            undo.exploded = self.explode(to);
+        }
+
+        // Horde pawns stepping two tiles from the first rank can't be taken en passant
+        if chess_move.piece == ChessPieces::Pawn
+            && chess_move.from.0.abs_diff(chess_move.to.0) == 2
+            && (chess_move.from.0 == 1 || chess_move.from.0 == 6)
+        {
+            self.en_passant = Some(((chess_move.from.0 + chess_move.to.0) / 2, chess_move.from.1));
+        }
+
+        self.white_move = !self.white_move;
+        self.hash ^= self.castling.hash() ^ zobrist::side_key() ^ self.en_passant_hash();
+
+        if self.variant == Variant::ThreeCheck && self.is_in_check(self.white_move) {
+            self.hash ^= self.checks_hash();
+            self.checks_given[colour_index(!self.white_move)] += 1;
+            self.hash ^= self.checks_hash();
+        }
+        undo
+    }
+
+    // Moves pieces into and out of the pockets for a Crazyhouse move and keeps track of which pieces were promoted
+    // A captured piece joins the capturer's pocket, as a pawn if it had been promoted
+    fn update_pockets(&mut self, chess_move: &Move, from: Square, to: Square) {
+        self.hash ^= self.pockets_hash();
+        let mover = colour_index(self.white_move);
+        if let Some(capture) = chess_move.capture {
+            let pocketed = if self.promoted & square_bit(to) != 0 {
+                ChessPieces::Pawn
+            } else {
+                capture
+            };
+            self.pockets[mover][pocketed.pocket_index()] += 1;
+        }
+        if chess_move.drop {
+            self.pockets[mover][chess_move.piece.pocket_index()] -= 1;
+        }
+        self.hash ^= self.pockets_hash();
+
+        let was_promoted = self.promoted & square_bit(from) != 0;
+        self.promoted &= !(square_bit(from) | square_bit(to));
+        if was_promoted || chess_move.promotion.is_some() {
+            self.promoted |= square_bit(to);
+        }
+    }
+
+    // Blows up the capturing piece on the square along with every piece but pawns around it, losing any castling rights they held
+    // Returns the pieces around the square that were blown up, for unmake_move to put back
+    fn explode(&mut self, center: Square) -> [Option<(u8, ChessPieces, bool)>; 8] {
+        let mut exploded = [None; 8];
+        self.remove_piece(center);
+        let pawns =
+            self.pieces_of(true, ChessPieces::Pawn) | self.pieces_of(false, ChessPieces::Pawn);
+        let blast = KING_ATTACKS[center] & self.occupied() & !pawns;
+        for (slot, tile) in exploded.iter_mut().zip(squares(blast)) {
+            let (symbol, white) = self.remove_piece(tile);
+            let (rank, file) = coordinates(tile);
+            self.castling.remove_for_tile(rank, file);
+            if symbol == ChessPieces::King {
+                self.castling.remove_for_colour(white);
+            }
+            *slot = Some((tile as u8, symbol, white));
+        }
+        exploded
+    }
+
+    // Takes back a move played with make_move, given the state make_move returned
+    pub fn unmake_move(&mut self, chess_move: Move, undo: UndoInfo) {
+        self.white_move = !self.white_move;
+        let from = square(chess_move.from.0, chess_move.from.1);
+        let to = square(chess_move.to.0, chess_move.to.1);
+
+        let castling_rook = chess_move
+            .castle
+            .and_then(|side| undo.castling.for_side(self.white_move, side));
+
+        if self.variant == Variant::Atomic && chess_move.capture.is_some() {
+            for (tile, symbol, white) in undo.exploded.into_iter().flatten() {
+                self.put_piece(tile as Square, symbol, white);
+            }
+            let capturer = chess_move.promotion.unwrap_or(chess_move.piece);
+            self.put_piece(to, capturer, self.white_move);
+        }
+
+        match (chess_move.castle, castling_rook) {
+            (Some(side), Some(rook_file)) => {
+                let rank = chess_move.from.0;
+                self.move_castling_pieces(
+                    (to, from),
+                    (square(rank, side.rook_file()), square(rank, rook_file)),
+                );
+            }
+            _ if chess_move.drop => {
+                self.remove_piece(to);
+            }
+            _ if chess_move.promotion.is_some() => {
+                self.remove_piece(to);
+                self.put_piece(from, ChessPieces::Pawn, self.white_move);
+            }
+            _ => self.shift_piece(to, from),
+        }
+
+        if chess_move.en_passant {
+            self.put_piece(
+                square(chess_move.from.0, chess_move.to.1),
+                ChessPieces::Pawn,
+                !self.white_move,
+            );
+        } else if let Some(capture) = chess_move.capture {
+            self.put_piece(to, capture, !self.white_move);
+        }
+
+        if !self.white_move {
+            self.fullmove_number -= 1;
+        }
+        self.castling = undo.castling;
+        self.en_passant = undo.en_passant;
+        self.halfmove_clock = undo.halfmove_clock;
+        self.hash = undo.hash;
+        self.checks_given = undo.checks_given;
+        self.pockets = undo.pockets;
+        self.promoted = undo.promoted;
+    }
+
+    // Checks whether moving the piece onto the given rank would promote it
+    pub fn is_promotion(&self, piece: Piece, new_rank: usize) -> bool {
+        piece.symbol == ChessPieces::Pawn && new_rank == if piece.white { 0 } else { 7 }
+    }
+
+    // Lists every legal move for the side to move, with one move per promotion choice
+    pub fn legal_moves(&self) -> Vec<Move> {
+        self.position_copy().generate_legal_moves()
+    }
+
+    // Keeps the pseudo legal moves that don't leave the mover's own king in check, trying each one out with make and unmake
+    // In Antichess nothing is ever in check, but a capture has to be made whenever one can be
+    pub fn generate_legal_moves(&mut self) -> Vec<Move> {
+        let white = self.white_move;
+        let mut moves = self.pseudo_legal_moves();
+        if self.variant == Variant::Antichess {
+            if moves.iter().any(|chess_move| chess_move.capture.is_some()) {
+                moves.retain(|chess_move| chess_move.capture.is_some());
+            }
+            return moves;
+        }
+
+        moves.retain(|chess_move| {
+            let undo = self.make_move(*chess_move);
+            let legal = self.king_survived(white);
+            self.unmake_move(*chess_move, undo);
+            legal
+        });
+        moves
+    }
+
+    // Checks whether the king of the given colour came through the move just made safely
+    // In Atomic it must not have been blown up, but leaving it attacked is fine when the enemy king was blown up instead
+    fn king_survived(&self, white: bool) -> bool {
+        match self.variant {
+            Variant::Atomic => {
+                self.pieces_of(white, ChessPieces::King) != 0
+                    && (self.pieces_of(!white, ChessPieces::King) == 0 || !self.is_in_check(white))
+            }
+            _ => !self.is_in_check(white),
+        }
+    }
+
+    // Plays the move if it is legal and records the new position, returns whether or not the move was made
+    pub fn play_move(&mut self, chess_move: Move) -> bool {
+        if !self.legal_moves().contains(&chess_move) {
+            return false;
+        }
+        self.redo_stack.clear();
+        self.record_move(chess_move);
+        true
+    }
+
+    // Plays an already validated move and adds it to the game's history
+    fn record_move(&mut self, chess_move: Move) {
+        let undo = self.make_move(chess_move);
+        self.history.push(self.hash());
+        self.moves.push(chess_move);
+        self.undo_stack.push(undo);
+    }
+
+    // Takes back the last move played, returning it, or None at the start of the game
+    pub fn undo(&mut self) -> Option<Move> {
+        let chess_move = self.moves.pop()?;
+        let undo = self.undo_stack.pop()?;
+        self.unmake_move(chess_move, undo);
+        self.history.pop();
+        self.claimed_draw = None;
+        self.redo_stack.push(chess_move);
+        Some(chess_move)
+    }
+
+    // Plays the last move taken back again, returning it, or None if there is nothing to redo
+    pub fn redo(&mut self) -> Option<Move> {
+        let chess_move = self.redo_stack.pop()?;
+        self.record_move(chess_move);
+        Some(chess_move)
+    }
+
+    // Counts the leaf nodes of the legal move tree to the given depth, used to check move generation
+    pub fn perft(&self, depth: u32) -> u64 {
+        self.position_copy().perft_nodes(depth)
+    }
+
+    fn perft_nodes(&mut self, depth: u32) -> u64 {
+        if depth == 0 {
+            return 1;
+        }
+
+        let moves = self.generate_legal_moves();
+        if depth == 1 {
+            return moves.len() as u64;
+        }
+
+        let mut nodes = 0;
+        for chess_move in moves {
+            let undo = self.make_move(chess_move);
+            nodes += self.perft_nodes(depth - 1);
+            self.unmake_move(chess_move, undo);
+        }
+        nodes
+    }
+
+    // Splits the perft count by the first move, to help find which move generation is wrong
+    pub fn divide(&self, depth: u32) -> Vec<(Move, u64)> {
+        if depth == 0 {
+            return Vec::new();
+        }
+
+        let mut board = self.position_copy();
+        board
+            .generate_legal_moves()
+            .into_iter()
+            .map(|chess_move| {
+                let undo = board.make_move(chess_move);
+                let nodes = board.perft_nodes(depth - 1);
+                board.unmake_move(chess_move, undo);
+                (chess_move, nodes)
+            })
+            .collect()
+    }
+
+    // Writes the move in Standard Algebraic Notation, the move must be legal in the current position
+    pub fn to_san(&self, chess_move: &Move) -> String {
+        let mut san = match chess_move.castle {
+            Some(CastleSide::KingSide) => "O-O".to_string(),
+            Some(CastleSide::QueenSide) => "O-O-O".to_string(),
+            None if chess_move.drop => format!(
+                "{}@{}",
+                chess_move.piece.san_letter().unwrap_or('P'),
+                tile_to_algebraic(chess_move.to.0, chess_move.to.1)
+            ),
+            None => {
+                let mut san = String::new();
+                let from_name = tile_to_algebraic(chess_move.from.0, chess_move.from.1);
+                let (from_file, from_rank) = from_name.split_at(1);
+
+                match chess_move.piece.san_letter() {
+                    Some(letter) => {
+                        san.push(letter);
+                        let rivals = self
+                            .legal_moves()
+                            .into_iter()
+                            .filter(|other| {
+                                other.piece == chess_move.piece
+                                    && other.to == chess_move.to
+                                    && other.from != chess_move.from
+                                    && !other.drop
+                            })
+                            .collect::<Vec<_>>();
+                        if !rivals.is_empty() {
+                            if rivals.iter().all(|other| other.from.1 != chess_move.from.1) {
+                                san.push_str(from_file);
+                            } else if rivals.iter().all(|other| other.from.0 != chess_move.from.0) {
+                                san.push_str(from_rank);
+                            } else {
+                                san.push_str(&from_name);
+                            }
+                        }
+                    }
+                    None => {
+                        if chess_move.capture.is_some() {
+                            san.push_str(from_file);
+                        }
+                    }
+                }
+
+                if chess_move.capture.is_some() {
+                    san.push('x');
+                }
+                san.push_str(&tile_to_algebraic(chess_move.to.0, chess_move.to.1));
+                if let Some(promotion) = chess_move.promotion {
+                    san.push('=');
+                    san.push(promotion.san_letter().unwrap_or('Q'));
+                }
+                san
+            }
+        };
+
+        let mut next_board = self.position_copy();
+        next_board.make_move(*chess_move);
+        if next_board.is_in_check(next_board.white_move) {
+            if next_board.has_legal_moves() {
+                san.push('+');
+            } else {
+                san.push('#');
+            }
+        }
+        san
+    }
+
+    // Reads a move written in Standard Algebraic Notation, such as "Nf3", "exd5", "O-O", "e8=Q+" or the Crazyhouse drop "N@f3"
+    pub fn parse_san(&self, text: &str) -> Result<Move, MoveParseError> {
+        let unparseable = || MoveParseError::Unparseable(text.to_string());
+        let san = text.trim().trim_end_matches(['+', '#', '!', '?']);
+
+        let candidates = match san {
+            "O-O" | "0-0" => self
+                .legal_moves()
+                .into_iter()
+                .filter(|chess_move| chess_move.castle == Some(CastleSide::KingSide))
+                .collect::<Vec<_>>(),
+            "O-O-O" | "0-0-0" => self
+                .legal_moves()
+                .into_iter()
+                .filter(|chess_move| chess_move.castle == Some(CastleSide::QueenSide))
+                .collect::<Vec<_>>(),
+            _ if san.contains('@') => {
+                static DROP: OnceLock<Regex> = OnceLock::new();
+                let re = DROP.get_or_init(|| Regex::new("^([QRBNP])?@([a-h][1-8])$").unwrap());
+                let captures = re.captures(san).ok_or_else(unparseable)?;
+                let piece = match captures.get(1) {
+                    Some(letter) => ChessPieces::from_fen(letter.as_str().chars().next().unwrap())
+                        .ok_or_else(unparseable)?,
+                    None => ChessPieces::Pawn,
+                };
+                let to = tile_from_algebraic(&captures[2]).ok_or_else(unparseable)?;
+                self.legal_moves()
+                    .into_iter()
+                    .filter(|chess_move| {
+                        chess_move.drop && chess_move.piece == piece && chess_move.to == to
+                    })
+                    .collect::<Vec<_>>()
+            }
+            _ => {
+                static MOVE: OnceLock<Regex> = OnceLock::new();
+                let re = MOVE.get_or_init(|| {
+                    Regex::new("^([KQRBN])?([a-h])?([1-8])?x?([a-h][1-8])(?:=?([QRBNKqrbnk]))?$")
+                        .unwrap()
+                });
+                let captures = re.captures(san).ok_or_else(unparseable)?;
+
+                let piece = match captures.get(1) {
+                    Some(letter) => ChessPieces::from_fen(letter.as_str().chars().next().unwrap())
+                        .ok_or_else(unparseable)?,
+                    None => ChessPieces::Pawn,
+                };
+                let from_file = captures
+                    .get(2)
+                    .map(|file| file.as_str().as_bytes()[0] as usize - 'a' as usize);
+                let from_rank = captures
+                    .get(3)
+                    .map(|rank| 8 - rank.as_str().parse::<usize>().unwrap());
+                let to = tile_from_algebraic(&captures[4]).ok_or_else(unparseable)?;
+                let promotion = match captures.get(5) {
+                    Some(letter) => Some(
+                        ChessPieces::from_fen(letter.as_str().chars().next().unwrap())
+                            .ok_or_else(unparseable)?,
+                    ),
+                    None => None,
+                };
+
+                // A pawn reaching the last rank has to name its piece, so "a8" on its own is illegal rather than ambiguous
+                self.legal_moves()
+                    .into_iter()
+                    .filter(|chess_move| {
+                        chess_move.piece == piece
+                            && chess_move.to == to
+                            && chess_move.castle.is_none()
+                            && !chess_move.drop
+                            && from_file.is_none_or(|file| chess_move.from.1 == file)
+                            && from_rank.is_none_or(|rank| chess_move.from.0 == rank)
+                            && chess_move.promotion == promotion
+                    })
+                    .collect::<Vec<_>>()
+            }
+        };
+
+        match candidates.len() {
+            0 => Err(MoveParseError::Illegal(text.to_string())),
+            1 => Ok(candidates[0]),
+            _ => Err(MoveParseError::Ambiguous(text.to_string())),
+        }
+    }
+
+    // Reads a move written in UCI long algebraic notation, such as "e2e4" or "e7e8q", Antichess promotions to a king end in "k"
+    pub fn parse_uci(&self, text: &str) -> Result<Move, MoveParseError> {
+        if text.contains('@') {
+            return self.parse_san(text);
+        }
+        static UCI: OnceLock<Regex> = OnceLock::new();
+        let re = UCI.get_or_init(|| Regex::new("^([a-h][1-8])([a-h][1-8])([qrbnk])?$").unwrap());
+        let captures = re
+            .captures(text.trim())
+            .ok_or_else(|| MoveParseError::Unparseable(text.to_string()))?;
+        let from = tile_from_algebraic(&captures[1]).unwrap();
+        let to = tile_from_algebraic(&captures[2]).unwrap();
+        let promotion = captures
+            .get(3)
+            .and_then(|letter| ChessPieces::from_fen(letter.as_str().chars().next().unwrap()));
+
+        self.legal_moves()
+            .into_iter()
+            .find(|chess_move| {
+                chess_move.from == from
+                    && !chess_move.drop
+                    && self.target_tile(chess_move) == to
+                    && chess_move.promotion == promotion
+            })
+            .ok_or_else(|| MoveParseError::Illegal(text.to_string()))
+    }
+
+    // Writes a move of the side to move in UCI long algebraic notation, such as "e2e4" or "e7e8q"
+    // Castling in Chess960 is written as the king taking its own rook, such as "e1h1", and Crazyhouse drops like "N@f3"
+    pub fn to_uci(&self, chess_move: &Move) -> String {
+        if chess_move.drop {
+            return self
+                .to_san(chess_move)
+                .trim_end_matches(['+', '#'])
+                .to_string();
+        }
+        let to = self.target_tile(chess_move);
+        let mut uci = format!(
+            "{}{}",
+            tile_to_algebraic(chess_move.from.0, chess_move.from.1),
+            tile_to_algebraic(to.0, to.1)
+        );
+        if let Some(promotion) = chess_move.promotion {
+            uci.push(promotion.san_letter().unwrap_or('q').to_ascii_lowercase());
+        }
+        uci
+    }
+
+    // Checks whether the side to move has at least one legal move
+    pub fn has_legal_moves(&self) -> bool {
+        !self.legal_moves().is_empty()
+    }
+
+    // Works out the state of the game from the point of view of the side to move
+    pub fn game_state(&self) -> GameState {
+        if let Some(state) = self.variant_result() {
+            return state;
+        }
+
+        let in_check = self.is_in_check(self.white_move);
+        let can_move = self.has_legal_moves();
+        match (in_check, can_move) {
+            // In Antichess running out of moves, pieces included, wins
+            (_, false) if self.variant == Variant::Antichess => {
+                let reason = if self.pieces_of_colour(self.white_move) == 0 {
+                    WinReason::LostAllPieces
+                } else {
+                    WinReason::NoMovesLeft
+                };
+                return GameState::Won {
+                    white: self.white_move,
+                    reason,
+                };
+            }
+            (true, false) => return GameState::Checkmate,
+            (false, false) => return GameState::Stalemate,
+            _ => {}
+        }
+
+        if self.halfmove_clock >= 150 {
+            return GameState::Draw(DrawReason::SeventyFiveMoveRule);
+        }
+        if self.repetition_count() >= 5 {
+            return GameState::Draw(DrawReason::FivefoldRepetition);
+        }
+        if self.is_insufficient_material() {
+            return GameState::Draw(DrawReason::InsufficientMaterial);
+        }
+        if let Some(reason) = self.claimed_draw {
+            return GameState::Draw(reason);
+        }
+
+        if in_check {
+            GameState::Check
+        } else {
+            GameState::Ongoing
+        }
+    }
+
+    // Checks for the ways the variant being played can be won besides checkmate or, in Antichess, running out of moves
+    // Only looks at the pieces and counters, so it is cheap enough to call at every node of a search
+    pub fn variant_result(&self) -> Option<GameState> {
+        let mover = !self.white_move;
+        let won = |white: bool, reason: WinReason| GameState::Won { white, reason };
+        match self.variant {
+            Variant::Standard | Variant::Crazyhouse | Variant::Antichess => None,
+            Variant::KingOfTheHill => (self.pieces_of(mover, ChessPieces::King) & HILL != 0)
+                .then(|| won(mover, WinReason::KingOfTheHill)),
+            Variant::ThreeCheck => (self.checks_given[colour_index(mover)] >= 3)
+                .then(|| won(mover, WinReason::ThreeChecks)),
+            Variant::Atomic => (self.pieces_of(self.white_move, ChessPieces::King) == 0)
+                .then(|| won(mover, WinReason::KingExploded)),
+            Variant::Horde => {
+                (self.pieces_of_colour(true) == 0).then(|| won(false, WinReason::HordeDestroyed))
+            }
+        }
+    }
+
+    // Sets up the position the game started from again, under the same rules
+    pub fn starting_board(&self) -> Self {
+        let mut board = Board::from_variant_fen(&self.start_fen, self.variant).unwrap();
+        board.chess960 = self.chess960;
+        board
+    }
+
+    // Counts how many times the current position has been reached, positions count as the same when their hashes match
+    pub fn repetition_count(&self) -> usize {
+        self.history
+            .iter()
+            .filter(|hash| **hash == self.hash())
+            .count()
+    }
+
+    // Checks whether neither side has enough pieces left to ever checkmate, or to win some other way in a variant
+    pub fn is_insufficient_material(&self) -> bool {
+        match self.variant {
+            Variant::Standard => {}
+            // Bare kings can neither give check nor blow anything up
+            Variant::ThreeCheck | Variant::Atomic => return self.occupied().count_ones() == 2,
+            // A lone king can still walk to the hill, captured pieces come back in Crazyhouse,
+            // and the other variants are won by losing pieces
+            Variant::KingOfTheHill | Variant::Crazyhouse | Variant::Antichess | Variant::Horde => {
+                return false
+            }
+        }
+
+        let both =
+            |symbol: ChessPieces| self.pieces_of(true, symbol) | self.pieces_of(false, symbol);
+        if both(ChessPieces::Queen) | both(ChessPieces::Rook) | both(ChessPieces::Pawn) != 0 {
+            return false;
+        }
+
+        let knights = both(ChessPieces::Knight);
+        let bishops = both(ChessPieces::Bishop);
+        // Bishops that all stand on tiles of one colour can never reach the other colour
+        let light_tiles: Bitboard = 0xaa55_aa55_aa55_aa55;
+        match (knights | bishops).count_ones() {
+            0 | 1 => true,
+            _ => knights == 0 && (bishops & light_tiles == 0 || bishops & !light_tiles == 0),
+        }
+    }
+
+    // Returns the draw the side to move could claim right now, if any
+    pub fn claimable_draw(&self) -> Option<DrawReason> {
+        if self.repetition_count() >= 3 {
+            Some(DrawReason::ThreefoldRepetition)
+        } else if self.halfmove_clock >= 100 {
+            Some(DrawReason::FiftyMoveRule)
+        } else {
+            None
+        }
+    }
+
+    // Ends the game in a draw if one can be claimed, returns whether or not the claim succeeded
+    pub fn claim_draw(&mut self) -> bool {
+        self.claimed_draw = self.claimable_draw();
+        self.claimed_draw.is_some()
+    }
+}
+
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub struct Move {
+    pub from: (usize, usize),
+    pub to: (usize, usize),
+    pub piece: ChessPieces,
+    pub capture: Option<ChessPieces>,
+    pub promotion: Option<ChessPieces>,
+    pub castle: Option<CastleSide>,
+    pub en_passant: bool,
+    // The piece comes out of the mover's Crazyhouse pocket onto the empty tile `to`, which `from` is set to as well
+    pub drop: bool,
+}
+
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum CastleSide {
+    KingSide,
+    QueenSide,
+}
+
+impl CastleSide {
+    // Returns the file the king ends up on when castling this way, wherever it started
+    fn king_file(self) -> usize {
+        match self {
+            CastleSide::KingSide => 6,
+            CastleSide::QueenSide => 2,
+        }
+    }
+
+    // Returns the file the rook ends up on when castling this way, wherever it started
+    fn rook_file(self) -> usize {
+        match self {
+            CastleSide::KingSide => 5,
+            CastleSide::QueenSide => 3,
+        }
+    }
+}
+
+// The parts of the position a move overwrites that can't be worked out from the move itself
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub struct UndoInfo {
+    pub castling: CastlingRights,
+    pub en_passant: Option<(usize, usize)>,
+    pub halfmove_clock: u32,
+    pub hash: u64,
+    pub checks_given: [u32; 2],
+    // The square, type and colour of each piece an Atomic capture blew up around the capturing tile
+    pub exploded: [Option<(u8, ChessPieces, bool)>; 8],
+    pub pockets: [[u8; 5]; 2],
+    pub promoted: Bitboard,
+}
+
+#[derive(Debug, PartialEq)]
+pub enum MoveParseError {
+    // More than one legal move fits the text
+    Ambiguous(String),
+    // The text is well formed but no legal move fits it
+    Illegal(String),
+    // The text is not a move at all
+    Unparseable(String),
+}
+
+impl std::fmt::Display for MoveParseError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            MoveParseError::Ambiguous(text) => {
+                write!(f, "'{}' could mean more than one move", text)
+            }
+            MoveParseError::Illegal(text) => write!(f, "'{}' is not a legal move here", text),
+            MoveParseError::Unparseable(text) => write!(f, "'{}' is not a move", text),
+        }
+    }
+}
+
+impl std::error::Error for MoveParseError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        None
+    }
+}
+
+// Converts a tile name such as "e3" into (rank, file) coordinates
+pub fn tile_from_algebraic(name: &str) -> Option<(usize, usize)> {
+    let mut chars = name.chars();
+    let file = chars.next()?;
+    let rank = chars.next()?.to_digit(10)? as usize;
+    if chars.next().is_some() || !('a'..='h').contains(&file) || !(1..=8).contains(&rank) {
+        return None;
+    }
+    Some((8 - rank, file as usize - 'a' as usize))
+}
+
+// Converts (rank, file) coordinates into a tile name such as "e3"
+pub fn tile_to_algebraic(rank: usize, file: usize) -> String {
+    format!("{}{}", (b'a' + file as u8) as char, 8 - rank)
+}
+
+#[derive(Debug)]
+pub enum FenError {
+    WrongFieldCount(usize),
+    WrongRankCount(usize),
+    WrongRankLength { rank: usize, length: usize },
+    AdjacentBlanks { rank: usize },
+    UnknownPiece(char),
+    InvalidSideToMove(String),
+    InvalidCastling(String),
+    InvalidEnPassant(String),
+    InvalidHalfmoveClock(String),
+    InvalidFullmoveNumber(String),
+    MissingKing { white: bool },
+    TooManyKings { white: bool },
+    PawnOnBackRank(String),
+    OpponentInCheck,
+    InvalidCheckCount(String),
+    InvalidPocket(String),
+}
+
+impl std::fmt::Display for FenError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let colour = |white: &bool| if *white { "White" } else { "Black" };
+        match self {
+            FenError::WrongFieldCount(count) => {
+                write!(
+                    f,
+                    "FEN error: expected 6 fields (or 4 without the clocks) but found {}",
+                    count
+                )
+            }
+            FenError::WrongRankCount(count) => {
+                write!(f, "FEN error: expected 8 ranks but found {}", count)
+            }
+            FenError::WrongRankLength { rank, length } => write!(
+                f,
+                "FEN error: rank {} describes {} files instead of 8",
+                rank, length
+            ),
+            FenError::AdjacentBlanks { rank } => write!(
+                f,
+                "FEN error: rank {} has two numbers of empty tiles in a row",
+                rank
+            ),
+            FenError::UnknownPiece(char) => {
+                write!(
+                    f,
+                    "FEN error: '{}' is not a piece or a number of empty tiles",
+                    char
+                )
+            }
+            FenError::InvalidSideToMove(field) => {
+                write!(
+                    f,
+                    "FEN error: side to move must be 'w' or 'b', not '{}'",
+                    field
+                )
+            }
+            FenError::InvalidCastling(field) => write!(
+                f,
+                "FEN error: castling rights '{}' are malformed or do not match the kings and rooks",
+                field
+            ),
+            FenError::InvalidEnPassant(field) => write!(
+                f,
+                "FEN error: '{}' is not a possible en passant tile for the side to move",
+                field
+            ),
+            FenError::InvalidHalfmoveClock(field) => {
+                write!(f, "FEN error: halfmove clock '{}' is not a number", field)
+            }
+            FenError::InvalidFullmoveNumber(field) => write!(
+                f,
+                "FEN error: fullmove number '{}' is not a positive number",
+                field
+            ),
+            FenError::MissingKing { white } => {
+                write!(f, "FEN error: {} has no king", colour(white))
+            }
+            FenError::TooManyKings { white } => {
+                write!(f, "FEN error: {} has too many kings", colour(white))
+            }
+            FenError::PawnOnBackRank(tile) => {
+                write!(f, "FEN error: there is a pawn on the back rank at {}", tile)
+            }
+            FenError::OpponentInCheck => {
+                write!(f, "FEN error: the side that just moved is still in check")
+            }
+            FenError::InvalidCheckCount(field) => write!(
+                f,
+                "FEN error: '{}' is not a count of checks given such as '+1+0'",
+                field
+            ),
+            FenError::InvalidPocket(field) => write!(
+                f,
+                "FEN error: pocket '[{}' should list pieces other than kings and end with ']'",
+                field
+            ),
+        }
+    }
+}
+
+impl std::error::Error for FenError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        None
+    }
+}
+
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub struct CastlingRights {
+    // The file of the rook each right castles with, None once the right is lost
+    pub white_king_side: Option<usize>,
+    pub white_queen_side: Option<usize>,
+    pub black_king_side: Option<usize>,
+    pub black_queen_side: Option<usize>,
+}
+
+impl CastlingRights {
     pub fn none() -> Self {
         Self {
-            symbol: ChessPieces::None,
-            file: 0,
-            rank: 0,
-            white: true,
+            white_king_side: None,
+            white_queen_side: None,
+            black_king_side: None,
+            black_queen_side: None,
+        }
+    }
+
+    // Returns the rook files in the order white king side, white queen side, black king side, black queen side
+    fn all(self) -> [Option<usize>; 4] {
+        [
+            self.white_king_side,
+            self.white_queen_side,
+            self.black_king_side,
+            self.black_queen_side,
+        ]
+    }
+
+    // The castling part of a position's Zobrist hash
+    fn hash(self) -> u64 {
+        self.all()
+            .iter()
+            .enumerate()
+            .filter(|(_, right)| right.is_some())
+            .fold(0, |hash, (index, _)| hash ^ zobrist::castling_key(index))
+    }
+
+    // Returns the rook files of the (king side, queen side) rights of the given colour
+    pub fn for_colour(&self, white: bool) -> (Option<usize>, Option<usize>) {
+        if white {
+            (self.white_king_side, self.white_queen_side)
+        } else {
+            (self.black_king_side, self.black_queen_side)
+        }
+    }
+
+    // Returns the rook file of the right of the given colour and side
+    pub fn for_side(&self, white: bool, side: CastleSide) -> Option<usize> {
+        let (king_side, queen_side) = self.for_colour(white);
+        match side {
+            CastleSide::KingSide => king_side,
+            CastleSide::QueenSide => queen_side,
+        }
+    }
+
+    // Returns the right of the given colour and side, for changing it
+    fn side_mut(&mut self, white: bool, side: CastleSide) -> &mut Option<usize> {
+        match (white, side) {
+            (true, CastleSide::KingSide) => &mut self.white_king_side,
+            (true, CastleSide::QueenSide) => &mut self.white_queen_side,
+            (false, CastleSide::KingSide) => &mut self.black_king_side,
+            (false, CastleSide::QueenSide) => &mut self.black_queen_side,
+        }
+    }
+
+    // Clears both rights of the given colour, used whenever its king moves
+    fn remove_for_colour(&mut self, white: bool) {
+        if white {
+            self.white_king_side = None;
+            self.white_queen_side = None;
+        } else {
+            self.black_king_side = None;
+            self.black_queen_side = None;
+        }
+    }
+
+    // Clears any right that castles with a rook starting on (rank, file), used whenever a piece leaves or is captured on that tile
+    fn remove_for_tile(&mut self, rank: usize, file: usize) {
+        let white = match rank {
+            7 => true,
+            0 => false,
+            _ => return,
+        };
+        for side in [CastleSide::KingSide, CastleSide::QueenSide] {
+            let right = self.side_mut(white, side);
+            if *right == Some(file) {
+                *right = None;
+            }
+        }
+    }
+}
+
+// The rules a game can be played under, each can change how pieces move, how the game is won and where it starts
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum Variant {
+    Standard,
+    // Bringing the king to one of the four centre tiles wins
+    KingOfTheHill,
+    // Giving check for the third time wins
+    ThreeCheck,
+    // Captured pieces change colour and can be dropped back onto the board by the side that captured them
+    Crazyhouse,
+    // Captures are forced, the king is an ordinary piece and losing every piece wins
+    Antichess,
+    // A capture blows up the capturing piece and every piece but pawns around it, blowing up the enemy king wins
+    Atomic,
+    // White has a horde of pawns and no king, black wins by capturing all of them
+    Horde,
+}
+
+impl Variant {
+    // Every variant in the order the game screen cycles through them
+    pub const ALL: [Variant; 7] = [
+        Variant::Standard,
+        Variant::KingOfTheHill,
+        Variant::ThreeCheck,
+        Variant::Crazyhouse,
+        Variant::Antichess,
+        Variant::Atomic,
+        Variant::Horde,
+    ];
+
+    // Returns the name of the variant, as written in a PGN Variant tag
+    pub fn name(self) -> &'static str {
+        match self {
+            Variant::Standard => "Standard",
+            Variant::KingOfTheHill => "King of the Hill",
+            Variant::ThreeCheck => "Three-check",
+            Variant::Crazyhouse => "Crazyhouse",
+            Variant::Antichess => "Antichess",
+            Variant::Atomic => "Atomic",
+            Variant::Horde => "Horde",
+        }
+    }
+
+    // Finds a variant by name, ignoring case, spaces and dashes, so "three-check" and "kingofthehill" both work
+    pub fn from_name(name: &str) -> Option<Self> {
+        let simplify = |name: &str| {
+            name.chars()
+                .filter(|char| char.is_ascii_alphanumeric())
+                .collect::<String>()
+                .to_ascii_lowercase()
+        };
+        Variant::ALL
+            .into_iter()
+            .find(|variant| simplify(variant.name()) == simplify(name))
+    }
+
+    // Returns a one line summary of how the variant differs from standard chess
+    pub fn rules(self) -> &'static str {
+        match self {
+            Variant::Standard => "checkmate the enemy king",
+            Variant::KingOfTheHill => "bring your king to one of the four centre tiles",
+            Variant::ThreeCheck => "give check three times",
+            Variant::Crazyhouse => "captured pieces can be dropped back in as your own",
+            Variant::Antichess => "captures are forced, lose all your pieces",
+            Variant::Atomic => "captures explode, blow up the enemy king",
+            Variant::Horde => "the pawn horde has no king, red must take every pawn",
+        }
+    }
+
+    // Returns the variant after this one in ALL, wrapping around to the first
+    pub fn next(self) -> Self {
+        let index = Variant::ALL
+            .iter()
+            .position(|variant| *variant == self)
+            .unwrap();
+        Variant::ALL[(index + 1) % Variant::ALL.len()]
+    }
+
+    // Returns the FEN of the position games of the variant start from
+    pub fn starting_fen(self) -> &'static str {
+        match self {
+            Variant::Standard | Variant::KingOfTheHill | Variant::Atomic => STARTING_FEN,
+            Variant::ThreeCheck => "rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR w KQkq - 0 1 +0+0",
+            Variant::Crazyhouse => "rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR[] w KQkq - 0 1",
+            Variant::Antichess => "rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR w - - 0 1",
+            Variant::Horde => {
+                "rnbqkbnr/pppppppp/8/1PP2PP1/PPPPPPPP/PPPPPPPP/PPPPPPPP/PPPPPPPP w kq - 0 1"
+            }
+        }
+    }
+
+    // Returns the pieces a pawn may promote to under the variant, best first
+    pub fn promotion_pieces(self) -> &'static [ChessPieces] {
+        match self {
+            Variant::Antichess => &ANTICHESS_PROMOTION_PIECES,
+            _ => &PROMOTION_PIECES,
+        }
+    }
+
+    // Whether kings may capture, which they can't in Atomic since they would blow themselves up
+    fn kings_capture(self) -> bool {
+        self != Variant::Atomic
+    }
+}
+
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum GameState {
+    Ongoing,
+    Check,
+    Checkmate,
+    Stalemate,
+    Draw(DrawReason),
+    // A variant's own winning condition was met by the given side
+    Won { white: bool, reason: WinReason },
+}
+
+impl GameState {
+    // Whether the game has finished and no more moves can be played
+    pub fn is_over(self) -> bool {
+        matches!(
+            self,
+            GameState::Checkmate
+                | GameState::Stalemate
+                | GameState::Draw(_)
+                | GameState::Won { .. }
+        )
+    }
+}
+
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum WinReason {
+    KingOfTheHill,
+    ThreeChecks,
+    // Antichess is won by losing every piece or by having no move left
+    LostAllPieces,
+    NoMovesLeft,
+    KingExploded,
+    HordeDestroyed,
+}
+
+impl WinReason {
+    // Returns a readable description of how the game was won
+    pub fn description(self) -> &'static str {
+        match self {
+            WinReason::KingOfTheHill => "reaching the centre with the king",
+            WinReason::ThreeChecks => "giving check three times",
+            WinReason::LostAllPieces => "losing every piece",
+            WinReason::NoMovesLeft => "running out of moves",
+            WinReason::KingExploded => "blowing up the enemy king",
+            WinReason::HordeDestroyed => "capturing the whole horde",
+        }
+    }
+}
+
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum DrawReason {
+    // Claimable once 50 moves go by without a capture or pawn move
+    FiftyMoveRule,
+    // Automatic once 75 moves go by without a capture or pawn move
+    SeventyFiveMoveRule,
+    // Claimable once the same position comes up a third time
+    ThreefoldRepetition,
+    // Automatic once the same position comes up a fifth time
+    FivefoldRepetition,
+    InsufficientMaterial,
+}
+
+impl DrawReason {
+    // Returns a readable name for the rule that drew the game
+    pub fn description(self) -> &'static str {
+        match self {
+            DrawReason::FiftyMoveRule => "the fifty-move rule",
+            DrawReason::SeventyFiveMoveRule => "the seventy-five-move rule",
+            DrawReason::ThreefoldRepetition => "threefold repetition",
+            DrawReason::FivefoldRepetition => "fivefold repetition",
+            DrawReason::InsufficientMaterial => "insufficient material",
         }
     }
+}
+
+#[derive(Clone, Copy, Debug)]
+pub struct Piece {
+    pub symbol: ChessPieces,
+    pub file: usize,
+    pub rank: usize,
+    pub white: bool,
+}
+
+impl Piece {
+    // Returns the FEN character for the piece, uppercase for white and lowercase for black
+    pub fn to_fen(self) -> Option<char> {
+        let char = match self.symbol {
+            ChessPieces::None => return None,
+            ChessPieces::King => 'k',
+            ChessPieces::Queen => 'q',
+            ChessPieces::Rook => 'r',
+            ChessPieces::Bishop => 'b',
+            ChessPieces::Knight => 'n',
+            ChessPieces::Pawn => 'p',
+        };
+        Some(if self.white {
+            char.to_ascii_uppercase()
+        } else {
+            char
+        })
+    }
 
     // Returns the text symbol for the piece, including ANSI sequences
     pub fn get_symbol(&self) -> String {
@@ -637,7 +2257,7 @@ impl Piece {
     }
 }
 
-#[derive(Clone, Copy, PartialEq, Debug)]
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
 pub enum ChessPieces {
     None,
     King,
@@ -649,29 +2269,59 @@ pub enum ChessPieces {
 }
 
 impl ChessPieces {
+    // Returns the position of the piece type in the bitboard arrays, there is no bitboard for empty tiles
+    fn index(self) -> usize {
+        match self {
+            ChessPieces::King => 0,
+            ChessPieces::Queen => 1,
+            ChessPieces::Rook => 2,
+            ChessPieces::Bishop => 3,
+            ChessPieces::Knight => 4,
+            ChessPieces::Pawn => 5,
+            ChessPieces::None => panic!("empty tiles have no bitboard"),
+        }
+    }
+
+    // Returns the position of the piece type in a Crazyhouse pocket, which holds everything but kings
+    fn pocket_index(self) -> usize {
+        self.index() - 1
+    }
+
     // Converts a FEN character into a useable piece type
-    fn from_fen(piece: char) -> Self {
+    fn from_fen(piece: char) -> Option<Self> {
         match piece {
             // White pieces
-            'K' => Self::King,
-            'Q' => Self::Queen,
-            'B' => Self::Bishop,
-            'R' => Self::Rook,
-            'N' => Self::Knight,
-            'P' => Self::Pawn,
+            'K' => Some(Self::King),
+            'Q' => Some(Self::Queen),
+            'B' => Some(Self::Bishop),
+            'R' => Some(Self::Rook),
+            'N' => Some(Self::Knight),
+            'P' => Some(Self::Pawn),
             // Black pieces
-            'k' => Self::King,
-            'q' => Self::Queen,
-            'b' => Self::Bishop,
-            'r' => Self::Rook,
-            'n' => Self::Knight,
-            'p' => Self::Pawn,
-            _ => Self::None,
+            'k' => Some(Self::King),
+            'q' => Some(Self::Queen),
+            'b' => Some(Self::Bishop),
+            'r' => Some(Self::Rook),
+            'n' => Some(Self::Knight),
+            'p' => Some(Self::Pawn),
+            _ => None,
+        }
+    }
+
+    // Returns the letter used for the piece in algebraic notation, pawns have none
+    pub fn san_letter(self) -> Option<char> {
+        match self {
+            ChessPieces::King => Some('K'),
+            ChessPieces::Queen => Some('Q'),
+            ChessPieces::Rook => Some('R'),
+            ChessPieces::Bishop => Some('B'),
+            ChessPieces::Knight => Some('N'),
+            ChessPieces::Pawn | ChessPieces::None => None,
         }
     }
 
     // Returns the ASCII character for each piece
-    fn to_symbol(&self) -> String {
+    pub fn to_symbol(self) -> String {
         let symbol = match self {
             ChessPieces::None => "_",
             ChessPieces::King => "♔",
@@ -684,3 +2334,566 @@ impl ChessPieces {
         symbol.to_string()
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const KIWIPETE: &str = "r3k2r/p1ppqpb1/bn2pnp1/3PN3/1p2P3/2N2Q1p/PPPBBPPP/R3K2R w KQkq - 0 1";
+    const POSITION_3: &str = "8/2p5/3p4/KP5r/1R3p1k/8/4P1P1/8 w - - 0 1";
+    const POSITION_4: &str = "r3k2r/Pppp1ppp/1b3nbN/nP6/BBP1P3/q4N2/Pp1P2PP/R2Q1RK1 w kq - 0 1";
+    const POSITION_4_MIRRORED: &str =
+        "r2q1rk1/pP1p2pp/Q4n2/bbp1p3/Np6/1B3NBn/pPPP1PPP/R3K2R b KQ - 0 1";
+    const POSITION_5: &str = "rnbq1k1r/pp1Pbppp/2p5/8/2B5/8/PPP1NnPP/RNBQK2R w KQ - 1 8";
+    const POSITION_6: &str =
+        "r4rk1/1pp1qppp/p1np1n2/2b1p1B1/2B1P1b1/P1NP1N2/1PP1QPPP/R4RK1 w - - 0 10";
+
+    // Checks perft counts for every depth from 1 up to the length of the expected list
+    fn assert_perft(fen: &str, expected: &[u64]) {
+        let board = Board::from_fen(fen).unwrap();
+        for (depth, nodes) in expected.iter().enumerate() {
+            assert_eq!(
+                board.perft(depth as u32 + 1),
+                *nodes,
+                "perft({}) of {}",
+                depth + 1,
+                fen
+            );
+        }
+    }
+
+    #[test]
+    fn perft_initial_position() {
+        assert_perft(STARTING_FEN, &[20, 400, 8902, 197281]);
+    }
+
+    #[test]
+    fn perft_kiwipete() {
+        assert_perft(KIWIPETE, &[48, 2039, 97862]);
+    }
+
+    #[test]
+    fn perft_position_3() {
+        assert_perft(POSITION_3, &[14, 191, 2812, 43238]);
+    }
+
+    #[test]
+    fn perft_position_4() {
+        assert_perft(POSITION_4, &[6, 264, 9467]);
+        assert_perft(POSITION_4_MIRRORED, &[6, 264, 9467]);
+    }
+
+    #[test]
+    fn perft_position_5() {
+        assert_perft(POSITION_5, &[44, 1486, 62379]);
+    }
+
+    #[test]
+    fn perft_position_6() {
+        assert_perft(POSITION_6, &[46, 2079, 89890]);
+    }
+
+    // Plays moves given in SAN, failing the test if any of them is illegal
+    fn play(board: &mut Board, moves: &[&str]) {
+        for san in moves {
+            let chess_move = board.parse_san(san).unwrap();
+            assert!(board.play_move(chess_move), "{}", san);
+        }
+    }
+
+    #[test]
+    fn fifty_and_seventy_five_move_rules() {
+        let mut board = Board::from_fen("4k3/8/8/8/8/8/8/R3K3 w - - 98 60").unwrap();
+        play(&mut board, &["Ra2"]);
+        assert_eq!(board.claimable_draw(), None);
+        play(&mut board, &["Kd8"]);
+        assert_eq!(board.claimable_draw(), Some(DrawReason::FiftyMoveRule));
+        assert_eq!(board.game_state(), GameState::Ongoing);
+
+        let mut board = Board::from_fen("4k3/8/8/8/8/8/8/R3K3 w - - 148 80").unwrap();
+        play(&mut board, &["Ra2"]);
+        assert_eq!(board.game_state(), GameState::Ongoing);
+        play(&mut board, &["Kd8"]);
+        assert_eq!(
+            board.game_state(),
+            GameState::Draw(DrawReason::SeventyFiveMoveRule)
+        );
+    }
+
+    #[test]
+    fn threefold_and_fivefold_repetition() {
+        let shuffle = ["Nf3", "Nf6", "Ng1", "Ng8"];
+        let mut board = Board::with_variant(Variant::Standard);
+        assert!(!board.claim_draw());
+        play(&mut board, &shuffle);
+        play(&mut board, &shuffle);
+        assert_eq!(board.repetition_count(), 3);
+        assert_eq!(
+            board.claimable_draw(),
+            Some(DrawReason::ThreefoldRepetition)
+        );
+        assert_eq!(board.game_state(), GameState::Ongoing);
+
+        // A claimed draw ends the game until the move it was claimed after is taken back
+        assert!(board.claim_draw());
+        assert_eq!(
+            board.game_state(),
+            GameState::Draw(DrawReason::ThreefoldRepetition)
+        );
+        board.undo();
+        assert_eq!(board.claimable_draw(), None);
+        assert_eq!(board.game_state(), GameState::Ongoing);
+        board.redo();
+        assert_eq!(board.game_state(), GameState::Ongoing);
+
+        play(&mut board, &shuffle);
+        assert_eq!(board.game_state(), GameState::Ongoing);
+        play(&mut board, &shuffle);
+        assert_eq!(board.repetition_count(), 5);
+        assert_eq!(
+            board.game_state(),
+            GameState::Draw(DrawReason::FivefoldRepetition)
+        );
+    }
+
+    #[test]
+    fn insufficient_material() {
+        for (fen, insufficient) in [
+            ("4k3/8/8/8/8/8/8/4K3 w - - 0 1", true),
+            ("4k3/8/8/8/8/8/8/2B1K3 w - - 0 1", true),
+            ("4k3/8/8/8/8/8/8/1N2K3 w - - 0 1", true),
+            // Bishops on c1 and f8 both stand on dark tiles, the one on c8 stands on a light tile
+            ("4kb2/8/8/8/8/8/8/2B1K3 w - - 0 1", true),
+            ("2b1k3/8/8/8/8/8/8/2B1K3 w - - 0 1", false),
+            ("1n2k3/8/8/8/8/8/8/1N2K3 w - - 0 1", false),
+            ("4k3/8/8/8/8/8/8/R3K3 w - - 0 1", false),
+        ] {
+            let board = Board::from_fen(fen).unwrap();
+            assert_eq!(board.is_insufficient_material(), insufficient, "{}", fen);
+            let drawn = board.game_state() == GameState::Draw(DrawReason::InsufficientMaterial);
+            assert_eq!(drawn, insufficient, "{}", fen);
+        }
+    }
+
+    #[test]
+    fn perft_chess960() {
+        assert_perft(
+            "bqnb1rkr/pp3ppp/3ppn2/2p5/5P2/P2P4/NPP1P1PP/BQ1BNRKR w HFhf - 2 9",
+            &[21, 528, 12189],
+        );
+        assert_perft(
+            "2nnrbkr/p1qppppp/8/1ppb4/6PP/3PP3/PPP2P2/BQNNRBKR w HEhe - 1 9",
+            &[21, 807, 18002],
+        );
+        assert_perft(
+            "qbbnnrkr/2pp2pp/p7/1p2pp2/8/P3PP2/1PPP1KPP/QBBNNR1R w hf - 0 9",
+            &[22, 593, 13440],
+        );
+    }
+
+    #[test]
+    fn chess960_numbers_every_starting_position() {
+        assert_eq!(Board::chess960(518).unwrap().to_fen(), STARTING_FEN);
+        assert_eq!(
+            Board::chess960(0).unwrap().to_fen(),
+            "bbqnnrkr/pppppppp/8/8/8/8/PPPPPPPP/BBQNNRKR w KQkq - 0 1"
+        );
+        assert!(Board::chess960(960).is_none());
+
+        let mut fens = (0..960)
+            .map(|index| Board::chess960(index).unwrap().to_fen())
+            .collect::<Vec<_>>();
+        fens.sort();
+        fens.dedup();
+        assert_eq!(fens.len(), 960);
+    }
+
+    #[test]
+    fn chess960_castling() {
+        // Shredder-FEN file letters are written back as X-FEN, which only names a file when the rook isn't outermost
+        let board =
+            Board::from_fen("1r2k1r1/pppppppp/8/8/8/8/PPPPPPPP/RR2K2R w BHg - 0 1").unwrap();
+        assert!(board.chess960);
+        assert_eq!(
+            board.to_fen(),
+            "1r2k1r1/pppppppp/8/8/8/8/PPPPPPPP/RR2K2R w KBk - 0 1"
+        );
+
+        // The king on b1 castles with the rook on c1, the two end up on g1 and f1
+        let mut board = Board::from_fen("2rk3r/pppppppp/8/8/8/8/PPPPPPPP/1KR5 w Cc - 0 1").unwrap();
+        let king = board.piece_at(7, 1);
+        assert!(board.filter_possible_moves(king).contains(&(7, 2)));
+        let castle = board.build_move(king, 7, 2, None);
+        assert_eq!(castle.castle, Some(CastleSide::KingSide));
+        assert_eq!(board.to_uci(&castle), "b1c1");
+        assert_eq!(board.parse_uci("b1c1"), Ok(castle));
+        assert_eq!(board.parse_san("O-O"), Ok(castle));
+        assert!(board.play_move(castle));
+        assert_eq!(
+            board.to_fen(),
+            "2rk3r/pppppppp/8/8/8/8/PPPPPPPP/5RK1 b q - 1 1"
+        );
+
+        // Black's king and rook swap tiles when castling queen side from d8 and c8
+        let chess_move = board.parse_san("O-O-O").unwrap();
+        assert!(board.play_move(chess_move));
+        assert_eq!(
+            board.to_fen(),
+            "2kr3r/pppppppp/8/8/8/8/PPPPPPPP/5RK1 w - - 2 2"
+        );
+        board.undo();
+        board.undo();
+        assert_eq!(
+            board.to_fen(),
+            "2rk3r/pppppppp/8/8/8/8/PPPPPPPP/1KR5 w Kq - 0 1"
+        );
+    }
+
+    #[test]
+    fn rejects_malformed_fen() {
+        let error = |fen: &str| Board::from_fen(fen).err().unwrap();
+        assert!(matches!(
+            error("4k3/8/8/8/8/8/4K3 w - - 0 1"),
+            FenError::WrongRankCount(7)
+        ));
+        assert!(matches!(
+            error("4k3/8/8/8/8/8/8/4K4 w - - 0 1"),
+            FenError::WrongRankLength { rank: 1, length: 9 }
+        ));
+        assert!(matches!(
+            error("4k3/8/8/8/8/8/7/4K3 w - - 0 1"),
+            FenError::WrongRankLength { rank: 2, length: 7 }
+        ));
+        assert!(matches!(
+            error("44/8/8/8/8/8/8/4K2k w - - 0 1"),
+            FenError::AdjacentBlanks { rank: 8 }
+        ));
+        assert!(matches!(
+            error("4k3/8/8/8/8/8/8/4K2X w - - 0 1"),
+            FenError::UnknownPiece('X')
+        ));
+        assert!(matches!(
+            error("8/8/8/8/8/8/8/4K3 w - - 0 1"),
+            FenError::MissingKing { white: false }
+        ));
+        assert!(matches!(
+            error("4k3/8/8/8/8/8/8/4K1K1 w - - 0 1"),
+            FenError::TooManyKings { white: true }
+        ));
+        assert!(matches!(
+            error("4k2P/8/8/8/8/8/8/4K3 w - - 0 1"),
+            FenError::PawnOnBackRank(tile) if tile == "h8"
+        ));
+        assert!(matches!(
+            error("4k3/8/8/8/4P3/8/8/4K3 w - e3 0 1"),
+            FenError::InvalidEnPassant(tile) if tile == "e3"
+        ));
+        assert!(matches!(
+            error("4k3/8/8/8/8/8/8/4K3 w - - x 1"),
+            FenError::InvalidHalfmoveClock(field) if field == "x"
+        ));
+        assert!(matches!(
+            error("4k3/8/8/8/8/8/8/4K3 w - - 0 0"),
+            FenError::InvalidFullmoveNumber(field) if field == "0"
+        ));
+        assert_eq!(
+            error("44/8/8/8/8/8/8/4K2k w - - 0 1").to_string(),
+            "FEN error: rank 8 has two numbers of empty tiles in a row"
+        );
+    }
+
+    #[test]
+    fn unmake_restores_the_position() {
+        for fen in [STARTING_FEN, KIWIPETE, POSITION_3, POSITION_4, POSITION_5] {
+            let mut board = Board::from_fen(fen).unwrap();
+            for chess_move in board.legal_moves() {
+                let undo = board.make_move(chess_move);
+                board.unmake_move(chess_move, undo);
+                assert_eq!(board.to_fen(), fen, "after {}", board.to_uci(&chess_move));
+            }
+        }
+    }
+
+    // Walks the move tree checking the incrementally updated hash against one worked out from scratch
+    fn assert_hashes_match(board: &mut Board, depth: u32) {
+        assert_eq!(board.hash(), board.compute_hash(), "{}", board.to_fen());
+        if depth == 0 {
+            return;
+        }
+        for chess_move in board.generate_legal_moves() {
+            let undo = board.make_move(chess_move);
+            assert_hashes_match(board, depth - 1);
+            board.unmake_move(chess_move, undo);
+        }
+    }
+
+    #[test]
+    fn incremental_hash_matches_full_hash() {
+        for fen in [STARTING_FEN, KIWIPETE, POSITION_3, POSITION_4, POSITION_5] {
+            assert_hashes_match(&mut Board::from_fen(fen).unwrap(), 3);
+        }
+    }
+
+    #[test]
+    fn hash_identifies_positions() {
+        let play = |moves: &[&str]| {
+            let mut board = Board::with_variant(Variant::Standard);
+            for san in moves {
+                let chess_move = board.parse_san(san).unwrap();
+                assert!(board.play_move(chess_move));
+            }
+            board
+        };
+
+        let transposed = [
+            play(&["Nf3", "Nf6", "e4"]),
+            play(&["e4", "Nf6", "Nf3"]),
+            Board::from_fen("rnbqkb1r/pppppppp/5n2/8/4P3/5N2/PPPP1PPP/RNBQKB1R b KQkq - 1 2")
+                .unwrap(),
+        ];
+        assert!(transposed
+            .iter()
+            .all(|board| board.hash() == transposed[0].hash()));
+
+        // The en passant tile only counts when a pawn could actually take on it
+        let hash_of = |fen: &str| Board::from_fen(fen).unwrap().hash();
+        assert_eq!(
+            play(&["e4"]).hash(),
+            hash_of("rnbqkbnr/pppppppp/8/8/4P3/8/PPPP1PPP/RNBQKBNR b KQkq - 0 1")
+        );
+        assert_ne!(
+            hash_of("rnbqkbnr/ppp1pppp/8/8/3pP3/8/PPPP1PPP/RNBQKBNR b KQkq e3 0 3"),
+            hash_of("rnbqkbnr/ppp1pppp/8/8/3pP3/8/PPPP1PPP/RNBQKBNR b KQkq - 0 3")
+        );
+
+        let mut back_home = play(&["Nf3", "Nf6", "Ng1", "Ng8"]);
+        assert_eq!(
+            back_home.hash(),
+            Board::with_variant(Variant::Standard).hash()
+        );
+        assert_eq!(back_home.repetition_count(), 2);
+        back_home.castling.white_king_side = None;
+        assert_ne!(
+            back_home.compute_hash(),
+            Board::with_variant(Variant::Standard).hash()
+        );
+        assert_ne!(
+            Board::with_variant(Variant::Standard).hash(),
+            hash_of("rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR b KQkq - 0 1")
+        );
+    }
+
+    #[test]
+    fn undo_and_redo_walk_through_the_game() {
+        let mut board = Board::from_fen(KIWIPETE).unwrap();
+        let mut fens = vec![board.to_fen()];
+        for san in ["O-O-O", "Bxe2", "Qxe2", "c5", "dxc6", "Nc4", "Nxf7"] {
+            let chess_move = board.parse_san(san).unwrap();
+            assert!(board.play_move(chess_move));
+            fens.push(board.to_fen());
+        }
+
+        for fen in fens.iter().rev().skip(1) {
+            assert!(board.undo().is_some());
+            assert_eq!(&board.to_fen(), fen);
+        }
+        assert_eq!(board.undo(), None);
+        assert_eq!(board.history.len(), 1);
+
+        for fen in fens.iter().skip(1).take(3) {
+            assert!(board.redo().is_some());
+            assert_eq!(&board.to_fen(), fen);
+        }
+        let chess_move = board.parse_san("a6").unwrap();
+        assert!(board.play_move(chess_move));
+        assert_eq!(board.redo(), None);
+        assert_eq!(board.moves.len(), 4);
+    }
+
+    #[test]
+    fn divide_adds_up_to_perft() {
+        let board = Board::from_fen(KIWIPETE).unwrap();
+        let divided = board.divide(2);
+        assert_eq!(divided.len(), 48);
+        assert_eq!(divided.iter().map(|(_, nodes)| nodes).sum::<u64>(), 2039);
+    }
+
+    #[test]
+    fn parses_san_and_uci_and_reports_why_a_move_was_not_read() {
+        let board = Board::from_fen("8/P3P3/1k6/8/8/8/7K/R6R w - - 0 1").unwrap();
+        let rook_a = board.parse_san("Rad1").unwrap();
+        assert_eq!((rook_a.from, rook_a.to), ((7, 0), (7, 3)));
+        assert_eq!(board.parse_uci("a1d1"), Ok(rook_a));
+        assert_eq!(
+            board.parse_san("e8=Q+").unwrap().promotion,
+            Some(ChessPieces::Queen)
+        );
+
+        for (uci, piece) in [
+            ("e7e8q", ChessPieces::Queen),
+            ("a7a8n", ChessPieces::Knight),
+        ] {
+            let chess_move = board.parse_uci(uci).unwrap();
+            assert_eq!(chess_move.promotion, Some(piece));
+            assert_eq!(board.to_uci(&chess_move), uci);
+        }
+
+        assert_eq!(
+            board.parse_san("Rd1"),
+            Err(MoveParseError::Ambiguous("Rd1".to_string()))
+        );
+        for text in ["e8", "a8", "Qd4"] {
+            assert_eq!(
+                board.parse_san(text),
+                Err(MoveParseError::Illegal(text.to_string()))
+            );
+        }
+        assert_eq!(
+            board.parse_uci("e7e8"),
+            Err(MoveParseError::Illegal("e7e8".to_string()))
+        );
+        assert_eq!(
+            board.parse_san("Zz9"),
+            Err(MoveParseError::Unparseable("Zz9".to_string()))
+        );
+        assert_eq!(
+            board.parse_uci("e7e9q"),
+            Err(MoveParseError::Unparseable("e7e9q".to_string()))
+        );
+        assert_eq!(
+            MoveParseError::Illegal("a8".to_string()).to_string(),
+            "'a8' is not a legal move here"
+        );
+    }
+
+    #[test]
+    fn perft_variants() {
+        assert_eq!(Board::with_variant(Variant::Horde).perft(4), 23310);
+        assert_eq!(Board::with_variant(Variant::Antichess).perft(4), 153299);
+        assert_eq!(Board::with_variant(Variant::Atomic).perft(4), 197326);
+    }
+
+    #[test]
+    fn standard_checkmate_and_stalemate() {
+        let mut board = Board::with_variant(Variant::Standard);
+        play(&mut board, &["f3", "e5", "g4"]);
+        assert_eq!(board.game_state(), GameState::Ongoing);
+        let mate = board.parse_san("Qh4").unwrap();
+        assert_eq!(board.to_san(&mate), "Qh4#");
+        assert!(board.play_move(mate));
+        assert_eq!(board.game_state(), GameState::Checkmate);
+        assert!(board.game_state().is_over());
+
+        let board = Board::from_fen("k7/8/1Q6/8/8/8/8/7K b - - 0 1").unwrap();
+        assert_eq!(board.game_state(), GameState::Stalemate);
+        let board = Board::from_fen("k7/8/8/2Q5/8/8/8/7K b - - 0 1").unwrap();
+        assert_eq!(board.game_state(), GameState::Ongoing);
+        let board = Board::from_fen("k7/8/8/8/8/8/8/Q6K b - - 0 1").unwrap();
+        assert_eq!(board.game_state(), GameState::Check);
+    }
+
+    #[test]
+    fn variant_winning_conditions() {
+        let play = |fen: &str, variant: Variant, san: &str| {
+            let mut board = Board::from_variant_fen(fen, variant).unwrap();
+            let chess_move = board.parse_san(san).unwrap();
+            assert!(board.play_move(chess_move));
+            board
+        };
+        let won = |white: bool, reason: WinReason| GameState::Won { white, reason };
+
+        let board = play(
+            "4k3/8/8/8/8/4K3/8/8 w - - 0 1",
+            Variant::KingOfTheHill,
+            "Ke4",
+        );
+        assert_eq!(board.game_state(), won(true, WinReason::KingOfTheHill));
+
+        let board = play(
+            "4k3/8/8/8/8/8/8/R3K3 w - - 0 1 +2+0",
+            Variant::ThreeCheck,
+            "Ra8+",
+        );
+        assert_eq!(board.game_state(), won(true, WinReason::ThreeChecks));
+        assert!(board.to_fen().ends_with(" +3+0"));
+
+        let mut board = play("4k3/3p4/8/8/8/8/8/3QK3 w - - 0 1", Variant::Atomic, "Qxd7");
+        assert_eq!(board.to_fen(), "8/8/8/8/8/8/8/4K3 b - - 0 1");
+        assert_eq!(board.game_state(), won(true, WinReason::KingExploded));
+        board.undo();
+        assert_eq!(board.to_fen(), "4k3/3p4/8/8/8/8/8/3QK3 w - - 0 1");
+        assert_eq!(board.hash(), board.compute_hash());
+
+        let board = play("4k3/8/8/8/8/8/3p4/4P3 b - - 0 1", Variant::Horde, "dxe1=Q");
+        assert_eq!(board.game_state(), won(false, WinReason::HordeDestroyed));
+
+        let mut board = Board::with_variant(Variant::Antichess);
+        for san in ["e3", "b5"] {
+            let chess_move = board.parse_san(san).unwrap();
+            assert!(board.play_move(chess_move));
+        }
+        let moves = board.legal_moves();
+        assert_eq!(moves.len(), 1);
+        assert_eq!(board.to_san(&moves[0]), "Bxb5");
+    }
+
+    #[test]
+    fn crazyhouse_pockets_and_drops() {
+        let zh_middlegame =
+            "r1bqk2r/pppp1ppp/2n1p3/4P3/1b1Pn3/2NB1N2/PPP2PPP/R1BQK2R[] b KQkq - 0 1";
+        let board = Board::from_variant_fen(zh_middlegame, Variant::Crazyhouse).unwrap();
+        assert_eq!(board.perft(3), 58057);
+
+        let fen = "4k3/1Q~6/8/8/4b3/8/Kpp5/8[Nq] b - - 0 1";
+        let mut board = Board::from_variant_fen(fen, Variant::Crazyhouse).unwrap();
+        assert_eq!(board.to_fen(), fen);
+
+        // The promoted queen goes into the pocket as a pawn
+        let chess_move = board.parse_san("Bxb7").unwrap();
+        assert!(board.play_move(chess_move));
+        assert_eq!(board.to_fen(), "4k3/1b6/8/8/8/8/Kpp5/8[Nqp] w - - 0 2");
+        assert_eq!(board.hash(), board.compute_hash());
+
+        let chess_move = board.parse_san("N@c3").unwrap();
+        assert_eq!(board.to_uci(&chess_move), "N@c3");
+        assert!(board.play_move(chess_move));
+        assert_eq!(board.pocket_count(true, ChessPieces::Knight), 0);
+        assert_eq!(
+            board.parse_san("P@b1"),
+            Err(MoveParseError::Illegal("P@b1".to_string()))
+        );
+        assert!(board.parse_san("@d4").is_ok());
+
+        board.undo();
+        board.undo();
+        assert_eq!(board.to_fen(), fen);
+    }
+
+    #[test]
+    fn san_names_as_much_of_the_origin_as_it_needs() {
+        // Three knights can reach c2: the ones on a1 and a3 share a file, and the ones on a1 and e1 share a rank
+        let board = Board::from_fen("8/8/8/8/3k4/N7/8/N3N2K w - - 0 1").unwrap();
+        for (uci, san) in [("a1c2", "Na1c2+"), ("a3c2", "N3c2+"), ("e1c2", "Nec2+")] {
+            let chess_move = board.parse_uci(uci).unwrap();
+            assert_eq!(board.to_san(&chess_move), san);
+            assert_eq!(board.parse_san(san), Ok(chess_move));
+        }
+        assert_eq!(board.to_san(&board.parse_uci("a3b1").unwrap()), "Nb1");
+
+        let board =
+            Board::from_fen("rnbqkb1r/ppp2ppp/5n2/3pp3/4P3/5N2/PPPP1PPP/RNBQKB1R b KQkq - 0 1")
+                .unwrap();
+        assert_eq!(board.to_san(&board.parse_uci("b8d7").unwrap()), "Nbd7");
+        assert_eq!(board.to_san(&board.parse_uci("f6d7").unwrap()), "Nfd7");
+    }
+
+    #[test]
+    fn crazyhouse_drops_are_not_rivals_in_san() {
+        let fen = "r1bqkb1r/pppp1ppp/2n2n2/4p3/4P3/5N2/PPPP1PPP/RNBQKB1R[N] w KQkq - 0 1";
+        let board = Board::from_variant_fen(fen, Variant::Crazyhouse).unwrap();
+        let chess_move = board.parse_san("Nc3").unwrap();
+        assert!(!chess_move.drop);
+        assert_eq!(board.to_san(&chess_move), "Nc3");
+        assert_eq!(board.to_san(&board.parse_san("N@c3").unwrap()), "N@c3");
+    }
+}
@@ -0,0 +1,949 @@
+// Generates distance to mate tables for endings of three and four pieces by retrograde analysis, and saves and loads them
+// Every position of an ending gets one byte: 0 for a draw, otherwise one more than the number of plies until mate,
+// which makes it even when the side to move mates and odd when it gets mated
+// Positions are mirrored so the white king stands in the a1-d1-d4 triangle, or on the queen side when there are pawns,
+// which keeps a table down to an eighth or a half of every placement of the pieces
+
+use std::collections::HashMap;
+use std::path::Path;
+
+use crate::bitboard::{
+    bishop_attacks, coordinates, queen_attacks, rook_attacks, square, squares, Bitboard, Square,
+    KING_ATTACKS, KNIGHT_ATTACKS,
+};
+use crate::chess::{Board, CastlingRights, ChessPieces, Move, Variant};
+
+// The most pieces an ending may have, kings included
+const MAX_PIECES: usize = 4;
+
+// The first four bytes of every table file, followed by how many positions the table holds
+const MAGIC: [u8; 4] = *b"TCDM";
+
+// The extension table files are saved with, after the name of the ending such as "KBNK"
+const EXTENSION: &str = "dtm";
+
+// The longest mate a table byte can hold, in plies
+const MAX_PLIES: u32 = 250;
+
+// Marks a position without any capture or promotion in the byte holding its best one
+const NO_EXIT: u8 = u8::MAX;
+
+// The letters endings are written with, strongest piece first, and what each piece is worth when telling which side is stronger
+const PIECE_LETTERS: [(ChessPieces, char, u32); 5] = [
+    (ChessPieces::Queen, 'Q', 9),
+    (ChessPieces::Rook, 'R', 5),
+    (ChessPieces::Bishop, 'B', 3),
+    (ChessPieces::Knight, 'N', 3),
+    (ChessPieces::Pawn, 'P', 1),
+];
+
+// How far a position is from mate with perfect play, in plies, for the side to move
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Distance {
+    Win(u32),
+    Loss(u32),
+    Draw,
+}
+
+impl Distance {
+    // Writes the result the way players read it, counted in moves
+    pub fn describe(self) -> String {
+        match self {
+            Distance::Win(plies) => format!("win, mate in {}", plies.div_ceil(2)),
+            Distance::Loss(0) => "loss, checkmated".to_string(),
+            Distance::Loss(plies) => format!("loss, mated in {}", plies / 2),
+            Distance::Draw => "draw".to_string(),
+        }
+    }
+
+    // The result for the side that made the move leading to this position
+    fn before_move(self) -> Self {
+        match self {
+            Distance::Win(plies) => Distance::Loss(plies + 1),
+            Distance::Loss(plies) => Distance::Win(plies + 1),
+            Distance::Draw => Distance::Draw,
+        }
+    }
+
+    // How much the side to move likes the result: quick wins most, then slow wins, draws, slow losses and quick losses
+    fn preference(self) -> i64 {
+        match self {
+            Distance::Win(plies) => i64::from(MAX_PLIES) * 2 - i64::from(plies),
+            Distance::Loss(plies) => i64::from(plies) - i64::from(MAX_PLIES) * 2,
+            Distance::Draw => 0,
+        }
+    }
+
+    fn from_byte(byte: u8) -> Self {
+        match byte {
+            0 => Distance::Draw,
+            byte if byte % 2 == 0 => Distance::Win(u32::from(byte) - 1),
+            byte => Distance::Loss(u32::from(byte) - 1),
+        }
+    }
+
+    fn to_byte(self) -> u8 {
+        match self {
+            Distance::Win(plies) | Distance::Loss(plies) => plies as u8 + 1,
+            Distance::Draw => 0,
+        }
+    }
+}
+
+// The pieces of an ending besides the two kings, white's and then black's, each side strongest first
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Material {
+    pieces: Vec<(ChessPieces, bool)>,
+}
+
+impl Material {
+    // Reads an ending written like "KBNK" or "KQKR", white's king and pieces followed by black's
+    pub fn from_name(name: &str) -> Result<Self, EndgameError> {
+        let invalid = || EndgameError::InvalidMaterial(name.to_string());
+        let upper = name.to_ascii_uppercase();
+        let (white, black) = upper
+            .strip_prefix('K')
+            .and_then(|rest| rest.split_once('K'))
+            .ok_or_else(invalid)?;
+
+        let mut pieces = vec![];
+        for (letters, is_white) in [(white, true), (black, false)] {
+            for letter in letters.chars() {
+                let (symbol, _, _) = PIECE_LETTERS
+                    .iter()
+                    .find(|(_, piece_letter, _)| *piece_letter == letter)
+                    .ok_or_else(invalid)?;
+                pieces.push((*symbol, is_white));
+            }
+        }
+
+        let material = Material::new(pieces);
+        let pawns = |white| material.pieces.contains(&(ChessPieces::Pawn, white));
+        // Pawns on both sides could take en passant, which the tables don't keep track of
+        if !(3..=MAX_PIECES).contains(&material.piece_count()) || pawns(true) && pawns(false) {
+            return Err(invalid());
+        }
+        Ok(material)
+    }
+
+    // The ending on the board, if it is small enough to have a table
+    fn of_board(board: &Board) -> Option<Self> {
+        if board.occupied().count_ones() as usize > MAX_PIECES {
+            return None;
+        }
+        let mut pieces = vec![];
+        for white in [true, false] {
+            for (symbol, _, _) in PIECE_LETTERS {
+                for _ in 0..board.pieces_of(white, symbol).count_ones() {
+                    pieces.push((symbol, white));
+                }
+            }
+        }
+        Some(Material::new(pieces))
+    }
+
+    fn new(mut pieces: Vec<(ChessPieces, bool)>) -> Self {
+        pieces.sort_by_key(|(symbol, white)| (!white, letter_index(*symbol)));
+        Material { pieces }
+    }
+
+    pub fn name(&self) -> String {
+        let letters = |white: bool| {
+            self.pieces
+                .iter()
+                .filter(|(_, piece_white)| *piece_white == white)
+                .map(|(symbol, _)| PIECE_LETTERS[letter_index(*symbol)].1)
+                .collect::<String>()
+        };
+        format!("K{}K{}", letters(true), letters(false))
+    }
+
+    // The same ending with the colours swapped
+    fn flipped(&self) -> Self {
+        Material::new(
+            self.pieces
+                .iter()
+                .map(|(symbol, white)| (*symbol, !white))
+                .collect(),
+        )
+    }
+
+    // The way round the ending is stored, with the stronger side as white, and whether that swaps the colours
+    fn canonical(&self) -> (Self, bool) {
+        let strength = |white: bool| {
+            let pieces = self
+                .pieces
+                .iter()
+                .filter(|(_, piece_white)| *piece_white == white);
+            let value: u32 = pieces
+                .clone()
+                .map(|(symbol, _)| PIECE_LETTERS[letter_index(*symbol)].2)
+                .sum();
+            // Between equal values the side with the stronger pieces, then the one with more of them, comes first
+            let letters: Vec<_> = pieces
+                .map(|(symbol, _)| std::cmp::Reverse(letter_index(*symbol)))
+                .collect();
+            (value, letters)
+        };
+        if strength(false) > strength(true) {
+            (self.flipped(), true)
+        } else {
+            (self.clone(), false)
+        }
+    }
+
+    // The endings a capture or promotion can lead to
+    fn children(&self) -> Vec<Material> {
+        let mut children = vec![];
+        for captured in 0..self.pieces.len() {
+            let mut pieces = self.pieces.clone();
+            pieces.remove(captured);
+            children.push(Material::new(pieces));
+        }
+        let captures = children.clone();
+        for material in std::iter::once(self).chain(&captures) {
+            for (index, piece) in material.pieces.iter().enumerate() {
+                if piece.0 != ChessPieces::Pawn {
+                    continue;
+                }
+                for (symbol, _, _) in &PIECE_LETTERS[..4] {
+                    let mut pieces = material.pieces.clone();
+                    pieces[index].0 = *symbol;
+                    children.push(Material::new(pieces));
+                }
+            }
+        }
+        children
+            .into_iter()
+            .filter(|child| !child.pieces.is_empty())
+            .map(|child| child.canonical().0)
+            .collect()
+    }
+
+    fn has_pawns(&self) -> bool {
+        self.pieces
+            .iter()
+            .any(|(symbol, _)| *symbol == ChessPieces::Pawn)
+    }
+
+    // How many pieces stand on the board, kings included
+    fn piece_count(&self) -> usize {
+        self.pieces.len() + 2
+    }
+
+    // The type and colour of the piece each place in a position stands for, the white king and black king first
+    fn slot(&self, slot: usize) -> (ChessPieces, bool) {
+        match slot {
+            0 => (ChessPieces::King, true),
+            1 => (ChessPieces::King, false),
+            slot => self.pieces[slot - 2],
+        }
+    }
+
+    // How many squares the white king is mirrored onto
+    fn king_squares(&self) -> usize {
+        if self.has_pawns() {
+            32
+        } else {
+            10
+        }
+    }
+
+    // How many positions the table holds, for both sides to move
+    fn size(&self) -> usize {
+        2 * self.king_squares() * 64usize.pow(self.piece_count() as u32 - 1)
+    }
+
+    // The index of a position, given the square of each piece and the side to move, without any mirroring
+    // Like pieces are put in square order first so either way round gives the same index
+    fn raw_index(&self, mut placement: [Square; MAX_PIECES], white_move: bool) -> Option<usize> {
+        let count = self.piece_count();
+        for slot in 3..count {
+            if self.slot(slot) == self.slot(slot - 1) && placement[slot] < placement[slot - 1] {
+                placement.swap(slot, slot - 1);
+            }
+        }
+        let mut index = usize::from(white_move) * self.king_squares()
+            + king_square_index(placement[0], self.has_pawns())?;
+        for tile in &placement[1..count] {
+            index = index * 64 + tile;
+        }
+        Some(index)
+    }
+
+    // The index of a position: the lowest one among its mirror images, so that every one of them shares a single entry
+    fn index(&self, placement: [Square; MAX_PIECES], white_move: bool) -> usize {
+        let symmetries = if self.has_pawns() { 2 } else { 8 };
+        (0..symmetries)
+            .filter_map(|symmetry| {
+                let mut mirrored = placement;
+                for tile in mirrored.iter_mut().take(self.piece_count()) {
+                    *tile = mirror(*tile, symmetry);
+                }
+                self.raw_index(mirrored, white_move)
+            })
+            .min()
+            .unwrap()
+    }
+
+    // Turns an index back into the square of each piece and the side to move
+    fn position(&self, mut index: usize) -> ([Square; MAX_PIECES], bool) {
+        let mut placement = [0; MAX_PIECES];
+        for slot in (1..self.piece_count()).rev() {
+            placement[slot] = index % 64;
+            index /= 64;
+        }
+        let king_squares = self.king_squares();
+        placement[0] = king_square(index % king_squares, self.has_pawns());
+        (placement, index / king_squares == 1)
+    }
+
+    // Where each piece of the ending stands on the board, with the colours swapped and the board turned around if asked to
+    fn placement(&self, board: &Board, flip: bool) -> [Square; MAX_PIECES] {
+        let mut placement = [0; MAX_PIECES];
+        let mut used: Bitboard = 0;
+        for (slot, tile) in placement.iter_mut().take(self.piece_count()).enumerate() {
+            let (symbol, white) = self.slot(slot);
+            let found = squares(board.pieces_of(white != flip, symbol) & !used)
+                .next()
+                .unwrap();
+            used |= 1 << found;
+            *tile = if flip { found ^ 56 } else { found };
+        }
+        placement
+    }
+
+    // Sets up the position on a board, or returns None if it could never come up in a game
+    fn board(&self, placement: [Square; MAX_PIECES], white_move: bool) -> Option<Board> {
+        let placement = &placement[..self.piece_count()];
+        if placement
+            .iter()
+            .enumerate()
+            .any(|(slot, tile)| placement[..slot].contains(tile))
+        {
+            return None;
+        }
+        let pieces: Vec<_> = placement
+            .iter()
+            .enumerate()
+            .map(|(slot, tile)| {
+                let (symbol, white) = self.slot(slot);
+                (*tile, symbol, white)
+            })
+            .collect();
+        Board::from_pieces(&pieces, white_move).ok()
+    }
+}
+
+// The position of a piece type in PIECE_LETTERS
+fn letter_index(symbol: ChessPieces) -> usize {
+    PIECE_LETTERS
+        .iter()
+        .position(|(piece, _, _)| *piece == symbol)
+        .unwrap()
+}
+
+// Numbers the squares the white king is mirrored onto: the a1-d1-d4 triangle, or files a to d when there are pawns
+fn king_square_index(tile: Square, pawns: bool) -> Option<usize> {
+    let (rank, file) = coordinates(tile);
+    let row = 7 - rank;
+    if pawns {
+        (file < 4).then_some(row * 4 + file)
+    } else if file < 4 && row <= file {
+        Some(file * (file + 1) / 2 + row)
+    } else {
+        None
+    }
+}
+
+// The square the white king stands on for the number king_square_index gives it
+fn king_square(index: usize, pawns: bool) -> Square {
+    if pawns {
+        return square(7 - index / 4, index % 4);
+    }
+    let file = (0..4)
+        .rev()
+        .find(|file| file * (file + 1) / 2 <= index)
+        .unwrap();
+    square(7 - (index - file * (file + 1) / 2), file)
+}
+
+// Moves a square to its image under one of the eight symmetries of the board, the first two only flip the files
+fn mirror(tile: Square, symmetry: usize) -> Square {
+    let (mut rank, mut file) = coordinates(tile);
+    if symmetry & 1 != 0 {
+        file = 7 - file;
+    }
+    if symmetry & 2 != 0 {
+        rank = 7 - rank;
+    }
+    if symmetry & 4 != 0 {
+        std::mem::swap(&mut rank, &mut file);
+    }
+    square(rank, file)
+}
+
+// What a position looks like before the tables it leads into are worked backwards through
+#[derive(Clone, Copy)]
+enum Start {
+    // The index isn't used, because the position is impossible or is stored under a mirror image
+    Unused,
+    Mated,
+    Stalemate,
+    // The number of different positions of the ending the moves lead to, and the best result of any capture or promotion
+    Open { moves: u8, exit: Option<Distance> },
+}
+
+// A set of endgame tables, by the name of the ending with the stronger side as white
+#[derive(Default)]
+pub struct EndgameTables {
+    tables: HashMap<String, Vec<u8>>,
+}
+
+impl EndgameTables {
+    // Reads every table saved in the directory
+    pub fn load(directory: &str) -> Result<Self, EndgameError> {
+        let mut tables = EndgameTables::default();
+        for entry in std::fs::read_dir(directory).map_err(EndgameError::Io)? {
+            let path = entry.map_err(EndgameError::Io)?.path();
+            if path.extension().and_then(|extension| extension.to_str()) != Some(EXTENSION) {
+                continue;
+            }
+            let name = path
+                .file_stem()
+                .and_then(|stem| stem.to_str())
+                .unwrap_or_default();
+            let material = Material::from_name(name)?;
+            let bytes = std::fs::read(&path).map_err(EndgameError::Io)?;
+            let values = match bytes.split_at_checked(8) {
+                Some((header, values))
+                    if header[..4] == MAGIC
+                        && u32::from_le_bytes(header[4..].try_into().unwrap()) as usize
+                            == material.size()
+                        && values.len() == material.size() =>
+                {
+                    values.to_vec()
+                }
+                _ => return Err(EndgameError::Corrupt(path.display().to_string())),
+            };
+            tables.tables.insert(material.canonical().0.name(), values);
+        }
+        if tables.tables.is_empty() {
+            return Err(EndgameError::NoTables(directory.to_string()));
+        }
+        Ok(tables)
+    }
+
+    // Writes every table into the directory, one file per ending
+    pub fn save(&self, directory: &str) -> Result<(), EndgameError> {
+        std::fs::create_dir_all(directory).map_err(EndgameError::Io)?;
+        for (name, values) in &self.tables {
+            let mut bytes = MAGIC.to_vec();
+            bytes.extend((values.len() as u32).to_le_bytes());
+            bytes.extend(values);
+            let path = Path::new(directory).join(format!("{}.{}", name, EXTENSION));
+            std::fs::write(path, bytes).map_err(EndgameError::Io)?;
+        }
+        Ok(())
+    }
+
+    // The names of the endings there are tables for, in alphabetical order
+    pub fn names(&self) -> Vec<String> {
+        let mut names: Vec<_> = self.tables.keys().cloned().collect();
+        names.sort();
+        names
+    }
+
+    // The longest mate in the ending, in moves
+    pub fn longest_mate(&self, name: &str) -> Option<u32> {
+        self.tables[name]
+            .iter()
+            .filter_map(|byte| match Distance::from_byte(*byte) {
+                Distance::Win(plies) => Some(plies.div_ceil(2)),
+                _ => None,
+            })
+            .max()
+    }
+
+    // Looks up how far the position is from mate, if there is a table for it
+    pub fn probe(&self, board: &Board) -> Option<Distance> {
+        if board.variant != Variant::Standard || board.castling != CastlingRights::none() {
+            return None;
+        }
+        let (material, flip) = Material::of_board(board)?.canonical();
+        if material.pieces.is_empty() {
+            return Some(Distance::Draw);
+        }
+        let values = self.tables.get(&material.name())?;
+        let index = material.index(material.placement(board, flip), board.white_move != flip);
+        Some(Distance::from_byte(values[index]))
+    }
+
+    // The move that mates quickest, or puts off being mated longest, and how far the position is from mate
+    pub fn best_move(&self, board: &Board) -> Option<(Move, Distance)> {
+        let distance = self.probe(board)?;
+        let mut board = board.clone();
+        let mut best: Option<(Move, Distance)> = None;
+        for chess_move in board.generate_legal_moves() {
+            let undo = board.make_move(chess_move);
+            let after = self.probe(&board).map(Distance::before_move);
+            board.unmake_move(chess_move, undo);
+            let after = after?;
+            if best.is_none_or(|(_, best)| after.preference() > best.preference()) {
+                best = Some((chess_move, after));
+            }
+        }
+        best.map(|(chess_move, _)| (chess_move, distance))
+    }
+
+    // Generates the table for an ending written like "KBNK", after the tables of every ending its captures and promotions lead to
+    // Returns the names of the endings that were generated, smallest first
+    pub fn generate(&mut self, name: &str) -> Result<Vec<String>, EndgameError> {
+        let (material, _) = Material::from_name(name)?.canonical();
+        let mut generated = vec![];
+        self.generate_material(&material, &mut generated)?;
+        Ok(generated)
+    }
+
+    fn generate_material(
+        &mut self,
+        material: &Material,
+        generated: &mut Vec<String>,
+    ) -> Result<(), EndgameError> {
+        if self.tables.contains_key(&material.name()) {
+            return Ok(());
+        }
+        for child in material.children() {
+            self.generate_material(&child, generated)?;
+        }
+        let values = self.retrograde(material)?;
+        self.tables.insert(material.name(), values);
+        generated.push(material.name());
+        Ok(())
+    }
+
+    // Works out every position of the ending: first every mate, stalemate and move out of the ending is found with the move generator,
+    // then the results are spread backwards a ply at a time, so a position is won as soon as one move leads to a loss
+    // and lost once every move leads to a win, and whatever is left at the end is a draw
+    fn retrograde(&self, material: &Material) -> Result<Vec<u8>, EndgameError> {
+        let size = material.size();
+        let starts = self.starts(material);
+
+        let mut values = vec![0; size];
+        let mut resolved = vec![false; size];
+        let mut remaining = vec![0; size];
+        let mut exits = vec![NO_EXIT; size];
+        // The positions to settle at each ply, as (index, won)
+        let mut schedule: Vec<Vec<(usize, bool)>> = vec![];
+        let plan = |schedule: &mut Vec<Vec<(usize, bool)>>, plies: u32, index, won| {
+            if schedule.len() <= plies as usize {
+                schedule.resize(plies as usize + 1, vec![]);
+            }
+            schedule[plies as usize].push((index, won));
+        };
+
+        for (index, start) in starts.into_iter().enumerate() {
+            match start {
+                Start::Unused | Start::Stalemate => resolved[index] = true,
+                Start::Mated => plan(&mut schedule, 0, index, false),
+                Start::Open { moves, exit } => {
+                    remaining[index] = moves;
+                    exits[index] = exit.map_or(NO_EXIT, Distance::to_byte);
+                    match exit {
+                        Some(Distance::Win(plies)) => plan(&mut schedule, plies, index, true),
+                        Some(Distance::Loss(plies)) if moves == 0 => {
+                            plan(&mut schedule, plies, index, false)
+                        }
+                        Some(Distance::Draw) if moves == 0 => resolved[index] = true,
+                        _ => {}
+                    }
+                }
+            }
+        }
+
+        let mut plies = 0;
+        while plies < schedule.len() {
+            if plies as u32 > MAX_PLIES {
+                return Err(EndgameError::TooLong(material.name()));
+            }
+            let settled: Vec<_> = std::mem::take(&mut schedule[plies])
+                .into_iter()
+                .filter(|(index, won)| {
+                    let fresh = !resolved[*index];
+                    if fresh {
+                        resolved[*index] = true;
+                        let distance = match won {
+                            true => Distance::Win(plies as u32),
+                            false => Distance::Loss(plies as u32),
+                        };
+                        values[*index] = distance.to_byte();
+                    }
+                    fresh
+                })
+                .collect();
+
+            let next = plies as u32 + 1;
+            for (index, won) in settled {
+                for previous in predecessors(material, index) {
+                    if resolved[previous] {
+                        continue;
+                    }
+                    if !won {
+                        plan(&mut schedule, next, previous, true);
+                        continue;
+                    }
+                    remaining[previous] -= 1;
+                    if remaining[previous] > 0 {
+                        continue;
+                    }
+                    match exits[previous] {
+                        NO_EXIT => plan(&mut schedule, next, previous, false),
+                        exit => {
+                            if let Distance::Loss(exit_plies) = Distance::from_byte(exit) {
+                                plan(&mut schedule, next.max(exit_plies), previous, false);
+                            }
+                        }
+                    }
+                }
+            }
+            plies += 1;
+        }
+        Ok(values)
+    }
+
+    // Sorts out every position of the ending with the move generator, splitting the work between threads
+    fn starts(&self, material: &Material) -> Vec<Start> {
+        let mut starts = vec![Start::Unused; material.size()];
+        let threads = std::thread::available_parallelism().map_or(1, usize::from);
+        let chunk_size = starts.len().div_ceil(threads);
+        std::thread::scope(|scope| {
+            for (chunk, part) in starts.chunks_mut(chunk_size).enumerate() {
+                scope.spawn(move || {
+                    for (offset, start) in part.iter_mut().enumerate() {
+                        *start = self.start(material, chunk * chunk_size + offset);
+                    }
+                });
+            }
+        });
+        starts
+    }
+
+    fn start(&self, material: &Material, index: usize) -> Start {
+        let (placement, white_move) = material.position(index);
+        let mut board = match material.board(placement, white_move) {
+            Some(board) if material.index(placement, white_move) == index => board,
+            _ => return Start::Unused,
+        };
+
+        let moves = board.generate_legal_moves();
+        if moves.is_empty() {
+            return match board.is_in_check(white_move) {
+                true => Start::Mated,
+                false => Start::Stalemate,
+            };
+        }
+
+        let mut inside = vec![];
+        let mut exit: Option<Distance> = None;
+        for chess_move in moves {
+            let undo = board.make_move(chess_move);
+            if chess_move.capture.is_some() || chess_move.promotion.is_some() {
+                // The tables a move out of the ending leads to were generated first
+                let after = self.probe(&board).unwrap().before_move();
+                if exit.is_none_or(|exit| after.preference() > exit.preference()) {
+                    exit = Some(after);
+                }
+            } else {
+                inside.push(material.index(material.placement(&board, false), board.white_move));
+            }
+            board.unmake_move(chess_move, undo);
+        }
+        inside.sort_unstable();
+        inside.dedup();
+        Start::Open {
+            moves: inside.len() as u8,
+            exit,
+        }
+    }
+}
+
+// The positions of the ending that lead to the given one with a move that neither captures nor promotes
+// Pieces other than pawns move the same way back as forwards, so their moves from where they stand now show where they came from
+fn predecessors(material: &Material, index: usize) -> Vec<usize> {
+    let (placement, white_move) = material.position(index);
+    let occupied: Bitboard = placement[..material.piece_count()]
+        .iter()
+        .fold(0, |occupied, tile| occupied | 1 << tile);
+
+    let mut previous = vec![];
+    for slot in 0..material.piece_count() {
+        let (symbol, white) = material.slot(slot);
+        if white == white_move {
+            continue;
+        }
+        let tile = placement[slot];
+        let origins = match symbol {
+            ChessPieces::King => KING_ATTACKS[tile],
+            ChessPieces::Knight => KNIGHT_ATTACKS[tile],
+            ChessPieces::Bishop => bishop_attacks(tile, occupied),
+            ChessPieces::Rook => rook_attacks(tile, occupied),
+            ChessPieces::Queen => queen_attacks(tile, occupied),
+            ChessPieces::Pawn => pawn_origins(tile, white, occupied),
+            ChessPieces::None => 0,
+        };
+        for origin in squares(origins & !occupied) {
+            let mut before = placement;
+            before[slot] = origin;
+            if material.board(before, !white_move).is_some() {
+                previous.push(material.index(before, !white_move));
+            }
+        }
+    }
+    previous.sort_unstable();
+    previous.dedup();
+    previous
+}
+
+// Where a pawn standing on the square could have been pushed from, white pawns move towards rank index 0
+fn pawn_origins(tile: Square, white: bool, occupied: Bitboard) -> Bitboard {
+    let (rank, file) = coordinates(tile);
+    let (back, start_rank, double_rank) = match white {
+        true => (rank + 1, 6, 4),
+        false => (rank.wrapping_sub(1), 1, 3),
+    };
+    if !(1..=6).contains(&back) {
+        return 0;
+    }
+    let single = 1 << square(back, file);
+    if rank == double_rank && occupied & single == 0 {
+        single | 1 << square(start_rank, file)
+    } else {
+        single
+    }
+}
+
+#[derive(Debug)]
+pub enum EndgameError {
+    Io(std::io::Error),
+    InvalidMaterial(String),
+    Corrupt(String),
+    NoTables(String),
+    TooLong(String),
+}
+
+impl std::fmt::Display for EndgameError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            EndgameError::Io(error) => write!(f, "Endgame table error: {}", error),
+            EndgameError::InvalidMaterial(name) => write!(
+                f,
+                "Endgame table error: {} is not an ending of three or four pieces like KQK or KBNK, with pawns on at most one side",
+                name
+            ),
+            EndgameError::Corrupt(path) => {
+                write!(f, "Endgame table error: {} is not a valid table", path)
+            }
+            EndgameError::NoTables(directory) => {
+                write!(f, "Endgame table error: no tables in {}", directory)
+            }
+            EndgameError::TooLong(name) => write!(
+                f,
+                "Endgame table error: {} has mates longer than {} plies",
+                name, MAX_PLIES
+            ),
+        }
+    }
+}
+
+impl std::error::Error for EndgameError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        None
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // Generating takes a while, so the tests share one set of tables: KPK and every ending its promotions lead to
+    fn tables() -> &'static EndgameTables {
+        static TABLES: std::sync::OnceLock<EndgameTables> = std::sync::OnceLock::new();
+        TABLES.get_or_init(|| {
+            let mut tables = EndgameTables::default();
+            let generated = tables.generate("KPK").unwrap();
+            assert_eq!(generated.last().map(String::as_str), Some("KPK"));
+            tables
+        })
+    }
+
+    fn board(fen: &str) -> Board {
+        Board::from_fen(fen).unwrap()
+    }
+
+    #[test]
+    fn reads_endings_with_the_stronger_side_first() {
+        assert_eq!(Material::from_name("kbnk").unwrap().name(), "KBNK");
+        let (material, flip) = Material::from_name("KKQ").unwrap().canonical();
+        assert_eq!((material.name(), flip), ("KQK".to_string(), true));
+        assert!(!Material::from_name("KBKN").unwrap().canonical().1);
+        for name in ["KK", "KQRKB", "KPKP", "KXK", "QKK"] {
+            assert!(Material::from_name(name).is_err(), "{}", name);
+        }
+    }
+
+    #[test]
+    fn mirror_images_share_an_index() {
+        let material = Material::from_name("KBNK").unwrap();
+        let placement = [square(6, 2), square(0, 7), square(5, 5), square(2, 1)];
+        let index = material.index(placement, true);
+        for symmetry in 0..8 {
+            let mirrored = placement.map(|tile| mirror(tile, symmetry));
+            assert_eq!(material.index(mirrored, true), index);
+        }
+        for pawns in [false, true] {
+            for tile in 0..64 {
+                if let Some(index) = king_square_index(tile, pawns) {
+                    assert_eq!(king_square(index, pawns), tile);
+                }
+            }
+        }
+        let (stored, white_move) = material.position(index);
+        assert_eq!(material.index(stored, white_move), index);
+        // The two knights of KNNK can be listed either way round
+        let knights = Material::from_name("KNNK").unwrap();
+        let swapped = [placement[0], placement[1], placement[3], placement[2]];
+        assert_eq!(
+            knights.index(placement, false),
+            knights.index(swapped, false)
+        );
+    }
+
+    #[test]
+    fn generates_distance_to_mate() {
+        let tables = tables();
+        assert_eq!(tables.names(), ["KBK", "KNK", "KPK", "KQK", "KRK"]);
+        assert_eq!(tables.longest_mate("KQK"), Some(10));
+        assert_eq!(
+            tables.probe(&board("k7/8/1K6/8/8/8/8/6Q1 w - - 0 1")),
+            Some(Distance::Win(1))
+        );
+        assert_eq!(
+            tables.probe(&board("k7/1Q6/1K6/8/8/8/8/8 b - - 0 1")),
+            Some(Distance::Loss(0))
+        );
+        // Stalemate, and the queen can be taken
+        assert_eq!(
+            tables.probe(&board("k7/2Q5/1K6/8/8/8/8/8 b - - 0 1")),
+            Some(Distance::Draw)
+        );
+        assert_eq!(
+            tables.probe(&board("k7/1Q6/8/8/8/8/8/6K1 b - - 0 1")),
+            Some(Distance::Draw)
+        );
+        // Black's queen is looked up with the colours swapped
+        assert_eq!(
+            tables.probe(&board("K7/8/1k6/8/8/8/8/6q1 b - - 0 1")),
+            Some(Distance::Win(1))
+        );
+        assert_eq!(
+            tables.probe(&board("k7/8/1K6/8/8/8/8/5RQ1 w - - 0 1")),
+            None
+        );
+    }
+
+    #[test]
+    fn generates_pawn_endings() {
+        let tables = tables();
+        assert_eq!(tables.longest_mate("KPK"), Some(28));
+        assert_eq!(tables.longest_mate("KBK"), None);
+
+        // With the king on the sixth rank in front of its pawn white wins whoever is to move
+        let in_front = tables.probe(&board("4k3/8/4K3/4P3/8/8/8/8 b - - 0 1"));
+        assert!(matches!(in_front, Some(Distance::Loss(_))));
+        assert!(matches!(
+            tables.probe(&board("4k3/8/4K3/4P3/8/8/8/8 w - - 0 1")),
+            Some(Distance::Win(_))
+        ));
+        // The same position a file over is its mirror image
+        assert_eq!(
+            tables.probe(&board("3k4/8/3K4/3P4/8/8/8/8 b - - 0 1")),
+            in_front
+        );
+        // A rook's pawn with the defending king in the corner, and a stalemate
+        assert_eq!(
+            tables.probe(&board("k7/8/8/8/8/8/P7/K7 w - - 0 1")),
+            Some(Distance::Draw)
+        );
+        assert_eq!(
+            tables.probe(&board("4k3/4P3/4K3/8/8/8/8/8 b - - 0 1")),
+            Some(Distance::Draw)
+        );
+        // Black's pawn is looked up with the board turned around
+        assert_eq!(
+            tables.probe(&board("8/8/8/8/4p3/4k3/8/4K3 w - - 0 1")),
+            in_front
+        );
+
+        // Every position's result is the best result of its moves, which only holds if pawn moves were followed back correctly
+        let material = Material::from_name("KPK").unwrap();
+        for index in (0..material.size()).step_by(11) {
+            let (placement, white_move) = material.position(index);
+            let mut board = match material.board(placement, white_move) {
+                Some(board) if material.index(placement, white_move) == index => board,
+                _ => continue,
+            };
+            let expected = match tables.best_move(&board) {
+                Some((chess_move, _)) => {
+                    board.make_move(chess_move);
+                    tables.probe(&board).unwrap().before_move()
+                }
+                None if board.is_in_check(white_move) => Distance::Loss(0),
+                None => Distance::Draw,
+            };
+            assert_eq!(
+                Distance::from_byte(tables.tables["KPK"][index]),
+                expected,
+                "{}",
+                board.to_fen()
+            );
+        }
+    }
+
+    #[test]
+    fn best_move_mates_quickest() {
+        let tables = tables();
+        let mating = board("k7/8/1K6/8/8/8/8/6Q1 w - - 0 1");
+        let (chess_move, distance) = tables.best_move(&mating).unwrap();
+        assert_eq!(distance, Distance::Win(1));
+        assert_eq!(mating.to_san(&chess_move), "Qg8#");
+
+        // The losing side holds out as long as it can
+        let mut losing = board("8/8/8/3k4/8/8/8/KQ6 b - - 0 1");
+        let (chess_move, distance) = tables.best_move(&losing).unwrap();
+        let Distance::Loss(plies) = distance else {
+            panic!("expected a loss, got {:?}", distance);
+        };
+        losing.make_move(chess_move);
+        assert_eq!(tables.probe(&losing), Some(Distance::Win(plies - 1)));
+        assert_eq!(Distance::Loss(18).describe(), "loss, mated in 9");
+        assert_eq!(Distance::Win(19).describe(), "win, mate in 10");
+    }
+
+    #[test]
+    fn saves_and_loads_tables() {
+        let directory =
+            std::env::temp_dir().join(format!("terminal_chess_endgame_{}", std::process::id()));
+        let directory = directory.to_string_lossy().to_string();
+        tables().save(&directory).unwrap();
+        let loaded = EndgameTables::load(&directory).unwrap();
+        assert_eq!(loaded.tables, tables().tables);
+
+        std::fs::write(Path::new(&directory).join("KRK.dtm"), [0; 16]).unwrap();
+        assert!(matches!(
+            EndgameTables::load(&directory),
+            Err(EndgameError::Corrupt(_))
+        ));
+        std::fs::remove_dir_all(&directory).unwrap();
+        assert!(EndgameTables::load(&directory).is_err());
+    }
+}
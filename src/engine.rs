@@ -0,0 +1,1097 @@
+// The computer opponent: a negamax alpha-beta search over the legal moves of a Board with a material and piece-square-table evaluation
+
+use std::sync::atomic::{AtomicBool, AtomicU64, Ordering};
+use std::sync::Arc;
+use std::thread::{self, JoinHandle};
+use std::time::{Duration, Instant};
+
+use rand::seq::SliceRandom;
+use rand::Rng;
+
+use crate::bitboard::{square, squares};
+use crate::chess::{Board, ChessPieces, GameState, Move, Variant, POCKET_PIECES};
+use crate::endgame::{Distance, EndgameTables};
+use crate::syzygy::{Tablebase, Wdl};
+
+// Score for being checkmated, a mate found further from the root scores a little closer to zero so quicker mates are preferred
+pub const MATE_SCORE: i32 = 30_000;
+// Larger than any score a search can return
+const INFINITY: i32 = MATE_SCORE + 1;
+// Scores this close to MATE_SCORE are forced mates rather than evaluations
+const MAX_MATE_PLY: i32 = 1_000;
+// Score for a position the endgame tablebases say is won, below any mate so a mate the search sees is still preferred
+// Like mates, wins further from the root score a little closer to zero
+const TABLEBASE_WIN: i32 = MATE_SCORE - 2 * MAX_MATE_PLY;
+
+// The deepest any search goes, well past anything that finishes in time
+const MAX_DEPTH: u32 = 64;
+
+// How many positions the transposition table holds, a power of two so a hash can be masked down to an index
+const TABLE_SIZE: usize = 1 << 20;
+
+// How many nodes each thread visits between adding them to the shared count and checking the budget
+const BUDGET_CHECK_INTERVAL: u64 = 1024;
+
+// Once this little material besides kings and pawns is left the kings should head for the centre
+const ENDGAME_MATERIAL: i32 = 1_300;
+
+// How much each piece is worth in centipawns, kings only count when ordering captures
+fn piece_value(symbol: ChessPieces) -> i32 {
+    match symbol {
+        ChessPieces::Pawn => 100,
+        ChessPieces::Knight => 320,
+        ChessPieces::Bishop => 330,
+        ChessPieces::Rook => 500,
+        ChessPieces::Queen => 900,
+        ChessPieces::King => 2_000,
+        ChessPieces::None => 0,
+    }
+}
+
+// Bonuses for where each piece stands, written from white's side with a8 first so a square index reads the table directly
+// Black looks its squares up with the ranks flipped
+#[rustfmt::skip]
+const PAWN_TABLE: [i32; 64] = [
+      0,   0,   0,   0,   0,   0,   0,   0,
+     50,  50,  50,  50,  50,  50,  50,  50,
+     10,  10,  20,  30,  30,  20,  10,  10,
+      5,   5,  10,  25,  25,  10,   5,   5,
+      0,   0,   0,  20,  20,   0,   0,   0,
+      5,  -5, -10,   0,   0, -10,  -5,   5,
+      5,  10,  10, -20, -20,  10,  10,   5,
+      0,   0,   0,   0,   0,   0,   0,   0,
+];
+
+#[rustfmt::skip]
+const KNIGHT_TABLE: [i32; 64] = [
+    -50, -40, -30, -30, -30, -30, -40, -50,
+    -40, -20,   0,   0,   0,   0, -20, -40,
+    -30,   0,  10,  15,  15,  10,   0, -30,
+    -30,   5,  15,  20,  20,  15,   5, -30,
+    -30,   0,  15,  20,  20,  15,   0, -30,
+    -30,   5,  10,  15,  15,  10,   5, -30,
+    -40, -20,   0,   5,   5,   0, -20, -40,
+    -50, -40, -30, -30, -30, -30, -40, -50,
+];
+
+#[rustfmt::skip]
+const BISHOP_TABLE: [i32; 64] = [
+    -20, -10, -10, -10, -10, -10, -10, -20,
+    -10,   0,   0,   0,   0,   0,   0, -10,
+    -10,   0,   5,  10,  10,   5,   0, -10,
+    -10,   5,   5,  10,  10,   5,   5, -10,
+    -10,   0,  10,  10,  10,  10,   0, -10,
+    -10,  10,  10,  10,  10,  10,  10, -10,
+    -10,   5,   0,   0,   0,   0,   5, -10,
+    -20, -10, -10, -10, -10, -10, -10, -20,
+];
+
+#[rustfmt::skip]
+const ROOK_TABLE: [i32; 64] = [
+      0,   0,   0,   0,   0,   0,   0,   0,
+      5,  10,  10,  10,  10,  10,  10,   5,
+     -5,   0,   0,   0,   0,   0,   0,  -5,
+     -5,   0,   0,   0,   0,   0,   0,  -5,
+     -5,   0,   0,   0,   0,   0,   0,  -5,
+     -5,   0,   0,   0,   0,   0,   0,  -5,
+     -5,   0,   0,   0,   0,   0,   0,  -5,
+      0,   0,   0,   5,   5,   0,   0,   0,
+];
+
+#[rustfmt::skip]
+const QUEEN_TABLE: [i32; 64] = [
+    -20, -10, -10,  -5,  -5, -10, -10, -20,
+    -10,   0,   0,   0,   0,   0,   0, -10,
+    -10,   0,   5,   5,   5,   5,   0, -10,
+     -5,   0,   5,   5,   5,   5,   0,  -5,
+      0,   0,   5,   5,   5,   5,   0,  -5,
+    -10,   5,   5,   5,   5,   5,   0, -10,
+    -10,   0,   5,   0,   0,   0,   0, -10,
+    -20, -10, -10,  -5,  -5, -10, -10, -20,
+];
+
+// The king hides behind its pawns while there is plenty of material left
+#[rustfmt::skip]
+const KING_MIDDLEGAME_TABLE: [i32; 64] = [
+    -30, -40, -40, -50, -50, -40, -40, -30,
+    -30, -40, -40, -50, -50, -40, -40, -30,
+    -30, -40, -40, -50, -50, -40, -40, -30,
+    -30, -40, -40, -50, -50, -40, -40, -30,
+    -20, -30, -30, -40, -40, -30, -30, -20,
+    -10, -20, -20, -20, -20, -20, -20, -10,
+     20,  20,   0,   0,   0,   0,  20,  20,
+     20,  30,  10,   0,   0,  10,  30,  20,
+];
+
+// And comes out to the centre once the board empties
+#[rustfmt::skip]
+const KING_ENDGAME_TABLE: [i32; 64] = [
+    -50, -40, -30, -20, -20, -30, -40, -50,
+    -30, -20, -10,   0,   0, -10, -20, -30,
+    -30, -10,  20,  30,  30,  20, -10, -30,
+    -30, -10,  30,  40,  40,  30, -10, -30,
+    -30, -10,  30,  40,  40,  30, -10, -30,
+    -30, -10,  20,  30,  30,  20, -10, -30,
+    -30, -30,   0,   0,   0,   0, -30, -30,
+    -50, -30, -30, -30, -30, -30, -30, -50,
+];
+
+// Scores the position in centipawns from the point of view of the side to move
+// Counts material, including pieces held in Crazyhouse pockets, plus where each piece stands
+pub fn evaluate(board: &Board) -> i32 {
+    let officers = [
+        ChessPieces::Queen,
+        ChessPieces::Rook,
+        ChessPieces::Bishop,
+        ChessPieces::Knight,
+    ];
+    let material = |white: bool| -> i32 {
+        officers
+            .iter()
+            .map(|symbol| {
+                board.pieces_of(white, *symbol).count_ones() as i32 * piece_value(*symbol)
+            })
+            .sum()
+    };
+    let king_table = if material(true) + material(false) <= ENDGAME_MATERIAL {
+        &KING_ENDGAME_TABLE
+    } else {
+        &KING_MIDDLEGAME_TABLE
+    };
+    let tables = [
+        (ChessPieces::Pawn, &PAWN_TABLE),
+        (ChessPieces::Knight, &KNIGHT_TABLE),
+        (ChessPieces::Bishop, &BISHOP_TABLE),
+        (ChessPieces::Rook, &ROOK_TABLE),
+        (ChessPieces::Queen, &QUEEN_TABLE),
+        (ChessPieces::King, king_table),
+    ];
+
+    let mut score = 0;
+    for white in [true, false] {
+        let sign = if white { 1 } else { -1 };
+        for (symbol, table) in tables {
+            let value = if symbol == ChessPieces::King {
+                0
+            } else {
+                piece_value(symbol)
+            };
+            for square in squares(board.pieces_of(white, symbol)) {
+                let index = if white { square } else { square ^ 56 };
+                score += sign * (value + table[index]);
+            }
+        }
+        if board.variant == Variant::Crazyhouse {
+            for symbol in POCKET_PIECES {
+                score += sign * piece_value(symbol) * board.pocket_count(white, symbol) as i32;
+            }
+        }
+    }
+
+    // Antichess is won by giving material away
+    if board.variant == Variant::Antichess {
+        score = -score;
+    }
+    if board.white_move {
+        score
+    } else {
+        -score
+    }
+}
+
+// How far and how long a search may go, it stops at whichever limit it reaches first
+// The first iteration always finishes so there is a move to play however tight the budget
+#[derive(Clone, Copy, Debug)]
+pub struct SearchLimits {
+    pub depth: u32,
+    pub nodes: Option<u64>,
+    pub time: Option<Duration>,
+    // How many threads search together
+    pub threads: usize,
+}
+
+impl SearchLimits {
+    // Searches exactly to the given depth, however many nodes and however long that takes
+    pub fn depth(depth: u32) -> Self {
+        Self {
+            depth,
+            nodes: None,
+            time: None,
+            threads: 1,
+        }
+    }
+}
+
+// How strongly the computer opponent plays, from blundering every few moves up to searching as deep as its time allows
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum Difficulty {
+    Beginner,
+    Casual,
+    #[default]
+    Club,
+    Expert,
+    FullStrength,
+}
+
+impl Difficulty {
+    pub const ALL: [Difficulty; 5] = [
+        Difficulty::Beginner,
+        Difficulty::Casual,
+        Difficulty::Club,
+        Difficulty::Expert,
+        Difficulty::FullStrength,
+    ];
+
+    pub fn name(self) -> &'static str {
+        match self {
+            Difficulty::Beginner => "Beginner",
+            Difficulty::Casual => "Casual",
+            Difficulty::Club => "Club",
+            Difficulty::Expert => "Expert",
+            Difficulty::FullStrength => "Full strength",
+        }
+    }
+
+    // The level after this one, wrapping back round to the first, for cycling through them with a button
+    pub fn next(self) -> Self {
+        let index = Difficulty::ALL
+            .iter()
+            .position(|level| *level == self)
+            .unwrap();
+        Difficulty::ALL[(index + 1) % Difficulty::ALL.len()]
+    }
+
+    // How deep and how long the computer searches at this level
+    pub fn limits(self) -> SearchLimits {
+        match self {
+            Difficulty::Beginner => SearchLimits::depth(1),
+            Difficulty::Casual => SearchLimits::depth(2),
+            Difficulty::Club => SearchLimits::depth(3),
+            Difficulty::Expert => SearchLimits {
+                depth: 6,
+                nodes: None,
+                time: Some(Duration::from_secs(1)),
+                threads: 1,
+            },
+            Difficulty::FullStrength => SearchLimits {
+                depth: MAX_DEPTH,
+                nodes: None,
+                time: Some(Duration::from_secs(3)),
+                threads: 1,
+            },
+        }
+    }
+
+    // The chance of ignoring the search and playing a random legal move instead
+    fn blunder_chance(self) -> f64 {
+        match self {
+            Difficulty::Beginner => 0.3,
+            Difficulty::Casual => 0.15,
+            Difficulty::Club => 0.05,
+            Difficulty::Expert | Difficulty::FullStrength => 0.0,
+        }
+    }
+}
+
+// What a search found: the best move, None when there are no legal moves, and its score for the side to move
+// Along with the deepest iteration that finished and how many positions were visited
+#[derive(Clone, Copy, Debug)]
+pub struct SearchResult {
+    pub best_move: Option<Move>,
+    pub score: i32,
+    pub depth: u32,
+    pub nodes: u64,
+}
+
+// Writes a score the way players read it, in pawns, as a forced mate counted in moves or as a tablebase result
+pub fn describe_score(score: i32) -> String {
+    match mate_distance(score) {
+        Some(plies) if score > 0 => format!("mate in {}", (plies + 1) / 2),
+        Some(plies) => format!("mated in {}", (plies + 1) / 2),
+        None if is_distance_score(score) && score > 0 => "tablebase win".to_string(),
+        None if is_distance_score(score) => "tablebase loss".to_string(),
+        None => format!("{:+.2}", score as f64 / 100.0),
+    }
+}
+
+// How many plies away the mate a score stands for is, or None when the score is an evaluation
+fn mate_distance(score: i32) -> Option<i32> {
+    let plies = MATE_SCORE - score.abs();
+    (plies <= MAX_MATE_PLY).then_some(plies)
+}
+
+// Whether a score is a mate or a tablebase result, which depend on how far from the root they were found
+fn is_distance_score(score: i32) -> bool {
+    score.abs() > TABLEBASE_WIN - MAX_MATE_PLY
+}
+
+// The score of a tablebase result at the given ply, cursed wins and blessed losses are draws under the fifty move rule
+fn tablebase_score(wdl: Wdl, ply: i32) -> i32 {
+    match wdl {
+        Wdl::Win => TABLEBASE_WIN - ply,
+        Wdl::Loss => -TABLEBASE_WIN + ply,
+        Wdl::CursedWin | Wdl::Draw | Wdl::BlessedLoss => 0,
+    }
+}
+
+// The score of a position the distance to mate tables cover, which is an exact mate score
+fn distance_score(distance: Distance) -> i32 {
+    match distance {
+        Distance::Win(plies) => MATE_SCORE - plies as i32,
+        Distance::Loss(plies) => -MATE_SCORE + plies as i32,
+        Distance::Draw => 0,
+    }
+}
+
+// Picks the computer's move at the given level, the weaker levels sometimes throw the search away and blunder
+// Setting the stop flag makes the search play the best move it has found so far, the flag is set once it returns
+pub fn choose_move(
+    board: &Board,
+    difficulty: Difficulty,
+    threads: usize,
+    tablebase: Option<&Tablebase>,
+    endgames: Option<&EndgameTables>,
+    stop: &AtomicBool,
+) -> SearchResult {
+    let limits = SearchLimits {
+        threads,
+        ..difficulty.limits()
+    };
+    let mut result = search_until_stopped(board, limits, tablebase, endgames, stop);
+    let mut rng = rand::thread_rng();
+    if rng.gen_bool(difficulty.blunder_chance()) {
+        if let Some(chess_move) = board.legal_moves().choose(&mut rng) {
+            result.best_move = Some(*chess_move);
+        }
+    }
+    result
+}
+
+// Searches one ply deeper at a time until the limits run out or the stop flag is set, which it sets itself on returning
+// Returns the best move of the deepest iteration that finished
+// With more than one thread the extra threads search the same position alongside the main one, sharing what they find
+// through the transposition table, half of them a ply deeper so they fill the table ahead of it (Lazy SMP)
+// With tablebases a position they cover isn't searched at all, the move that wins quickest under the fifty move rule is played,
+// and the search scores positions it reaches with a capture or pawn move by looking them up
+// Distance to mate tables come first when they cover the position, since they play the quickest mate itself,
+// unless the fifty move rule would let the game be drawn before the mate comes
+pub fn search_until_stopped(
+    board: &Board,
+    limits: SearchLimits,
+    tablebase: Option<&Tablebase>,
+    endgames: Option<&EndgameTables>,
+    stop: &AtomicBool,
+) -> SearchResult {
+    let endgame_move = endgames
+        .and_then(|endgames| endgames.best_move(board))
+        .filter(|(_, distance)| match distance {
+            Distance::Win(plies) | Distance::Loss(plies) => board.halfmove_clock + plies <= 100,
+            Distance::Draw => true,
+        });
+    if let Some((chess_move, distance)) = endgame_move {
+        stop.store(true, Ordering::Relaxed);
+        return SearchResult {
+            best_move: Some(chess_move),
+            score: distance_score(distance),
+            depth: 0,
+            nodes: 0,
+        };
+    }
+
+    if let Some((chess_move, dtz)) = tablebase.and_then(|tablebase| tablebase.best_move(board)) {
+        stop.store(true, Ordering::Relaxed);
+        // A win or loss the fifty move rule would cut short is only a draw
+        let wdl = if dtz == 0 || dtz.abs() + board.halfmove_clock as i32 > 100 {
+            Wdl::Draw
+        } else if dtz > 0 {
+            Wdl::Win
+        } else {
+            Wdl::Loss
+        };
+        return SearchResult {
+            best_move: Some(chess_move),
+            score: tablebase_score(wdl, 0),
+            depth: 0,
+            nodes: 0,
+        };
+    }
+
+    let shared = SharedSearch {
+        table: TranspositionTable::new(),
+        tablebase,
+        stop,
+        nodes: AtomicU64::new(0),
+        start: Instant::now(),
+    };
+    thread::scope(|scope| {
+        for helper in 1..limits.threads.max(1) {
+            let mut searcher = Searcher::new(board, limits, &shared, true);
+            scope.spawn(move || searcher.iterative_deepening(1 + helper as u32 % 2));
+        }
+        let mut searcher = Searcher::new(board, limits, &shared, false);
+        let mut result = searcher.iterative_deepening(1);
+        stop.store(true, Ordering::Relaxed);
+        result.nodes = shared.nodes.load(Ordering::Relaxed) + searcher.unreported_nodes();
+        result
+    })
+}
+
+// A search running on threads of its own, so the game screen stays responsive while the computer thinks
+pub struct BackgroundSearch {
+    stop: Arc<AtomicBool>,
+    handle: JoinHandle<SearchResult>,
+    // The position being searched, so a search of a position that is gone can be told apart
+    pub hash: u64,
+}
+
+impl BackgroundSearch {
+    // Starts looking for the computer's move in the position
+    pub fn start(
+        board: &Board,
+        difficulty: Difficulty,
+        threads: usize,
+        tablebase: Option<Arc<Tablebase>>,
+        endgames: Option<Arc<EndgameTables>>,
+    ) -> Self {
+        let stop = Arc::new(AtomicBool::new(false));
+        let board = board.clone();
+        let hash = board.hash();
+        let thread_stop = Arc::clone(&stop);
+        let handle = thread::spawn(move || {
+            choose_move(
+                &board,
+                difficulty,
+                threads,
+                tablebase.as_deref(),
+                endgames.as_deref(),
+                &thread_stop,
+            )
+        });
+        Self { stop, handle, hash }
+    }
+
+    pub fn is_finished(&self) -> bool {
+        self.handle.is_finished()
+    }
+
+    // Asks the search to finish now with the best move it has found so far
+    pub fn stop(&self) {
+        self.stop.store(true, Ordering::Relaxed);
+    }
+
+    // Waits for the search to finish and returns what it found
+    pub fn result(self) -> SearchResult {
+        self.handle.join().expect("search thread panicked")
+    }
+}
+
+// Whether a stored score is exact or only a bound, because the search of that position was cut off early
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+enum Bound {
+    Exact,
+    Lower,
+    Upper,
+}
+
+#[derive(Clone, Copy, Debug)]
+struct TableEntry {
+    depth: u32,
+    score: i32,
+    bound: Bound,
+    // The move found best, or the one that caused a cut-off, as packed by move_code
+    best_move: Option<u32>,
+}
+
+impl TableEntry {
+    // Packs the entry into 64 bits: the score in the low 16, then the depth, the bound and the move
+    fn pack(self) -> u64 {
+        let bound = match self.bound {
+            Bound::Exact => 0,
+            Bound::Lower => 1,
+            Bound::Upper => 2,
+        };
+        (self.score as i16 as u16 as u64)
+            | (self.depth.min(255) as u64) << 16
+            | bound << 24
+            | (self.best_move.unwrap_or(0) as u64) << 26
+    }
+
+    fn unpack(data: u64) -> Self {
+        let bound = match (data >> 24) & 0b11 {
+            0 => Bound::Exact,
+            1 => Bound::Lower,
+            _ => Bound::Upper,
+        };
+        let best_move = (data >> 26) as u32;
+        Self {
+            depth: ((data >> 16) & 0xff) as u32,
+            score: data as u16 as i16 as i32,
+            bound,
+            best_move: (best_move != 0).then_some(best_move),
+        }
+    }
+}
+
+// One entry of the transposition table, the key is the position's hash xored with the data
+// so an entry torn by two threads writing it at once fails the hash check instead of being read back wrong
+#[derive(Default)]
+struct TableSlot {
+    key: AtomicU64,
+    data: AtomicU64,
+}
+
+// Remembers what the search found about each position, keyed by Zobrist hash
+// so positions reached again by another move order aren't searched again, and the best move found is tried first next time
+// Shared by every search thread without locking
+struct TranspositionTable {
+    slots: Vec<TableSlot>,
+}
+
+impl TranspositionTable {
+    fn new() -> Self {
+        Self {
+            slots: (0..TABLE_SIZE).map(|_| TableSlot::default()).collect(),
+        }
+    }
+
+    fn probe(&self, hash: u64) -> Option<TableEntry> {
+        let slot = &self.slots[hash as usize & (TABLE_SIZE - 1)];
+        let data = slot.data.load(Ordering::Relaxed);
+        let key = slot.key.load(Ordering::Relaxed);
+        (key ^ data == hash).then(|| TableEntry::unpack(data))
+    }
+
+    // Newer entries always replace older ones, they come from deeper iterations
+    fn store(&self, hash: u64, entry: TableEntry) {
+        let slot = &self.slots[hash as usize & (TABLE_SIZE - 1)];
+        let data = entry.pack();
+        slot.key.store(hash ^ data, Ordering::Relaxed);
+        slot.data.store(data, Ordering::Relaxed);
+    }
+}
+
+// Packs the parts of a move that tell it apart from the position's other legal moves into 20 bits, never 0
+fn move_code(chess_move: &Move) -> u32 {
+    let square_of = |(rank, file): (usize, usize)| square(rank, file) as u32;
+    let piece_code = |symbol: Option<ChessPieces>| match symbol {
+        Some(ChessPieces::King) => 1,
+        Some(ChessPieces::Queen) => 2,
+        Some(ChessPieces::Rook) => 3,
+        Some(ChessPieces::Bishop) => 4,
+        Some(ChessPieces::Knight) => 5,
+        Some(ChessPieces::Pawn) => 6,
+        Some(ChessPieces::None) | None => 0,
+    };
+    1 << 19
+        | square_of(chess_move.from)
+        | square_of(chess_move.to) << 6
+        | piece_code(chess_move.promotion) << 12
+        | piece_code(Some(chess_move.piece)) << 15
+        | (chess_move.drop as u32) << 18
+}
+
+// Mate and tablebase scores count plies from the root, the table keeps them counted from the stored position instead
+// so they stay right when the position turns up again at a different ply
+fn score_to_table(score: i32, ply: i32) -> i32 {
+    match is_distance_score(score) {
+        true if score > 0 => score + ply,
+        true => score - ply,
+        false => score,
+    }
+}
+
+fn score_from_table(score: i32, ply: i32) -> i32 {
+    match is_distance_score(score) {
+        true if score > 0 => score - ply,
+        true => score + ply,
+        false => score,
+    }
+}
+
+// What every thread of one search shares
+struct SharedSearch<'a> {
+    table: TranspositionTable,
+    tablebase: Option<&'a Tablebase>,
+    stop: &'a AtomicBool,
+    // Nodes visited by all threads, each thread adds its count in batches
+    nodes: AtomicU64,
+    start: Instant,
+}
+
+// The state of one search thread, the board is played forwards and back with make and unmake as the tree is walked
+struct Searcher<'a> {
+    board: Board,
+    nodes: u64,
+    // Hashes of every position from the start of the game to the current node, for spotting repetitions
+    path: Vec<u64>,
+    limits: SearchLimits,
+    shared: &'a SharedSearch<'a>,
+    // Helper threads only fill the transposition table, their results are thrown away
+    helper: bool,
+    // The deepest iteration that has finished, the main thread doesn't stop until the first one has
+    completed_depth: u32,
+    // Set once the budget runs out or the search is stopped, every node then returns straight away and the iteration is thrown away
+    stopped: bool,
+}
+
+impl<'a> Searcher<'a> {
+    fn new(
+        board: &Board,
+        limits: SearchLimits,
+        shared: &'a SharedSearch<'a>,
+        helper: bool,
+    ) -> Self {
+        Self {
+            board: board.clone(),
+            nodes: 0,
+            path: board.history.clone(),
+            limits,
+            shared,
+            helper,
+            completed_depth: 0,
+            stopped: false,
+        }
+    }
+
+    // Searches one ply deeper at a time from the first depth until the limits run out or a mate is found
+    fn iterative_deepening(&mut self, first_depth: u32) -> SearchResult {
+        let mut result = SearchResult {
+            best_move: None,
+            score: self.no_moves_score(0),
+            depth: 0,
+            nodes: 0,
+        };
+
+        for depth in first_depth..=self.limits.depth.clamp(1, MAX_DEPTH) {
+            let (best_move, score) = match self.search_root(depth, result.best_move) {
+                Some(found) => found,
+                None => break,
+            };
+            result.best_move = Some(best_move);
+            result.score = score;
+            result.depth = depth;
+            self.completed_depth = depth;
+            // Every mate this close has been seen, so searching deeper can't find a quicker one
+            if mate_distance(score).is_some_and(|plies| plies <= depth as i32) {
+                break;
+            }
+        }
+        result
+    }
+
+    // Searches every root move to the given depth, the best move from the last iteration first
+    // Returns None when there are no legal moves or the search stopped before the iteration finished
+    fn search_root(&mut self, depth: u32, previous_best: Option<Move>) -> Option<(Move, i32)> {
+        let mut moves = self.board.generate_legal_moves();
+        order_moves(&mut moves, previous_best.as_ref().map(move_code));
+
+        let mut best = None;
+        let mut alpha = -INFINITY;
+        for chess_move in moves {
+            let score = -self.search_child(chess_move, depth - 1, 1, -INFINITY, -alpha);
+            if self.stopped {
+                return None;
+            }
+            if score > alpha || best.is_none() {
+                alpha = score;
+                best = Some(chess_move);
+            }
+        }
+        best.map(|chess_move| (chess_move, alpha))
+    }
+
+    // Plays the move, searches the position it leads to and takes it back again
+    fn search_child(
+        &mut self,
+        chess_move: Move,
+        depth: u32,
+        ply: i32,
+        alpha: i32,
+        beta: i32,
+    ) -> i32 {
+        let undo = self.board.make_move(chess_move);
+        self.path.push(self.board.hash());
+        let score = self.negamax(depth, ply, alpha, beta);
+        self.path.pop();
+        self.board.unmake_move(chess_move, undo);
+        score
+    }
+
+    // Returns the score of the position for the side to move, as long as it lies between alpha and beta
+    fn negamax(&mut self, depth: u32, ply: i32, mut alpha: i32, beta: i32) -> i32 {
+        self.nodes += 1;
+        if self.out_of_budget() {
+            return 0;
+        }
+        if let Some(score) = self.decided_score(ply) {
+            return score;
+        }
+        if depth == 0 {
+            return self.quiescence(ply, alpha, beta);
+        }
+        if let Some(score) = self.probe_tablebase(ply) {
+            return score;
+        }
+
+        let hash = self.board.hash();
+        let entry = self.shared.table.probe(hash);
+        if let Some(entry) = entry.filter(|entry| entry.depth >= depth) {
+            let score = score_from_table(entry.score, ply);
+            match entry.bound {
+                Bound::Exact => return score,
+                Bound::Lower if score >= beta => return score,
+                Bound::Upper if score <= alpha => return score,
+                _ => {}
+            }
+        }
+
+        let mut moves = self.board.generate_legal_moves();
+        if moves.is_empty() {
+            return self.no_moves_score(ply);
+        }
+        order_moves(&mut moves, entry.and_then(|entry| entry.best_move));
+
+        let mut best_move = None;
+        for chess_move in moves {
+            let score = -self.search_child(chess_move, depth - 1, ply + 1, -beta, -alpha);
+            if self.stopped {
+                return 0;
+            }
+            if score >= beta {
+                self.shared.table.store(
+                    hash,
+                    TableEntry {
+                        depth,
+                        score: score_to_table(beta, ply),
+                        bound: Bound::Lower,
+                        best_move: Some(move_code(&chess_move)),
+                    },
+                );
+                return beta;
+            }
+            if score > alpha {
+                alpha = score;
+                best_move = Some(move_code(&chess_move));
+            }
+        }
+
+        self.shared.table.store(
+            hash,
+            TableEntry {
+                depth,
+                score: score_to_table(alpha, ply),
+                bound: if best_move.is_some() {
+                    Bound::Exact
+                } else {
+                    Bound::Upper
+                },
+                best_move,
+            },
+        );
+        alpha
+    }
+
+    // Keeps following captures and promotions until the position is quiet, so the evaluation isn't taken mid exchange
+    // The side to move may stand pat on the evaluation instead of capturing, unless it is in check
+    fn quiescence(&mut self, ply: i32, mut alpha: i32, beta: i32) -> i32 {
+        self.nodes += 1;
+        if self.out_of_budget() {
+            return 0;
+        }
+        if let Some(score) = self.decided_score(ply) {
+            return score;
+        }
+
+        let in_check = self.board.is_in_check(self.board.white_move);
+        let mut moves = self.board.generate_legal_moves();
+        if moves.is_empty() {
+            return self.no_moves_score(ply);
+        }
+        if !in_check {
+            let stand_pat = evaluate(&self.board);
+            if stand_pat >= beta {
+                return beta;
+            }
+            alpha = alpha.max(stand_pat);
+            moves.retain(|chess_move| {
+                chess_move.capture.is_some() || chess_move.promotion.is_some()
+            });
+        }
+        order_moves(&mut moves, None);
+
+        for chess_move in moves {
+            let undo = self.board.make_move(chess_move);
+            self.path.push(self.board.hash());
+            let score = -self.quiescence(ply + 1, -beta, -alpha);
+            self.path.pop();
+            self.board.unmake_move(chess_move, undo);
+            if self.stopped {
+                return 0;
+            }
+            if score >= beta {
+                return beta;
+            }
+            alpha = alpha.max(score);
+        }
+        alpha
+    }
+
+    // Checks whether the search has been stopped or the node or time budget has run out, and if so stops this thread
+    // The main thread keeps going until its first iteration is done so there is always a move to play
+    fn out_of_budget(&mut self) -> bool {
+        if self.stopped {
+            return true;
+        }
+        if self.nodes.is_multiple_of(BUDGET_CHECK_INTERVAL) {
+            self.shared
+                .nodes
+                .fetch_add(BUDGET_CHECK_INTERVAL, Ordering::Relaxed);
+            let nodes = self.shared.nodes.load(Ordering::Relaxed);
+            let out_of_nodes = self.limits.nodes.is_some_and(|limit| nodes >= limit);
+            let out_of_time = self
+                .limits
+                .time
+                .is_some_and(|time| self.shared.start.elapsed() >= time);
+            if out_of_nodes || out_of_time {
+                self.shared.stop.store(true, Ordering::Relaxed);
+            }
+        }
+        if self.completed_depth == 0 && !self.helper {
+            return false;
+        }
+        self.stopped = self.shared.stop.load(Ordering::Relaxed);
+        self.stopped
+    }
+
+    // The nodes this thread has visited since it last added them to the shared count
+    fn unreported_nodes(&self) -> u64 {
+        self.nodes % BUDGET_CHECK_INTERVAL
+    }
+
+    // The score of a position the rules have already decided, won under a variant's rules or drawn by repetition or the fifty-move rule
+    fn decided_score(&self, ply: i32) -> Option<i32> {
+        if let Some(GameState::Won { white, .. }) = self.board.variant_result() {
+            return Some(if white == self.board.white_move {
+                MATE_SCORE - ply
+            } else {
+                -MATE_SCORE + ply
+            });
+        }
+
+        let hash = self.board.hash();
+        let reversible_plies = self.board.halfmove_clock as usize;
+        let repeated = self
+            .path
+            .iter()
+            .rev()
+            .skip(1)
+            .take(reversible_plies)
+            .any(|earlier| *earlier == hash);
+        if repeated || self.board.halfmove_clock >= 100 {
+            return Some(0);
+        }
+        None
+    }
+
+    // Looks up a position the tablebases cover, but only straight after a capture or pawn move
+    // as they know nothing of the fifty move count, and a position any later than that is left to the search
+    fn probe_tablebase(&self, ply: i32) -> Option<i32> {
+        let tablebase = self.shared.tablebase?;
+        if self.board.halfmove_clock != 0 {
+            return None;
+        }
+        tablebase
+            .probe_wdl(&self.board)
+            .map(|wdl| tablebase_score(wdl, ply))
+    }
+
+    // The score when the side to move has no legal moves, a loss when checkmated and a draw otherwise
+    // Except in Antichess, where running out of moves wins
+    fn no_moves_score(&self, ply: i32) -> i32 {
+        if self.board.variant == Variant::Antichess {
+            MATE_SCORE - ply
+        } else if self.board.is_in_check(self.board.white_move) {
+            -MATE_SCORE + ply
+        } else {
+            0
+        }
+    }
+}
+
+// Puts the most promising moves first so alpha-beta can cut off the rest sooner
+// The best move found by an earlier search of the position goes first, then captures,
+// most valuable victim first and then least valuable attacker first, then promotions
+fn order_moves(moves: &mut [Move], best_move: Option<u32>) {
+    moves.sort_by_key(|chess_move| {
+        let first = Some(move_code(chess_move)) == best_move;
+        std::cmp::Reverse((first, move_order_score(chess_move)))
+    });
+}
+
+fn move_order_score(chess_move: &Move) -> i32 {
+    let mut score = 0;
+    if let Some(capture) = chess_move.capture {
+        score += 10_000 + 10 * piece_value(capture) - piece_value(chess_move.piece) / 10;
+    }
+    if let Some(promotion) = chess_move.promotion {
+        score += piece_value(promotion);
+    }
+    score
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn search(board: &Board, limits: SearchLimits) -> SearchResult {
+        search_until_stopped(board, limits, None, None, &AtomicBool::new(false))
+    }
+
+    #[test]
+    fn finds_mate_in_one() {
+        let board = Board::from_fen("6k1/5ppp/8/8/8/8/8/R5K1 w - - 0 1").unwrap();
+        let result = search(&board, SearchLimits::depth(2));
+        assert_eq!(board.to_san(&result.best_move.unwrap()), "Ra8#");
+        assert_eq!(result.score, MATE_SCORE - 1);
+    }
+
+    #[test]
+    fn takes_a_hanging_queen() {
+        let board = Board::from_fen("4k3/8/8/3q4/8/8/3R4/4K3 w - - 0 1").unwrap();
+        let result = search(&board, SearchLimits::depth(3));
+        assert_eq!(board.to_san(&result.best_move.unwrap()), "Rxd5");
+    }
+
+    #[test]
+    fn stops_at_the_node_budget() {
+        let board = Board::with_variant(Variant::Standard);
+        let limits = SearchLimits {
+            depth: 20,
+            nodes: Some(20_000),
+            time: None,
+            threads: 1,
+        };
+        let result = search(&board, limits);
+        assert!(result.best_move.is_some());
+        assert!(result.depth >= 1 && result.depth < 20);
+        assert!(result.nodes <= 20_000 + BUDGET_CHECK_INTERVAL);
+    }
+
+    #[test]
+    fn stops_deepening_once_mate_is_found() {
+        let board = Board::from_fen("6k1/5ppp/8/8/8/8/8/R5K1 w - - 0 1").unwrap();
+        let result = search(&board, SearchLimits::depth(10));
+        assert_eq!(board.to_san(&result.best_move.unwrap()), "Ra8#");
+        assert_eq!(result.depth, 1);
+    }
+
+    #[test]
+    fn threads_share_the_search() {
+        let board =
+            Board::from_fen("r1bqkb1r/pppp1ppp/2n2n2/4p2Q/2B1P3/8/PPPP1PPP/RNB1K1NR w KQkq - 4 4")
+                .unwrap();
+        let limits = SearchLimits {
+            threads: 4,
+            ..SearchLimits::depth(3)
+        };
+        let result = search(&board, limits);
+        assert_eq!(board.to_san(&result.best_move.unwrap()), "Qxf7#");
+    }
+
+    #[test]
+    fn stop_flag_ends_the_search() {
+        let board = Board::with_variant(Variant::Standard);
+        let stop = AtomicBool::new(true);
+        let limits = SearchLimits {
+            threads: 2,
+            ..SearchLimits::depth(MAX_DEPTH)
+        };
+        let result = search_until_stopped(&board, limits, None, None, &stop);
+        assert!(result.best_move.is_some());
+        assert_eq!(result.depth, 1);
+    }
+
+    #[test]
+    fn plays_from_distance_to_mate_tables() {
+        let mut endgames = EndgameTables::default();
+        endgames.generate("KQK").unwrap();
+        let stop = AtomicBool::new(false);
+        let board = Board::from_fen("8/8/8/3k4/8/8/8/KQ6 w - - 0 1").unwrap();
+        let result =
+            search_until_stopped(&board, SearchLimits::depth(1), None, Some(&endgames), &stop);
+        let (best_move, distance) = endgames.best_move(&board).unwrap();
+        assert_eq!(result.best_move, Some(best_move));
+        assert_eq!(result.score, distance_score(distance));
+        assert!(describe_score(result.score).starts_with("mate in"));
+        assert_eq!(result.depth, 0);
+        assert!(stop.load(Ordering::Relaxed));
+
+        // A mate the fifty move rule would get to first is left to the search
+        let board = Board::from_fen("8/8/8/3k4/8/8/8/KQ6 w - - 99 1").unwrap();
+        let stop = AtomicBool::new(false);
+        let result =
+            search_until_stopped(&board, SearchLimits::depth(1), None, Some(&endgames), &stop);
+        assert_eq!(result.depth, 1);
+        assert!(result.nodes > 0);
+
+        // Positions the tables don't cover are searched as usual
+        let board = Board::from_fen("6k1/5ppp/8/8/8/8/8/R5K1 w - - 0 1").unwrap();
+        let stop = AtomicBool::new(false);
+        let result =
+            search_until_stopped(&board, SearchLimits::depth(2), None, Some(&endgames), &stop);
+        assert_eq!(board.to_san(&result.best_move.unwrap()), "Ra8#");
+        assert_eq!(result.depth, 1);
+    }
+
+    #[test]
+    fn table_entries_round_trip() {
+        let board = Board::with_variant(Variant::Standard);
+        let chess_move = board.parse_san("Nf3").unwrap();
+        let entry = TableEntry {
+            depth: 7,
+            score: -MATE_SCORE + 12,
+            bound: Bound::Lower,
+            best_move: Some(move_code(&chess_move)),
+        };
+        let table = TranspositionTable::new();
+        table.store(board.hash(), entry);
+        let stored = table.probe(board.hash()).unwrap();
+        assert_eq!(stored.depth, 7);
+        assert_eq!(stored.score, -MATE_SCORE + 12);
+        assert_eq!(stored.bound, Bound::Lower);
+        assert_eq!(stored.best_move, Some(move_code(&chess_move)));
+        assert!(table.probe(board.hash() ^ 1).is_none());
+    }
+
+    #[test]
+    fn difficulty_levels_cycle() {
+        let mut difficulty = Difficulty::Beginner;
+        for _ in 0..Difficulty::ALL.len() {
+            difficulty = difficulty.next();
+        }
+        assert_eq!(difficulty, Difficulty::Beginner);
+        assert!(Difficulty::Beginner.blunder_chance() > 0.0);
+        assert_eq!(Difficulty::FullStrength.blunder_chance(), 0.0);
+    }
+
+    #[test]
+    fn describes_scores() {
+        assert_eq!(describe_score(35), "+0.35");
+        assert_eq!(describe_score(-120), "-1.20");
+        assert_eq!(describe_score(MATE_SCORE - 3), "mate in 2");
+        assert_eq!(describe_score(-MATE_SCORE + 2), "mated in 1");
+        assert_eq!(describe_score(TABLEBASE_WIN - 5), "tablebase win");
+        assert_eq!(describe_score(-TABLEBASE_WIN + 5), "tablebase loss");
+    }
+
+    #[test]
+    fn evaluation_is_symmetric() {
+        let white =
+            Board::from_fen("r1bqkbnr/pppp1ppp/2n5/4p3/4P3/5N2/PPPP1PPP/RNBQKB1R w KQkq - 2 3")
+                .unwrap();
+        let black =
+            Board::from_fen("rnbqkb1r/pppp1ppp/5n2/4p3/4P3/2N5/PPPP1PPP/R1BQKBNR b KQkq - 2 3")
+                .unwrap();
+        assert_eq!(evaluate(&white), evaluate(&black));
+    }
+}
@@ -1,18 +1,138 @@
 use std::collections::HashMap;
 
-use chess::Board;
+use chess::{Board, Variant};
 use crossterm::style::Stylize;
 use crossterm::terminal;
 use screen::{
     ButtonText, InsertHorizontalPosition, InsertVerticalPosition, PlainText, Screen, Text,
 };
 
+mod bitboard;
 mod chess;
+mod endgame;
+mod engine;
+mod pgn;
+mod polyglot;
 mod render;
 mod screen;
+mod syzygy;
 mod terminal_management;
+mod zobrist;
+
+// Returns the value given after a command line flag, e.g. the FEN in `--fen "<fen>"`
+fn argument_value(flag: &str) -> Option<String> {
+    let mut args = std::env::args().skip(1);
+    args.find(|arg| arg == flag)?;
+    args.next()
+}
+
+// Reads a variant name such as "atomic" or "king-of-the-hill"
+fn parse_variant(name: &str) -> Result<Variant, String> {
+    Variant::from_name(name).ok_or_else(|| {
+        let names = Variant::ALL.map(Variant::name).join(", ");
+        format!("Unknown variant {}, expected one of: {}", name, names)
+    })
+}
+
+// Runs `terminal_chess perft <fen> <depth> [variant]`, printing the node count for each first move
+fn run_perft(args: &[String]) -> Result<(), Box<dyn std::error::Error>> {
+    let (fen, depth, variant) = match args {
+        [fen, depth] => (fen, depth, Variant::Standard),
+        [fen, depth, variant] => (fen, depth, parse_variant(variant)?),
+        _ => return Err("usage: terminal_chess perft \"<fen>\" <depth> [variant]".into()),
+    };
+    let board = Board::from_variant_fen(fen, variant)?;
+    let depth: u32 = depth
+        .parse()
+        .map_err(|_| format!("invalid perft depth: {}", depth))?;
+
+    let start = std::time::Instant::now();
+    let divided = board.divide(depth);
+    for (chess_move, nodes) in &divided {
+        println!("{}: {}", board.to_uci(chess_move), nodes);
+    }
+    let total = if divided.is_empty() {
+        board.perft(depth)
+    } else {
+        divided.iter().map(|(_, nodes)| nodes).sum()
+    };
+    let elapsed = start.elapsed();
+
+    println!();
+    println!("Nodes searched: {}", total);
+    println!(
+        "Time: {:.3}s ({:.0} nodes/s)",
+        elapsed.as_secs_f64(),
+        total as f64 / elapsed.as_secs_f64().max(f64::EPSILON)
+    );
+    Ok(())
+}
+
+// Runs `terminal_chess tablebase generate <directory> <ending>...`, which works out each ending such as KBNK and saves its table,
+// or `terminal_chess tablebase probe <directory> "<fen>"`, which looks the position up in the saved tables
+fn run_tablebase(args: &[String]) -> Result<(), Box<dyn std::error::Error>> {
+    match args {
+        [command, directory, endings @ ..] if command == "generate" && !endings.is_empty() => {
+            let mut tables = endgame::EndgameTables::default();
+            for ending in endings {
+                let start = std::time::Instant::now();
+                for name in tables.generate(ending)? {
+                    match tables.longest_mate(&name) {
+                        Some(moves) => println!("{}: longest mate in {}", name, moves),
+                        None => println!("{}: no forced mates", name),
+                    }
+                }
+                println!("Time: {:.3}s", start.elapsed().as_secs_f64());
+            }
+            tables.save(directory)?;
+            println!("Saved {} to {}", tables.names().join(", "), directory);
+        }
+        [command, directory, fen] if command == "probe" => {
+            let tables = endgame::EndgameTables::load(directory)?;
+            let board = Board::from_fen(fen)?;
+            match (tables.best_move(&board), tables.probe(&board)) {
+                (Some((chess_move, distance)), _) => {
+                    println!(
+                        "{}, best move {}",
+                        distance.describe(),
+                        board.to_san(&chess_move)
+                    )
+                }
+                (None, Some(distance)) => println!("{}", distance.describe()),
+                (None, None) => return Err(format!("No table covers {}", fen).into()),
+            }
+        }
+        _ => {
+            return Err(concat!(
+                "usage: terminal_chess tablebase generate <directory> <ending>...\n",
+                "       terminal_chess tablebase probe <directory> \"<fen>\""
+            )
+            .into())
+        }
+    }
+    Ok(())
+}
 
 fn main() -> Result<(), Box<dyn std::error::Error>> {
+    let args: Vec<String> = std::env::args().collect();
+    match args.get(1).map(String::as_str) {
+        Some("perft") => return run_perft(&args[2..]),
+        Some("tablebase") => return run_tablebase(&args[2..]),
+        _ => {}
+    }
+
+    // A file without any games would leave the Replay PGN button leading nowhere
+    let replay_games = match argument_value("--pgn") {
+        Some(path) => {
+            let games = pgn::parse_pgn(&std::fs::read_to_string(&path)?)?;
+            if games.is_empty() {
+                return Err(format!("no games in {}", path).into());
+            }
+            Some(games)
+        }
+        None => None,
+    };
+
     let (width, height) = terminal::size().unwrap();
     let width = width as usize;
     let height = height as usize;
@@ -37,9 +157,39 @@ r#" _______                       __               __      ______ __
             "Play Game".red().slow_blink().to_string(),
             width,
             height,
-            InsertHorizontalPosition::Center,
+            InsertHorizontalPosition::Exact(width / 2 - 11),
             InsertVerticalPosition::Exact(10),
-            "next_screen",
+            "play_game",
+        )));
+
+    initial_screen
+        .screen_rows
+        .edit_single_row(Text::Button(ButtonText::new(
+            "Chess960".red().to_string(),
+            width,
+            height,
+            InsertHorizontalPosition::Exact(width / 2 + 2),
+            InsertVerticalPosition::Exact(10),
+            "chess960",
+        )));
+
+    initial_screen
+        .screen_rows
+        .edit_single_row(Text::Button(ButtonText::new(
+            "Play vs Computer".red().to_string(),
+            width,
+            height,
+            InsertHorizontalPosition::Exact(width / 2 - 11),
+            InsertVerticalPosition::Exact(11),
+            "play_computer",
+        )));
+
+    initial_screen
+        .screen_rows
+        .edit_single_row(Text::Button(render::Render::difficulty_button(
+            width,
+            height,
+            engine::Difficulty::default(),
         )));
 
     initial_screen
@@ -52,15 +202,54 @@ r#" _______                       __               __      ______ __
             InsertVerticalPosition::Exact(12),
         )));
 
+    if replay_games.is_some() {
+        initial_screen
+            .screen_rows
+            .edit_single_row(Text::Button(ButtonText::new(
+                "Replay PGN".red().to_string(),
+                width,
+                height,
+                InsertHorizontalPosition::Center,
+                InsertVerticalPosition::Exact(14),
+                "replay_screen",
+            )));
+    }
+
     let mut renderer = render::Render::new(initial_screen)?;
+    if std::env::args().any(|arg| arg == "--no-takebacks") {
+        renderer.disable_takebacks();
+    }
+    let threads = match argument_value("--threads") {
+        Some(threads) => threads
+            .parse()
+            .ok()
+            .filter(|threads| *threads > 0)
+            .ok_or_else(|| format!("invalid thread count: {}", threads))?,
+        None => std::thread::available_parallelism().map_or(1, usize::from),
+    };
+    renderer.set_threads(threads);
 
-    let chess_game = Board::new();
+    let variant = match argument_value("--variant") {
+        Some(name) => parse_variant(&name)?,
+        None => Variant::Standard,
+    };
+    let chess_game = match (argument_value("--fen"), argument_value("--chess960")) {
+        (Some(fen), _) => Board::from_variant_fen(&fen, variant)?,
+        (None, Some(index)) => index
+            .parse()
+            .ok()
+            .and_then(Board::chess960)
+            .ok_or_else(|| format!("Chess960 positions are numbered 0 to 959, not {}", index))?,
+        (None, None) => Board::with_variant(variant),
+    };
+    let game_variant = chess_game.variant;
 
     let game_button_map: HashMap<&str, Box<dyn Fn()>> = HashMap::new();
 
-    let mut game_screen = Screen::new(game_button_map, Some(chess_game));
-
     let board_rows = chess_game.display_board();
+    let white_move = chess_game.white_move;
+
+    let mut game_screen = Screen::new(game_button_map, Some(chess_game));
 
     for row in board_rows {
         for piece in row {
@@ -71,11 +260,11 @@ r#" _______                       __               __      ______ __
     game_screen
         .screen_rows
         .edit_single_row(Text::Plain(PlainText::new(
-            "White's turn".to_string(),
+            format!("{}'s turn", if white_move { "White" } else { "Red" }),
             width,
             height,
             InsertHorizontalPosition::Exact(0),
-            InsertVerticalPosition::Exact(10),
+            InsertVerticalPosition::Exact(render::STATUS_ROW),
         )));
 
     game_screen
@@ -89,6 +278,50 @@ r#" _______                       __               __      ______ __
             "last_screen",
         )));
 
+    game_screen
+        .screen_rows
+        .edit_single_row(Text::Button(ButtonText::new(
+            "Claim draw".to_string(),
+            width,
+            height,
+            InsertHorizontalPosition::Exact(10),
+            InsertVerticalPosition::Center,
+            "claim_draw",
+        )));
+
+    game_screen
+        .screen_rows
+        .edit_single_row(Text::Button(ButtonText::new(
+            "Save PGN".to_string(),
+            width,
+            height,
+            InsertHorizontalPosition::Exact(23),
+            InsertVerticalPosition::Center,
+            "save_pgn",
+        )));
+
+    game_screen
+        .screen_rows
+        .edit_single_row(Text::Button(render::Render::variant_button(
+            width,
+            height,
+            game_variant,
+        )));
+    render::Render::draw_pockets(&mut game_screen, width, height, None);
+
+    if let Some(path) = argument_value("--book") {
+        let book = polyglot::OpeningBook::open(&path)?;
+        render::Render::draw_book(&mut game_screen, width, height, Some(&book));
+        renderer.set_book(book);
+    }
+
+    if let Some(directory) = argument_value("--syzygy") {
+        renderer.set_tablebase(syzygy::Tablebase::open(&directory)?);
+    }
+    if let Some(directory) = argument_value("--dtm") {
+        renderer.set_endgames(endgame::EndgameTables::load(&directory)?);
+    }
+
     renderer.new_screen(game_screen);
 
     let victory_button_map: HashMap<&str, Box<dyn Fn()>> = HashMap::new();
@@ -96,9 +329,9 @@ r#" _______                       __               __      ______ __
     let mut victory_screen = Screen::new(victory_button_map, None);
 
     victory_screen.screen_rows.edit_multiple_rows(
-        &vec![
+        &[
             Text::Plain(PlainText::new(
-                "Congratulations, you won!".to_string(),
+                "Game over".to_string(),
                 width,
                 height,
                 InsertHorizontalPosition::Center,
@@ -112,13 +345,36 @@ r#" _______                       __               __      ______ __
                 InsertVerticalPosition::Center,
                 "reset_game",
             )),
+            Text::Button(ButtonText::new(
+                "Save PGN".to_string(),
+                width,
+                height,
+                InsertHorizontalPosition::Center,
+                InsertVerticalPosition::Center,
+                "save_pgn",
+            )),
         ],
         1,
-        InsertVerticalPosition::Center,
+        InsertVerticalPosition::Exact(height / 2),
     );
 
     renderer.new_screen(victory_screen);
 
+    if let Some(games) = replay_games {
+        let replay_button_map: HashMap<&str, Box<dyn Fn()>> = HashMap::new();
+        let mut replay_screen = Screen::new(replay_button_map, None);
+        replay_screen
+            .screen_rows
+            .edit_single_row(Text::Plain(PlainText::new(
+                "Left/Right: step   Home/End: jump   Up/Down: change game   Esc: home".to_string(),
+                width,
+                height,
+                InsertHorizontalPosition::Exact(0),
+                InsertVerticalPosition::Exact(13),
+            )));
+        renderer.new_replay_screen(replay_screen, games);
+    }
+
     let mut terminal = terminal_management::Terminal::new(renderer);
 
     terminal::enable_raw_mode().expect("Could not turn on raw mode");
@@ -0,0 +1,679 @@
+use std::fs;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use crate::chess::{Board, GameState, Variant};
+
+// Longest line the movetext is wrapped to, as the PGN export format asks for
+const LINE_WIDTH: usize = 79;
+
+// Returns the PGN result token for the game: "1-0", "0-1", "1/2-1/2" or "*" while it is still going
+pub fn result_token(board: &Board) -> &'static str {
+    match board.game_state() {
+        GameState::Checkmate => {
+            if board.white_move {
+                "0-1"
+            } else {
+                "1-0"
+            }
+        }
+        GameState::Won { white: true, .. } => "1-0",
+        GameState::Won { white: false, .. } => "0-1",
+        GameState::Stalemate | GameState::Draw(_) => "1/2-1/2",
+        GameState::Ongoing | GameState::Check => "*",
+    }
+}
+
+// Writes the game as PGN with the Seven Tag Roster, SAN movetext and the result token
+pub fn game_to_pgn(board: &Board, date: &str) -> String {
+    let result = result_token(board);
+    let mut tags = vec![
+        ("Event", "Casual game".to_string()),
+        ("Site", "terminal_chess".to_string()),
+        ("Date", date.to_string()),
+        ("Round", "-".to_string()),
+        ("White", "?".to_string()),
+        ("Black", "?".to_string()),
+        ("Result", result.to_string()),
+    ];
+    if board.variant != Variant::Standard {
+        tags.push(("Variant", board.variant.name().to_string()));
+    } else if board.chess960 {
+        tags.push(("Variant", "Chess960".to_string()));
+    }
+    if board.start_fen != board.variant.starting_fen() || board.chess960 {
+        tags.push(("SetUp", "1".to_string()));
+        tags.push(("FEN", board.start_fen.clone()));
+    }
+
+    let mut pgn = String::new();
+    for (name, value) in tags {
+        pgn.push_str(&format!("[{} \"{}\"]\n", name, escape_tag_value(&value)));
+    }
+    pgn.push('\n');
+
+    let mut tokens = vec![];
+    let mut replay = board.starting_board();
+    for (i, chess_move) in board.moves.iter().enumerate() {
+        if replay.white_move {
+            tokens.push(format!("{}.", replay.fullmove_number));
+        } else if i == 0 {
+            tokens.push(format!("{}...", replay.fullmove_number));
+        }
+        tokens.push(replay.to_san(chess_move));
+        replay.play_move(*chess_move);
+    }
+    tokens.push(result.to_string());
+
+    pgn.push_str(&wrap_tokens(&tokens));
+    pgn.push('\n');
+    pgn
+}
+
+// Saves the game to a new .pgn file in the working directory, returns the file name
+pub fn save_game(board: &Board) -> std::io::Result<String> {
+    let (date, time) = current_date_time();
+    let file_name = format!(
+        "game_{}_{}.pgn",
+        date.replace('.', ""),
+        time.replace(':', "")
+    );
+    fs::write(&file_name, game_to_pgn(board, &date))?;
+    Ok(file_name)
+}
+
+// Joins tokens with spaces, starting a new line whenever the next token would go past the line width
+fn wrap_tokens(tokens: &[String]) -> String {
+    let mut text = String::new();
+    let mut line_length = 0;
+    for token in tokens {
+        if line_length > 0 && line_length + 1 + token.len() > LINE_WIDTH {
+            text.push('\n');
+            line_length = 0;
+        } else if line_length > 0 {
+            text.push(' ');
+            line_length += 1;
+        }
+        text.push_str(token);
+        line_length += token.len();
+    }
+    text
+}
+
+// Escapes quotes and backslashes inside a tag value
+fn escape_tag_value(value: &str) -> String {
+    value.replace('\\', "\\\\").replace('"', "\\\"")
+}
+
+// Returns the current UTC date as "YYYY.MM.DD" and time as "HH:MM:SS"
+fn current_date_time() -> (String, String) {
+    let seconds = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|duration| duration.as_secs())
+        .unwrap_or(0);
+    let days = (seconds / 86400) as i64;
+    let time_of_day = seconds % 86400;
+
+    // Converts days since 1970-01-01 into a civil date (Howard Hinnant's algorithm)
+    let shifted_days = days + 719468;
+    let era = shifted_days.div_euclid(146097);
+    let day_of_era = shifted_days.rem_euclid(146097);
+    let year_of_era =
+        (day_of_era - day_of_era / 1460 + day_of_era / 36524 - day_of_era / 146096) / 365;
+    let day_of_year = day_of_era - (365 * year_of_era + year_of_era / 4 - year_of_era / 100);
+    let month_index = (5 * day_of_year + 2) / 153;
+    let day = day_of_year - (153 * month_index + 2) / 5 + 1;
+    let month = if month_index < 10 {
+        month_index + 3
+    } else {
+        month_index - 9
+    };
+    let year = year_of_era + era * 400 + if month <= 2 { 1 } else { 0 };
+
+    (
+        format!("{:04}.{:02}.{:02}", year, month, day),
+        format!(
+            "{:02}:{:02}:{:02}",
+            time_of_day / 3600,
+            time_of_day / 60 % 60,
+            time_of_day % 60
+        ),
+    )
+}
+
+// A game read from a PGN file, the board holds the starting position and the main line of moves
+pub struct PgnGame {
+    pub tags: Vec<(String, String)>,
+    pub board: Board,
+    pub result: String,
+}
+
+impl PgnGame {
+    // Returns the value of a tag, such as "White" or "Event"
+    pub fn tag(&self, name: &str) -> Option<&str> {
+        self.tags
+            .iter()
+            .find(|(tag_name, _)| tag_name == name)
+            .map(|(_, value)| value.as_str())
+    }
+}
+
+#[derive(Debug)]
+pub struct PgnError {
+    pub line: usize,
+    pub column: usize,
+    pub message: String,
+}
+
+impl std::fmt::Display for PgnError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "PGN error at line {}, column {}: {}",
+            self.line, self.column, self.message
+        )
+    }
+}
+
+impl std::error::Error for PgnError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        None
+    }
+}
+
+#[derive(Clone, Debug, PartialEq)]
+enum TokenKind {
+    TagOpen,
+    TagClose,
+    Text(String),
+    Symbol(String),
+    MoveNumber,
+    Nag,
+    VariationOpen,
+    VariationClose,
+    Result(String),
+}
+
+#[derive(Clone, Debug)]
+struct Token {
+    kind: TokenKind,
+    line: usize,
+    column: usize,
+}
+
+impl Token {
+    // Builds an error pointing at this token
+    fn error(&self, message: String) -> PgnError {
+        error_at(self.line, self.column, message)
+    }
+}
+
+// Builds an error pointing at the given line and column
+fn error_at(line: usize, column: usize, message: String) -> PgnError {
+    PgnError {
+        line,
+        column,
+        message,
+    }
+}
+
+// Reads every game in a PGN file, variations are checked but only the main line is kept
+pub fn parse_pgn(text: &str) -> Result<Vec<PgnGame>, PgnError> {
+    let tokens = tokenize(text)?;
+    let mut parser = Parser {
+        tokens,
+        position: 0,
+    };
+
+    let mut games = vec![];
+    while parser.peek().is_some() {
+        games.push(parser.parse_game()?);
+    }
+    Ok(games)
+}
+
+// Splits PGN text into tokens, dropping comments and escaped lines
+fn tokenize(text: &str) -> Result<Vec<Token>, PgnError> {
+    let chars = text.chars().collect::<Vec<_>>();
+    let mut tokens = vec![];
+    let mut index = 0;
+    let mut line = 1;
+    let mut column = 1;
+
+    // Moves past the character at index, keeping the line and column up to date
+    let advance = |index: &mut usize, line: &mut usize, column: &mut usize| {
+        if chars[*index] == '\n' {
+            *line += 1;
+            *column = 1;
+        } else {
+            *column += 1;
+        }
+        *index += 1;
+    };
+
+    while index < chars.len() {
+        let char = chars[index];
+        let (start_line, start_column) = (line, column);
+        let token = |kind: TokenKind| Token {
+            kind,
+            line: start_line,
+            column: start_column,
+        };
+
+        match char {
+            _ if char.is_whitespace() => advance(&mut index, &mut line, &mut column),
+            '%' if column == 1 => {
+                while index < chars.len() && chars[index] != '\n' {
+                    advance(&mut index, &mut line, &mut column);
+                }
+            }
+            ';' => {
+                while index < chars.len() && chars[index] != '\n' {
+                    advance(&mut index, &mut line, &mut column);
+                }
+            }
+            '{' => {
+                while index < chars.len() && chars[index] != '}' {
+                    advance(&mut index, &mut line, &mut column);
+                }
+                if index == chars.len() {
+                    return Err(error_at(
+                        start_line,
+                        start_column,
+                        "comment is never closed".to_string(),
+                    ));
+                }
+                advance(&mut index, &mut line, &mut column);
+            }
+            '[' | ']' | '(' | ')' => {
+                tokens.push(token(match char {
+                    '[' => TokenKind::TagOpen,
+                    ']' => TokenKind::TagClose,
+                    '(' => TokenKind::VariationOpen,
+                    _ => TokenKind::VariationClose,
+                }));
+                advance(&mut index, &mut line, &mut column);
+            }
+            '"' => {
+                let mut value = String::new();
+                advance(&mut index, &mut line, &mut column);
+                loop {
+                    match chars.get(index) {
+                        None | Some('\n') => {
+                            return Err(error_at(
+                                start_line,
+                                start_column,
+                                "string is never closed".to_string(),
+                            ));
+                        }
+                        Some('"') => break,
+                        Some('\\') if index + 1 < chars.len() => {
+                            advance(&mut index, &mut line, &mut column);
+                            value.push(chars[index]);
+                        }
+                        Some(char) => value.push(*char),
+                    }
+                    advance(&mut index, &mut line, &mut column);
+                }
+                advance(&mut index, &mut line, &mut column);
+                tokens.push(token(TokenKind::Text(value)));
+            }
+            '$' => {
+                advance(&mut index, &mut line, &mut column);
+                let digits_start = index;
+                while index < chars.len() && chars[index].is_ascii_digit() {
+                    advance(&mut index, &mut line, &mut column);
+                }
+                if index == digits_start {
+                    return Err(error_at(
+                        start_line,
+                        start_column,
+                        "'$' must be followed by a number".to_string(),
+                    ));
+                }
+                tokens.push(token(TokenKind::Nag));
+            }
+            '!' | '?' => {
+                while index < chars.len() && matches!(chars[index], '!' | '?') {
+                    advance(&mut index, &mut line, &mut column);
+                }
+                tokens.push(token(TokenKind::Nag));
+            }
+            '.' => {
+                while index < chars.len() && chars[index] == '.' {
+                    advance(&mut index, &mut line, &mut column);
+                }
+                tokens.push(token(TokenKind::MoveNumber));
+            }
+            // Crazyhouse drops such as "N@c3" may leave off the pawn letter, as in "@e6"
+            _ if char.is_ascii_alphanumeric() || char == '@' => {
+                let mut symbol = String::new();
+                while index < chars.len()
+                    && (chars[index].is_ascii_alphanumeric()
+                        || matches!(chars[index], '_' | '+' | '#' | '=' | ':' | '-' | '/' | '@'))
+                {
+                    symbol.push(chars[index]);
+                    advance(&mut index, &mut line, &mut column);
+                }
+
+                let kind = if matches!(symbol.as_str(), "1-0" | "0-1" | "1/2-1/2") {
+                    TokenKind::Result(symbol)
+                } else if symbol.chars().all(|char| char.is_ascii_digit()) {
+                    while index < chars.len() && chars[index] == '.' {
+                        advance(&mut index, &mut line, &mut column);
+                    }
+                    TokenKind::MoveNumber
+                } else {
+                    TokenKind::Symbol(symbol)
+                };
+                tokens.push(token(kind));
+            }
+            '*' => {
+                tokens.push(token(TokenKind::Result("*".to_string())));
+                advance(&mut index, &mut line, &mut column);
+            }
+            _ => {
+                return Err(error_at(
+                    start_line,
+                    start_column,
+                    format!("unexpected character '{}'", char),
+                ));
+            }
+        }
+    }
+    Ok(tokens)
+}
+
+struct Parser {
+    tokens: Vec<Token>,
+    position: usize,
+}
+
+impl Parser {
+    fn peek(&self) -> Option<&Token> {
+        self.tokens.get(self.position)
+    }
+
+    fn next(&mut self) -> Option<Token> {
+        let token = self.tokens.get(self.position).cloned();
+        self.position += 1;
+        token
+    }
+
+    // Builds an error pointing at the end of the file
+    fn end_of_file_error(&self, message: &str) -> PgnError {
+        match self.tokens.last() {
+            Some(token) => token.error(message.to_string()),
+            None => error_at(1, 1, message.to_string()),
+        }
+    }
+
+    // Reads the tag pairs and movetext of one game
+    fn parse_game(&mut self) -> Result<PgnGame, PgnError> {
+        let mut tags = vec![];
+        let mut fen_token = None;
+        while self.peek().map(|token| &token.kind) == Some(&TokenKind::TagOpen) {
+            self.next();
+            let name_token = self
+                .next()
+                .ok_or_else(|| self.end_of_file_error("tag pair is cut off"))?;
+            let name = match &name_token.kind {
+                TokenKind::Symbol(name) => name.clone(),
+                _ => return Err(name_token.error("expected a tag name".to_string())),
+            };
+            let value_token = self
+                .next()
+                .ok_or_else(|| self.end_of_file_error("tag pair is cut off"))?;
+            let value = match &value_token.kind {
+                TokenKind::Text(value) => value.clone(),
+                _ => return Err(value_token.error("expected a quoted tag value".to_string())),
+            };
+            match self.next() {
+                Some(Token {
+                    kind: TokenKind::TagClose,
+                    ..
+                }) => {}
+                Some(token) => return Err(token.error("expected ']' to close the tag".to_string())),
+                None => return Err(self.end_of_file_error("tag pair is cut off")),
+            }
+            if name == "FEN" {
+                fen_token = Some(value_token);
+            }
+            tags.push((name, value));
+        }
+
+        let variant_tag = tags
+            .iter()
+            .find(|(name, _)| name == "Variant")
+            .map(|(_, value)| value.as_str());
+        let variant = variant_tag
+            .and_then(Variant::from_name)
+            .unwrap_or(Variant::Standard);
+
+        let mut board = match fen_token {
+            Some(token) => match &token.kind {
+                TokenKind::Text(fen) => Board::from_variant_fen(fen, variant)
+                    .map_err(|error| token.error(error.to_string()))?,
+                _ => unreachable!(),
+            },
+            None => Board::with_variant(variant),
+        };
+
+        if variant_tag.is_some_and(|value| value.eq_ignore_ascii_case("chess960")) {
+            board.chess960 = true;
+        }
+
+        let (board, result) = self.parse_line(board, false)?;
+        Ok(PgnGame {
+            tags,
+            board,
+            result: result.unwrap_or_else(|| "*".to_string()),
+        })
+    }
+
+    // Plays through a line of moves, checking any variations against the position they branch from
+    // Returns the board at the end of the line and the result token that ended it, if any
+    fn parse_line(
+        &mut self,
+        mut board: Board,
+        in_variation: bool,
+    ) -> Result<(Board, Option<String>), PgnError> {
+        let mut previous_board: Option<Board> = None;
+        loop {
+            let token = match self.peek() {
+                Some(token) => token.clone(),
+                None if in_variation => {
+                    return Err(self.end_of_file_error("variation is never closed"))
+                }
+                None => return Ok((board, None)),
+            };
+
+            match &token.kind {
+                TokenKind::TagOpen if !in_variation => return Ok((board, None)),
+                TokenKind::Symbol(san) => {
+                    self.next();
+                    let chess_move = board
+                        .parse_san(san)
+                        .map_err(|error| token.error(error.to_string()))?;
+                    previous_board = Some(board.clone());
+                    board.play_move(chess_move);
+                }
+                TokenKind::MoveNumber | TokenKind::Nag => {
+                    self.next();
+                }
+                TokenKind::VariationOpen => {
+                    self.next();
+                    let branch_board = previous_board.clone().ok_or_else(|| {
+                        token.error("variation comes before any move".to_string())
+                    })?;
+                    self.parse_line(branch_board, true)?;
+                }
+                TokenKind::VariationClose if in_variation => {
+                    self.next();
+                    return Ok((board, None));
+                }
+                TokenKind::Result(result) if !in_variation => {
+                    self.next();
+                    return Ok((board, Some(result.clone())));
+                }
+                _ => return Err(token.error("unexpected token in the movetext".to_string())),
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // Plays the moves from the variant's starting position
+    fn play(variant: Variant, moves: &[&str]) -> Board {
+        let mut board = Board::with_variant(variant);
+        for san in moves {
+            let chess_move = board.parse_san(san).unwrap();
+            assert!(board.play_move(chess_move), "{}", san);
+        }
+        board
+    }
+
+    #[test]
+    fn reloads_crazyhouse_drops() {
+        let board = play(
+            Variant::Crazyhouse,
+            &["e4", "d5", "exd5", "Qxd5", "Nc3", "Qa5", "P@e6"],
+        );
+        let pgn = game_to_pgn(&board, "2024.01.01");
+        assert!(pgn.contains("4. P@e6 *"), "{}", pgn);
+
+        let games = parse_pgn(&pgn).unwrap();
+        assert_eq!(games.len(), 1);
+        assert_eq!(games[0].board.variant, Variant::Crazyhouse);
+        assert_eq!(games[0].board.moves, board.moves);
+        assert_eq!(games[0].board.to_fen(), board.to_fen());
+    }
+
+    #[test]
+    fn reads_several_games_with_comments_nags_and_variations() {
+        let text = r#"[Event "Club \"Open\""]
+[White "A"]
+[Result "1-0"]
+
+1. e4 {best by test} e5 $1 2. Nf3 ; the rest of the line is a comment
+Nc6!? (2... d6 3. d4 (3. Bc4 Be7) exd4) 3. Bb5 1-0
+
+[Event "Second"]
+
+1. d4 d5 *
+"#;
+        let games = parse_pgn(text).unwrap();
+        assert_eq!(games.len(), 2);
+
+        assert_eq!(games[0].tag("Event"), Some(r#"Club "Open""#));
+        assert_eq!(games[0].tag("White"), Some("A"));
+        assert_eq!(games[0].result, "1-0");
+        let main_line = play(Variant::Standard, &["e4", "e5", "Nf3", "Nc6", "Bb5"]);
+        assert_eq!(games[0].board.moves, main_line.moves);
+
+        assert_eq!(games[1].tag("Event"), Some("Second"));
+        assert_eq!(games[1].result, "*");
+        assert_eq!(games[1].board.moves.len(), 2);
+    }
+
+    #[test]
+    fn points_errors_at_their_line_and_column() {
+        let error = |text: &str| {
+            let error = parse_pgn(text).err().unwrap();
+            (error.line, error.column)
+        };
+        assert_eq!(error("1. e4 e5\n2. Ke3 *"), (2, 4));
+        // A variation is checked even though it isn't kept
+        assert_eq!(error("1. e4 e5 (1... c5 (1... Qh4) d6) *"), (1, 25));
+        assert_eq!(error("1. e4 {never\nclosed"), (1, 7));
+
+        let unclosed = parse_pgn("1. e4 {never\nclosed").err().unwrap();
+        assert_eq!(
+            unclosed.to_string(),
+            "PGN error at line 1, column 7: comment is never closed"
+        );
+    }
+
+    // The tag pairs at the top of the PGN text, in order
+    fn tag_lines(pgn: &str) -> Vec<&str> {
+        pgn.lines()
+            .take_while(|line| line.starts_with('['))
+            .collect()
+    }
+
+    #[test]
+    fn writes_the_seven_tag_roster_and_result() {
+        let board = play(Variant::Standard, &["f3", "e5", "g4", "Qh4#"]);
+        let pgn = game_to_pgn(&board, "2024.05.06");
+        assert_eq!(
+            tag_lines(&pgn),
+            [
+                r#"[Event "Casual game"]"#,
+                r#"[Site "terminal_chess"]"#,
+                r#"[Date "2024.05.06"]"#,
+                r#"[Round "-"]"#,
+                r#"[White "?"]"#,
+                r#"[Black "?"]"#,
+                r#"[Result "0-1"]"#,
+            ]
+        );
+        assert!(pgn.ends_with("\n\n1. f3 e5 2. g4 Qh4# 0-1\n"), "{}", pgn);
+    }
+
+    #[test]
+    fn writes_the_starting_position_of_chess960_and_variant_games() {
+        let mut chess960 = Board::chess960(0).unwrap();
+        chess960.play_move(chess960.parse_san("e4").unwrap());
+        let pgn = game_to_pgn(&chess960, "2024.05.06");
+        let tags = tag_lines(&pgn);
+        assert_eq!(tags[7..9], [r#"[Variant "Chess960"]"#, r#"[SetUp "1"]"#]);
+        assert_eq!(tags[9], format!("[FEN \"{}\"]", chess960.start_fen));
+        let reloaded = &parse_pgn(&pgn).unwrap()[0].board;
+        assert!(reloaded.chess960);
+        assert_eq!(reloaded.to_fen(), chess960.to_fen());
+
+        let atomic = play(Variant::Atomic, &["e4"]);
+        let pgn = game_to_pgn(&atomic, "2024.05.06");
+        assert_eq!(
+            tag_lines(&pgn)[7..],
+            [format!("[Variant \"{}\"]", Variant::Atomic.name())]
+        );
+    }
+
+    #[test]
+    fn numbers_a_game_that_starts_with_black() {
+        let mut board =
+            Board::from_fen("rnbqkbnr/pppppppp/8/8/4P3/8/PPPP1PPP/RNBQKBNR b KQkq - 0 12").unwrap();
+        for san in ["e5", "Nf3"] {
+            board.play_move(board.parse_san(san).unwrap());
+        }
+        let pgn = game_to_pgn(&board, "2024.05.06");
+        assert!(tag_lines(&pgn).contains(&r#"[SetUp "1"]"#));
+        assert!(pgn.ends_with("\n\n12... e5 13. Nf3 *\n"), "{}", pgn);
+    }
+
+    #[test]
+    fn wraps_movetext_and_reads_it_back() {
+        let moves = [
+            "e4", "e5", "Nf3", "Nc6", "Bb5", "a6", "Ba4", "Nf6", "O-O", "Be7", "Re1", "b5", "Bb3",
+            "d6", "c3", "O-O", "h3", "Nb8", "d4", "Nbd7", "c4", "c6", "cxb5", "axb5", "Nc3", "Bb7",
+            "Bg5", "b4", "Nb1", "h6", "Bh4", "c5", "dxe5", "Nxe4",
+        ];
+        let board = play(Variant::Standard, &moves);
+        let pgn = game_to_pgn(&board, "2024.05.06");
+        let movetext: Vec<_> = pgn.lines().skip(tag_lines(&pgn).len() + 1).collect();
+        assert!(movetext.len() > 1);
+        assert!(movetext.iter().all(|line| line.len() <= LINE_WIDTH));
+        assert!(movetext[..movetext.len() - 1]
+            .iter()
+            .all(|line| line.len() > LINE_WIDTH - 8));
+
+        let games = parse_pgn(&pgn).unwrap();
+        assert_eq!(games.len(), 1);
+        assert_eq!(games[0].board.moves, board.moves);
+        assert_eq!(games[0].tag("Date"), Some("2024.05.06"));
+        assert_eq!(games[0].result, "*");
+    }
+}
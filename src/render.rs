@@ -1,10 +1,48 @@
 use std::io::{stdout, Write};
+use std::sync::Arc;
 
 use crossterm::style::Stylize;
 use crossterm::{cursor, event::KeyCode, execute, queue, terminal};
 
-use crate::chess::{Board, ChessPieces};
-use crate::screen::{InsertHorizontalPosition, InsertVerticalPosition, PlainText, Screen, Text};
+use crate::chess::{Board, ChessPieces, GameState, Move, MoveParseError, Variant, POCKET_PIECES};
+use crate::endgame::EndgameTables;
+use crate::engine::{self, BackgroundSearch, Difficulty};
+use crate::pgn::{self, PgnGame};
+use crate::polyglot::OpeningBook;
+use crate::screen::{
+    ButtonText, InsertHorizontalPosition, InsertVerticalPosition, PlainText, Screen, Text,
+};
+use crate::syzygy::{Tablebase, Wdl};
+
+// Row under the board that shows whose turn it is
+pub const STATUS_ROW: usize = 10;
+// Row under the board that holds the promotion picker while it is open
+const PROMOTION_ROW: usize = 9;
+// Row under the status row for extra information such as the position's FEN
+const INFO_ROW: usize = 11;
+// Where the button that picks the game's variant sits on the game screen's button row
+const VARIANT_BUTTON_X: usize = 33;
+// Wide enough for the longest variant button label, so a shorter label can blank out a longer one
+const VARIANT_BUTTON_WIDTH: usize = 25;
+// Where the Crazyhouse pockets are shown to the right of the board, red's at the top and white's at the bottom
+const POCKET_PANEL_X: usize = 18;
+const POCKET_PANEL_WIDTH: usize = 30;
+const RED_POCKET_ROW: usize = 0;
+const WHITE_POCKET_ROW: usize = 6;
+// Where the computer's level is picked on the home screen, beside the Play vs Computer button
+const LEVEL_BUTTON_ROW: usize = 11;
+const LEVEL_BUTTON_OFFSET: usize = 7;
+// Wide enough for the longest level label
+const LEVEL_BUTTON_WIDTH: usize = 20;
+// Where the opening book's moves for the position are listed, to the right of the pockets
+const BOOK_PANEL_X: usize = 50;
+const BOOK_PANEL_WIDTH: usize = 30;
+// A title row and then one row per move
+const BOOK_PANEL_ROWS: usize = 8;
+// Where analysis mode shows the tablebase result for the position, under the opening book panel
+const ANALYSIS_ROW: usize = 8;
+// Wide enough for the longest hint, "TB: blessed loss in 100 plies to zeroing"
+const ANALYSIS_WIDTH: usize = 40;
 
 pub struct Render {
     pub screens: Vec<Screen>,
@@ -12,6 +50,39 @@ pub struct Render {
     width: usize,
     height: usize,
     cursor_controller: CursorController,
+    pending_promotion: Option<(usize, usize)>,
+    // The pocket piece picked for a Crazyhouse drop, waiting for the tile to drop it on
+    pending_drop: Option<ChessPieces>,
+    move_entry: Option<String>,
+    // The last game that finished, kept so the victory screen can still save it
+    finished_game: Option<Board>,
+    replay: Option<Replay>,
+    // Whether moves on the game screen may be taken back, off for rated or serious games
+    takebacks: bool,
+    // The colour the computer plays on the game screen, true for white, or None when two people are playing
+    computer: Option<bool>,
+    // How strongly the computer plays
+    difficulty: Difficulty,
+    // How many threads the computer searches with
+    threads: usize,
+    // The computer's search for its next move, while it is thinking
+    thinking: Option<BackgroundSearch>,
+    // The opening book the computer plays from and the game screen lists moves from
+    book: Option<OpeningBook>,
+    // The endgame tablebases the computer plays perfectly from and analysis mode shows results from
+    tablebase: Option<Arc<Tablebase>>,
+    // The distance to mate tables the computer plays perfectly from
+    endgames: Option<Arc<EndgameTables>>,
+    // Whether the game screen shows what the tablebases say about the position
+    analysis: bool,
+}
+
+// Games loaded from a PGN file and how far through them the replay screen is
+struct Replay {
+    games: Vec<PgnGame>,
+    game_index: usize,
+    ply: usize,
+    screen_index: usize,
 }
 
 impl Render {
@@ -30,9 +101,48 @@ impl Render {
             width,
             height,
             cursor_controller: CursorController::new(width, height),
+            pending_promotion: None,
+            pending_drop: None,
+            move_entry: None,
+            finished_game: None,
+            replay: None,
+            takebacks: true,
+            computer: None,
+            difficulty: Difficulty::default(),
+            threads: 1,
+            thinking: None,
+            book: None,
+            tablebase: None,
+            endgames: None,
+            analysis: false,
         })
     }
 
+    // Sets how many threads the computer searches with
+    pub fn set_threads(&mut self, threads: usize) {
+        self.threads = threads.max(1);
+    }
+
+    // Sets the opening book the computer plays from and the game screen lists moves from
+    pub fn set_book(&mut self, book: OpeningBook) {
+        self.book = Some(book);
+    }
+
+    // Sets the endgame tablebases the computer plays from and analysis mode shows results from
+    pub fn set_tablebase(&mut self, tablebase: Tablebase) {
+        self.tablebase = Some(Arc::new(tablebase));
+    }
+
+    // Sets the distance to mate tables the computer plays from
+    pub fn set_endgames(&mut self, endgames: EndgameTables) {
+        self.endgames = Some(Arc::new(endgames));
+    }
+
+    // Stops moves from being taken back or replayed on the game screen
+    pub fn disable_takebacks(&mut self) {
+        self.takebacks = false;
+    }
+
     pub fn clear_screen() -> crossterm::Result<()> {
         execute!(stdout(), terminal::Clear(terminal::ClearType::All))?;
         execute!(stdout(), cursor::MoveTo(0, 0))
@@ -51,10 +161,27 @@ impl Render {
         self.cursor_controller.move_cursor_far(direction);
     }
 
+    // Writes the FEN of the current game into the info row, if this screen has a game
+    pub fn show_fen(&mut self) {
+        let current_screen = &mut self.screens[self.current_screen];
+        if let Some(board) = &current_screen.game {
+            let fen = board.to_fen();
+            Render::write_info(
+                current_screen,
+                self.width,
+                self.height,
+                format!("FEN: {}", fen),
+            );
+        }
+    }
+
     // Code to press for each button press, mostly based on button IDs
     pub fn press_button(&mut self) {
         let cursor_x = self.cursor_controller.cursor_x;
         let cursor_y = self.cursor_controller.cursor_y;
+        if cursor_x < 16 && cursor_y < 8 && self.refuse_while_thinking() {
+            return;
+        }
         let current_screen = &mut self.screens[self.current_screen];
         if let Some(board) = &mut current_screen.game {
             if cursor_x < 16 && cursor_y < 8 {
@@ -66,6 +193,21 @@ impl Render {
                     }
                 }
 
+                if self.pending_promotion.is_some() {
+                    self.pending_promotion = None;
+                    board.moving = false;
+                    current_screen
+                        .screen_rows
+                        .clear_row(InsertVerticalPosition::Exact(PROMOTION_ROW));
+                    return;
+                }
+
+                if let Some(symbol) = self.pending_drop.take() {
+                    let chess_move = board.build_drop(symbol, cursor_y, cursor_x / 2);
+                    self.commit_move(chess_move);
+                    return;
+                }
+
                 let piece = board.query_board(cursor_y, cursor_x / 2).0;
 
                 if piece.symbol != ChessPieces::None
@@ -80,35 +222,31 @@ impl Render {
                     let selected_piece = board
                         .query_board(selected_piece_coords.0, selected_piece_coords.1)
                         .0;
-                    let did_win = board.move_piece(selected_piece, cursor_y, cursor_x / 2);
-
-                    let board_rows = board.display_board();
 
-                    for row in board_rows {
-                        for piece in row {
-                            current_screen.screen_rows.edit_single_row(piece);
-                        }
-                    }
-
-                    if did_win {
-                        current_screen.game = Some(Board::new());
-                        let board_rows = current_screen.game.unwrap().display_board();
-
-                        for row in board_rows {
-                            for piece in row {
-                                current_screen.screen_rows.edit_single_row(piece);
-                            }
-                        }
-                        self.current_screen += 1;
+                    if board.is_promotion(selected_piece, cursor_y)
+                        && board
+                            .filter_possible_moves(selected_piece)
+                            .contains(&(cursor_y, cursor_x / 2))
+                    {
+                        self.pending_promotion = Some((cursor_y, cursor_x / 2));
+                        let variant = board.variant;
+                        Render::show_promotion_picker(
+                            current_screen,
+                            self.width,
+                            self.height,
+                            variant,
+                        );
+                        return;
                     }
 
+                    self.play_move(cursor_y, cursor_x / 2, None);
                     return;
                 }
 
                 let piece_moves = board.filter_possible_moves(piece);
 
                 for tile in piece_moves {
-                    let piece = board.pieces[tile.0][tile.1];
+                    let piece = board.piece_at(tile.0, tile.1);
                     let piece_text = piece.get_symbol().on_dark_green().to_string();
                     current_screen.screen_rows.edit_single_row(Text::new(
                         piece_text,
@@ -121,29 +259,62 @@ impl Render {
 
                 board.set_selected(cursor_y, cursor_x / 2);
 
-                current_screen
-                    .screen_rows
-                    .clear_row(InsertVerticalPosition::Exact(10));
-
-                current_screen
-                    .screen_rows
-                    .edit_single_row(Text::Plain(PlainText::new(
-                        format!("{}'s turn", if board.white_move { "White" } else { "Red" }),
-                        self.width,
-                        self.height,
-                        InsertHorizontalPosition::Exact(0),
-                        InsertVerticalPosition::Exact(10),
-                    )));
+                let white_move = board.white_move;
+                let in_check = board.is_in_check(white_move);
+                Render::write_status(
+                    current_screen,
+                    self.width,
+                    self.height,
+                    white_move,
+                    in_check,
+                );
             }
         }
         for button in current_screen.screen_rows.buttons[cursor_y].clone() {
             if button.position_x <= cursor_x && button.position_x + button.length > cursor_x {
-                if button.on_click == "next_screen" {
+                if let Some(promotion) = Render::promotion_choice(button.on_click) {
+                    if let Some((rank, file)) = self.pending_promotion.take() {
+                        self.screens[self.current_screen]
+                            .screen_rows
+                            .clear_row(InsertVerticalPosition::Exact(PROMOTION_ROW));
+                        self.play_move(rank, file, Some(promotion));
+                    }
+                    return;
+                } else if button.on_click == "claim_draw" {
+                    self.claim_draw();
+                    return;
+                } else if button.on_click == "save_pgn" {
+                    self.save_pgn();
+                    return;
+                } else if button.on_click == "chess960" {
+                    self.start_chess960();
+                    return;
+                } else if button.on_click == "play_game" {
+                    self.computer = None;
+                    self.current_screen += 1;
+                } else if button.on_click == "play_computer" {
+                    self.start_computer_game();
+                    return;
+                } else if button.on_click == "cycle_difficulty" {
+                    self.cycle_difficulty();
+                    return;
+                } else if button.on_click == "cycle_variant" {
+                    self.cycle_variant();
+                    return;
+                } else if let Some((white, symbol)) = Render::drop_choice(button.on_click) {
+                    self.select_drop(white, symbol);
+                    return;
+                } else if button.on_click == "next_screen" {
                     self.current_screen += 1;
                 } else if button.on_click == "last_screen" {
                     self.current_screen -= 1;
                 } else if button.on_click == "reset_game" {
                     self.current_screen = 0;
+                } else if button.on_click == "replay_screen" {
+                    if let Some(replay) = &self.replay {
+                        self.current_screen = replay.screen_index;
+                    }
+                    return;
                 } else {
                     let on_click = current_screen
                         .button_map
@@ -156,6 +327,1012 @@ impl Render {
         }
     }
 
+    // Moves the selected piece of the current game to (rank, file), then redraws the board and ends the game if it is over
+    fn play_move(&mut self, rank: usize, file: usize, promotion: Option<ChessPieces>) {
+        let board = match &mut self.screens[self.current_screen].game {
+            Some(board) => board,
+            None => return,
+        };
+        let selected_piece_coords = board.selected_piece.unwrap();
+        let selected_piece = board
+            .query_board(selected_piece_coords.0, selected_piece_coords.1)
+            .0;
+        let chess_move = board.build_move(selected_piece, rank, file, promotion);
+        self.commit_move(chess_move);
+    }
+
+    // Plays a move in the current game if it is legal, then redraws the board and ends the game if it is over
+    fn commit_move(&mut self, chess_move: Move) {
+        let current_screen = &mut self.screens[self.current_screen];
+        let board = match &mut current_screen.game {
+            Some(board) => board,
+            None => return,
+        };
+        let san = if board.legal_moves().contains(&chess_move) {
+            board.to_san(&chess_move)
+        } else {
+            String::new()
+        };
+        board.moving = false;
+        let did_move = board.play_move(chess_move);
+
+        let board_rows = board.display_board();
+
+        for row in board_rows {
+            for piece in row {
+                current_screen.screen_rows.edit_single_row(piece);
+            }
+        }
+        let game_state = board.game_state();
+        let white_move = board.white_move;
+        Render::draw_pockets(current_screen, self.width, self.height, None);
+        Render::draw_book(current_screen, self.width, self.height, self.book.as_ref());
+        Render::draw_analysis(
+            current_screen,
+            self.width,
+            self.height,
+            self.tablebase.as_deref().filter(|_| self.analysis),
+        );
+
+        if !did_move {
+            return;
+        }
+
+        if game_state.is_over() {
+            self.end_game(game_state, !white_move);
+            return;
+        }
+
+        Render::write_status(
+            current_screen,
+            self.width,
+            self.height,
+            white_move,
+            game_state == GameState::Check,
+        );
+        Render::write_info(
+            current_screen,
+            self.width,
+            self.height,
+            format!("Last move: {}", san),
+        );
+    }
+
+    // Whether the user is currently typing a move in
+    pub fn is_entering_move(&self) -> bool {
+        self.move_entry.is_some()
+    }
+
+    // Opens the move prompt in the info row, if this screen has a game
+    pub fn start_move_entry(&mut self) {
+        if self.screens[self.current_screen].game.is_some() && self.pending_promotion.is_none() {
+            self.move_entry = Some(String::new());
+            self.show_move_entry();
+        }
+    }
+
+    // Adds a typed character to the move prompt
+    pub fn type_move_char(&mut self, char: char) {
+        if let Some(entry) = &mut self.move_entry {
+            entry.push(char);
+            self.show_move_entry();
+        }
+    }
+
+    // Removes the last character from the move prompt
+    pub fn erase_move_char(&mut self) {
+        if let Some(entry) = &mut self.move_entry {
+            entry.pop();
+            self.show_move_entry();
+        }
+    }
+
+    // Closes the move prompt without playing anything
+    pub fn cancel_move_entry(&mut self) {
+        self.move_entry = None;
+        self.screens[self.current_screen]
+            .screen_rows
+            .clear_row(InsertVerticalPosition::Exact(INFO_ROW));
+    }
+
+    // Plays the typed move, read as SAN first and as UCI otherwise
+    pub fn submit_move_entry(&mut self) {
+        let entry = match self.move_entry.take() {
+            Some(entry) => entry,
+            None => return,
+        };
+        if self.refuse_while_thinking() {
+            return;
+        }
+        let current_screen = &mut self.screens[self.current_screen];
+        let board = match &current_screen.game {
+            Some(board) => board,
+            None => return,
+        };
+
+        let parsed_move = match board.parse_san(&entry) {
+            Err(MoveParseError::Unparseable(_)) => board.parse_uci(&entry),
+            result => result,
+        };
+        match parsed_move {
+            Ok(chess_move) => self.commit_move(chess_move),
+            Err(error) => {
+                Render::write_info(current_screen, self.width, self.height, error.to_string())
+            }
+        }
+    }
+
+    // Redraws the move prompt with what has been typed so far
+    fn show_move_entry(&mut self) {
+        if let Some(entry) = &self.move_entry {
+            Render::write_info(
+                &mut self.screens[self.current_screen],
+                self.width,
+                self.height,
+                format!("Move: {}", entry),
+            );
+        }
+    }
+
+    // Claims a draw in the current game if the rules allow one, otherwise explains why not
+    fn claim_draw(&mut self) {
+        let current_screen = &mut self.screens[self.current_screen];
+        let board = match &mut current_screen.game {
+            Some(board) => board,
+            None => return,
+        };
+
+        if board.claim_draw() {
+            let game_state = board.game_state();
+            self.end_game(game_state, false);
+            return;
+        }
+
+        Render::write_info(
+            current_screen,
+            self.width,
+            self.height,
+            "No draw can be claimed yet".to_string(),
+        );
+    }
+
+    // Sets up a random Chess960 position on the game screen, which comes after the current screen, and switches to it
+    fn start_chess960(&mut self) {
+        let game_screen = &mut self.screens[self.current_screen + 1];
+        let board = Board::random_chess960();
+        for row in board.display_board() {
+            for piece in row {
+                game_screen.screen_rows.edit_single_row(piece);
+            }
+        }
+        game_screen.game = Some(board);
+        self.pending_promotion = None;
+        self.pending_drop = None;
+        self.computer = None;
+        game_screen
+            .screen_rows
+            .clear_row(InsertVerticalPosition::Exact(PROMOTION_ROW));
+        Render::write_status(game_screen, self.width, self.height, true, false);
+        Render::write_info(
+            game_screen,
+            self.width,
+            self.height,
+            "Chess960: castle by moving the king onto the rook".to_string(),
+        );
+        Render::write_variant_button(game_screen, self.width, self.height, Variant::Standard);
+        Render::draw_pockets(game_screen, self.width, self.height, None);
+        Render::draw_book(game_screen, self.width, self.height, self.book.as_ref());
+        Render::draw_analysis(
+            game_screen,
+            self.width,
+            self.height,
+            self.tablebase.as_deref().filter(|_| self.analysis),
+        );
+        self.current_screen += 1;
+    }
+
+    // Starts a new game against the computer on the game screen, which comes after the current screen, and switches to it
+    // The human plays white and the computer red, under whichever variant the game screen was set to
+    fn start_computer_game(&mut self) {
+        let game_screen = &mut self.screens[self.current_screen + 1];
+        let variant = game_screen
+            .game
+            .as_ref()
+            .map_or(Variant::Standard, |board| board.variant);
+        let board = Board::with_variant(variant);
+        for row in board.display_board() {
+            for piece in row {
+                game_screen.screen_rows.edit_single_row(piece);
+            }
+        }
+        game_screen.game = Some(board);
+        self.pending_promotion = None;
+        self.pending_drop = None;
+        self.computer = Some(false);
+        game_screen
+            .screen_rows
+            .clear_row(InsertVerticalPosition::Exact(PROMOTION_ROW));
+        Render::write_status(game_screen, self.width, self.height, true, false);
+        Render::write_info(
+            game_screen,
+            self.width,
+            self.height,
+            format!(
+                "Playing the computer at {} level, you are white",
+                self.difficulty.name()
+            ),
+        );
+        Render::draw_pockets(game_screen, self.width, self.height, None);
+        Render::draw_book(game_screen, self.width, self.height, self.book.as_ref());
+        Render::draw_analysis(
+            game_screen,
+            self.width,
+            self.height,
+            self.tablebase.as_deref().filter(|_| self.analysis),
+        );
+        self.current_screen += 1;
+    }
+
+    // Whether the game on the current screen is waiting for the computer to move
+    pub fn computer_to_move(&self) -> bool {
+        match &self.screens[self.current_screen].game {
+            Some(board) => {
+                self.computer == Some(board.white_move) && self.pending_promotion.is_none()
+            }
+            None => false,
+        }
+    }
+
+    // Whether the computer is searching for its next move
+    pub fn is_thinking(&self) -> bool {
+        self.thinking.is_some()
+    }
+
+    // Whether the computer has found its move, which update_computer will then play
+    pub fn search_finished(&self) -> bool {
+        self.thinking
+            .as_ref()
+            .is_some_and(BackgroundSearch::is_finished)
+    }
+
+    // Has the computer play the best move it has found so far instead of thinking any longer
+    pub fn move_now(&mut self) {
+        if let Some(search) = &self.thinking {
+            search.stop();
+        }
+    }
+
+    // Starts the computer thinking when it is its turn, and plays its move once it has found one
+    // A search of a position that is no longer on the screen, after a takeback or leaving the game, is stopped and thrown away
+    pub fn update_computer(&mut self) {
+        let board = self.screens[self.current_screen].game.as_ref();
+        if let Some(search) = self.thinking.take() {
+            if board.map(Board::hash) != Some(search.hash) || !self.computer_to_move() {
+                search.stop();
+            } else if search.is_finished() {
+                self.play_computer_move(search.result());
+            } else {
+                self.thinking = Some(search);
+            }
+            return;
+        }
+
+        if let (Some(board), true) = (board, self.computer_to_move()) {
+            if let Some(chess_move) = self.book.as_ref().and_then(|book| book.choose_move(board)) {
+                let san = board.to_san(&chess_move);
+                self.commit_move(chess_move);
+                let current_screen = &mut self.screens[self.current_screen];
+                if current_screen.game.is_some() {
+                    Render::write_info(
+                        current_screen,
+                        self.width,
+                        self.height,
+                        format!("Computer played {} from the opening book", san),
+                    );
+                }
+                return;
+            }
+
+            self.thinking = Some(BackgroundSearch::start(
+                board,
+                self.difficulty,
+                self.threads,
+                self.tablebase.clone(),
+                self.endgames.clone(),
+            ));
+            let current_screen = &mut self.screens[self.current_screen];
+            Render::write_info(
+                current_screen,
+                self.width,
+                self.height,
+                "The computer is thinking, Esc makes it move now".to_string(),
+            );
+        }
+    }
+
+    // Tells the user to wait for the computer, returning whether it is still thinking
+    fn refuse_while_thinking(&mut self) -> bool {
+        if self.thinking.is_none() {
+            return false;
+        }
+        let current_screen = &mut self.screens[self.current_screen];
+        Render::write_info(
+            current_screen,
+            self.width,
+            self.height,
+            "The computer is thinking, Esc makes it move now".to_string(),
+        );
+        true
+    }
+
+    // Plays the move the computer's search found in the current game
+    fn play_computer_move(&mut self, result: engine::SearchResult) {
+        let board = match &self.screens[self.current_screen].game {
+            Some(board) => board,
+            None => return,
+        };
+        let chess_move = match result.best_move {
+            Some(chess_move) => chess_move,
+            None => return,
+        };
+        let san = board.to_san(&chess_move);
+        self.commit_move(chess_move);
+
+        // The game screen is left behind when the move ended the game
+        let current_screen = &mut self.screens[self.current_screen];
+        if current_screen.game.is_some() {
+            Render::write_info(
+                current_screen,
+                self.width,
+                self.height,
+                format!(
+                    "Computer played {} ({}, depth {}, {} nodes)",
+                    san,
+                    engine::describe_score(result.score),
+                    result.depth,
+                    result.nodes
+                ),
+            );
+        }
+    }
+
+    // Moves the computer on to the next level and shows it on the level button of the current screen
+    fn cycle_difficulty(&mut self) {
+        self.difficulty = self.difficulty.next();
+        let current_screen = &mut self.screens[self.current_screen];
+        current_screen.screen_rows.buttons[LEVEL_BUTTON_ROW]
+            .retain(|button| button.on_click != "cycle_difficulty");
+        current_screen.screen_rows.edit_single_row(Text::new(
+            " ".repeat(LEVEL_BUTTON_WIDTH),
+            self.width / 2 + LEVEL_BUTTON_OFFSET,
+            LEVEL_BUTTON_ROW,
+            None,
+        ));
+        current_screen
+            .screen_rows
+            .edit_single_row(Text::Button(Render::difficulty_button(
+                self.width,
+                self.height,
+                self.difficulty,
+            )));
+    }
+
+    // The home screen button showing the computer's level, pressing it moves on to the next level
+    pub fn difficulty_button(width: usize, height: usize, difficulty: Difficulty) -> ButtonText {
+        ButtonText::new(
+            format!("Level: {}", difficulty.name()).red().to_string(),
+            width,
+            height,
+            InsertHorizontalPosition::Exact(width / 2 + LEVEL_BUTTON_OFFSET),
+            InsertVerticalPosition::Exact(LEVEL_BUTTON_ROW),
+            "cycle_difficulty",
+        )
+    }
+
+    // Switches the game on the current screen to the next variant, which is only allowed before the first move
+    fn cycle_variant(&mut self) {
+        let current_screen = &mut self.screens[self.current_screen];
+        let board = match &current_screen.game {
+            Some(board) => board,
+            None => return,
+        };
+        if !board.moves.is_empty() {
+            Render::write_info(
+                current_screen,
+                self.width,
+                self.height,
+                "The variant can only be changed before the first move".to_string(),
+            );
+            return;
+        }
+
+        let variant = board.variant.next();
+        let board = Board::with_variant(variant);
+        for row in board.display_board() {
+            for piece in row {
+                current_screen.screen_rows.edit_single_row(piece);
+            }
+        }
+        current_screen.game = Some(board);
+        self.pending_promotion = None;
+        current_screen
+            .screen_rows
+            .clear_row(InsertVerticalPosition::Exact(PROMOTION_ROW));
+        Render::write_status(current_screen, self.width, self.height, true, false);
+        Render::write_info(
+            current_screen,
+            self.width,
+            self.height,
+            format!("{}: {}", variant.name(), variant.rules()),
+        );
+        Render::write_variant_button(current_screen, self.width, self.height, variant);
+        self.pending_drop = None;
+        Render::draw_pockets(current_screen, self.width, self.height, None);
+        Render::draw_book(current_screen, self.width, self.height, self.book.as_ref());
+        Render::draw_analysis(
+            current_screen,
+            self.width,
+            self.height,
+            self.tablebase.as_deref().filter(|_| self.analysis),
+        );
+    }
+
+    // Maps the pocket panel's button IDs to the colour and type of piece they drop
+    fn drop_choice(on_click: &str) -> Option<(bool, ChessPieces)> {
+        match on_click {
+            "drop_white_queen" => Some((true, ChessPieces::Queen)),
+            "drop_white_rook" => Some((true, ChessPieces::Rook)),
+            "drop_white_bishop" => Some((true, ChessPieces::Bishop)),
+            "drop_white_knight" => Some((true, ChessPieces::Knight)),
+            "drop_white_pawn" => Some((true, ChessPieces::Pawn)),
+            "drop_red_queen" => Some((false, ChessPieces::Queen)),
+            "drop_red_rook" => Some((false, ChessPieces::Rook)),
+            "drop_red_bishop" => Some((false, ChessPieces::Bishop)),
+            "drop_red_knight" => Some((false, ChessPieces::Knight)),
+            "drop_red_pawn" => Some((false, ChessPieces::Pawn)),
+            _ => None,
+        }
+    }
+
+    // The button ID for dropping the given piece, the reverse of drop_choice
+    fn drop_button_id(white: bool, symbol: ChessPieces) -> &'static str {
+        match (white, symbol) {
+            (true, ChessPieces::Queen) => "drop_white_queen",
+            (true, ChessPieces::Rook) => "drop_white_rook",
+            (true, ChessPieces::Bishop) => "drop_white_bishop",
+            (true, ChessPieces::Knight) => "drop_white_knight",
+            (true, _) => "drop_white_pawn",
+            (false, ChessPieces::Queen) => "drop_red_queen",
+            (false, ChessPieces::Rook) => "drop_red_rook",
+            (false, ChessPieces::Bishop) => "drop_red_bishop",
+            (false, ChessPieces::Knight) => "drop_red_knight",
+            (false, _) => "drop_red_pawn",
+        }
+    }
+
+    // Picks a piece from the mover's pocket to drop and highlights the tiles it can be dropped on
+    fn select_drop(&mut self, white: bool, symbol: ChessPieces) {
+        if self.refuse_while_thinking() {
+            return;
+        }
+        let current_screen = &mut self.screens[self.current_screen];
+        let board = match &mut current_screen.game {
+            Some(board) => board,
+            None => return,
+        };
+        if white != board.white_move || self.pending_promotion.is_some() {
+            return;
+        }
+
+        board.moving = false;
+        for row in board.display_board() {
+            for piece in row {
+                current_screen.screen_rows.edit_single_row(piece);
+            }
+        }
+        for tile in board.drop_tiles(symbol) {
+            let piece = board.piece_at(tile.0, tile.1);
+            let piece_text = piece.get_symbol().on_dark_green().to_string();
+            current_screen.screen_rows.edit_single_row(Text::new(
+                piece_text,
+                tile.1 * 2,
+                tile.0,
+                None,
+            ));
+        }
+        self.pending_drop = Some(symbol);
+        Render::draw_pockets(current_screen, self.width, self.height, Some(symbol));
+        Render::write_info(
+            current_screen,
+            self.width,
+            self.height,
+            "Pick a highlighted tile to drop the piece on".to_string(),
+        );
+    }
+
+    // Redraws the Crazyhouse pockets next to the board, each held piece is a button that picks it for dropping
+    // The selected piece of the side to move is highlighted, and the panel is left blank for other variants
+    pub fn draw_pockets(
+        screen: &mut Screen,
+        width: usize,
+        height: usize,
+        selected: Option<ChessPieces>,
+    ) {
+        for row in [
+            RED_POCKET_ROW,
+            RED_POCKET_ROW + 1,
+            WHITE_POCKET_ROW,
+            WHITE_POCKET_ROW + 1,
+        ] {
+            screen.screen_rows.buttons[row].retain(|button| !button.on_click.starts_with("drop_"));
+            screen.screen_rows.edit_single_row(Text::new(
+                " ".repeat(POCKET_PANEL_WIDTH),
+                POCKET_PANEL_X,
+                row,
+                None,
+            ));
+        }
+
+        let (white_move, pockets) = match &screen.game {
+            Some(board) if board.variant == Variant::Crazyhouse => (
+                board.white_move,
+                [true, false].map(|white| {
+                    POCKET_PIECES.map(|symbol| (symbol, board.pocket_count(white, symbol)))
+                }),
+            ),
+            _ => return,
+        };
+        for (white, row) in [(true, WHITE_POCKET_ROW), (false, RED_POCKET_ROW)] {
+            let title = format!("{} pocket", if white { "White" } else { "Red" });
+            screen
+                .screen_rows
+                .edit_single_row(Text::new(title, POCKET_PANEL_X, row, None));
+
+            let mut position_x = POCKET_PANEL_X;
+            let pocket = pockets[if white { 0 } else { 1 }];
+            for (symbol, count) in pocket {
+                if count == 0 {
+                    continue;
+                }
+                let label = format!("{}{}", symbol.to_symbol(), count);
+                let label = match (white, white == white_move && selected == Some(symbol)) {
+                    (_, true) => label.on_dark_green().to_string(),
+                    (true, false) => label.white().to_string(),
+                    (false, false) => label.red().to_string(),
+                };
+                let button = ButtonText::new(
+                    label,
+                    width,
+                    height,
+                    InsertHorizontalPosition::Exact(position_x),
+                    InsertVerticalPosition::Exact(row + 1),
+                    Render::drop_button_id(white, symbol),
+                );
+                position_x += button.length + 1;
+                screen.screen_rows.edit_single_row(Text::Button(button));
+            }
+        }
+    }
+
+    // Lists the book moves for the position on the screen with their weights, or clears the list when there is no book
+    pub fn draw_book(screen: &mut Screen, width: usize, height: usize, book: Option<&OpeningBook>) {
+        for row in 0..BOOK_PANEL_ROWS {
+            screen.screen_rows.edit_single_row(Text::new(
+                " ".repeat(BOOK_PANEL_WIDTH),
+                BOOK_PANEL_X,
+                row,
+                None,
+            ));
+        }
+
+        let (book, board) = match (book, &screen.game) {
+            (Some(book), Some(board)) => (book, board),
+            _ => return,
+        };
+        let moves = book.moves(board);
+        let total: u32 = moves.iter().map(|book_move| book_move.weight as u32).sum();
+        let mut lines = vec!["Opening book".to_string()];
+        if moves.is_empty() {
+            lines.push("No book moves".dark_grey().to_string());
+        }
+        for book_move in moves.iter().take(BOOK_PANEL_ROWS - 1) {
+            lines.push(format!(
+                "{:<8}{:>6} {:>3}%",
+                board.to_san(&book_move.chess_move),
+                book_move.weight,
+                book_move.weight as u32 * 100 / total.max(1)
+            ));
+        }
+        for (row, line) in lines.into_iter().enumerate() {
+            screen
+                .screen_rows
+                .edit_single_row(Text::Plain(PlainText::new(
+                    line,
+                    width,
+                    height,
+                    InsertHorizontalPosition::Exact(BOOK_PANEL_X),
+                    InsertVerticalPosition::Exact(row),
+                )));
+        }
+    }
+
+    // Turns analysis mode on or off for the game screen, which shows what the tablebases say about the position
+    pub fn toggle_analysis(&mut self) {
+        let current_screen = &mut self.screens[self.current_screen];
+        if current_screen.game.is_none() {
+            return;
+        }
+        self.analysis = !self.analysis;
+        let message = match (self.analysis, &self.tablebase) {
+            (true, None) => {
+                "Analysis mode on, start with --syzygy <directory> for tablebase results"
+            }
+            (true, Some(_)) => "Analysis mode on",
+            (false, _) => "Analysis mode off",
+        };
+        Render::write_info(current_screen, self.width, self.height, message.to_string());
+        Render::draw_analysis(
+            current_screen,
+            self.width,
+            self.height,
+            self.tablebase.as_deref().filter(|_| self.analysis),
+        );
+    }
+
+    // Shows the tablebase result for the position on the screen, such as "TB: win in 12 plies to zeroing", or clears it when there is no tablebase
+    // The distance counts plies until the next capture or pawn move, without the offset the tables add past the fifty move rule
+    // Nothing is shown for positions the tables don't cover
+    fn draw_analysis(
+        screen: &mut Screen,
+        width: usize,
+        height: usize,
+        tablebase: Option<&Tablebase>,
+    ) {
+        screen.screen_rows.edit_single_row(Text::new(
+            " ".repeat(ANALYSIS_WIDTH),
+            BOOK_PANEL_X,
+            ANALYSIS_ROW,
+            None,
+        ));
+
+        let (tablebase, board) = match (tablebase, &screen.game) {
+            (Some(tablebase), Some(board)) => (tablebase, board),
+            _ => return,
+        };
+        let wdl = match tablebase.probe_wdl(board) {
+            Some(wdl) => wdl,
+            None => return,
+        };
+        let hint = match tablebase.probe_dtz(board) {
+            Some(dtz) if wdl != Wdl::Draw => {
+                let plies = match wdl {
+                    Wdl::CursedWin | Wdl::BlessedLoss => dtz.abs() - 100,
+                    _ => dtz.abs(),
+                };
+                format!("TB: {} in {} plies to zeroing", wdl.name(), plies)
+            }
+            _ => format!("TB: {}", wdl.name()),
+        };
+        screen
+            .screen_rows
+            .edit_single_row(Text::Plain(PlainText::new(
+                hint,
+                width,
+                height,
+                InsertHorizontalPosition::Exact(BOOK_PANEL_X),
+                InsertVerticalPosition::Exact(ANALYSIS_ROW),
+            )));
+    }
+
+    // Builds the button on the game screen that shows the variant and switches to the next one
+    pub fn variant_button(width: usize, height: usize, variant: Variant) -> ButtonText {
+        ButtonText::new(
+            format!("Variant: {}", variant.name()),
+            width,
+            height,
+            InsertHorizontalPosition::Exact(VARIANT_BUTTON_X),
+            InsertVerticalPosition::Center,
+            "cycle_variant",
+        )
+    }
+
+    // Replaces the variant button on the screen with one showing the given variant
+    fn write_variant_button(screen: &mut Screen, width: usize, height: usize, variant: Variant) {
+        let row = height / 2;
+        screen.screen_rows.buttons[row].retain(|button| button.on_click != "cycle_variant");
+        screen.screen_rows.edit_single_row(Text::new(
+            " ".repeat(VARIANT_BUTTON_WIDTH),
+            VARIANT_BUTTON_X,
+            row,
+            None,
+        ));
+        screen
+            .screen_rows
+            .edit_single_row(Text::Button(Render::variant_button(width, height, variant)));
+    }
+
+    // Takes back the last move of the current game
+    pub fn undo_move(&mut self) {
+        self.step_history(true);
+    }
+
+    // Plays the last move taken back in the current game again
+    pub fn redo_move(&mut self) {
+        self.step_history(false);
+    }
+
+    // Takes back or replays one move of the current game, then redraws the board
+    // Against the computer its reply is stepped over as well, so it is the human's turn again afterwards
+    fn step_history(&mut self, undo: bool) {
+        let current_screen = &mut self.screens[self.current_screen];
+        let board = match &mut current_screen.game {
+            Some(board) => board,
+            None => return,
+        };
+
+        if !self.takebacks {
+            Render::write_info(
+                current_screen,
+                self.width,
+                self.height,
+                "Takebacks are disabled for this game".to_string(),
+            );
+            return;
+        }
+
+        if self.pending_promotion.take().is_some() {
+            current_screen
+                .screen_rows
+                .clear_row(InsertVerticalPosition::Exact(PROMOTION_ROW));
+        }
+        self.pending_drop = None;
+        board.moving = false;
+
+        let mut stepped = Vec::new();
+        loop {
+            let san = if undo {
+                board.undo().map(|chess_move| board.to_san(&chess_move))
+            } else {
+                let san = board
+                    .redo_stack
+                    .last()
+                    .map(|chess_move| board.to_san(chess_move));
+                board.redo().and(san)
+            };
+            match san {
+                Some(san) => stepped.push(san),
+                None => break,
+            }
+            if self.computer != Some(board.white_move) {
+                break;
+            }
+        }
+
+        let message = match (undo, stepped.is_empty()) {
+            (true, true) => "There are no moves to take back".to_string(),
+            (true, false) => {
+                stepped.reverse();
+                format!("Took back {}", stepped.join(" "))
+            }
+            (false, true) => "There are no moves to replay".to_string(),
+            (false, false) => format!("Replayed {}", stepped.join(" ")),
+        };
+
+        for row in board.display_board() {
+            for piece in row {
+                current_screen.screen_rows.edit_single_row(piece);
+            }
+        }
+
+        let white_move = board.white_move;
+        let in_check = board.is_in_check(white_move);
+        Render::draw_pockets(current_screen, self.width, self.height, None);
+        Render::draw_book(current_screen, self.width, self.height, self.book.as_ref());
+        Render::draw_analysis(
+            current_screen,
+            self.width,
+            self.height,
+            self.tablebase.as_deref().filter(|_| self.analysis),
+        );
+        Render::write_status(
+            current_screen,
+            self.width,
+            self.height,
+            white_move,
+            in_check,
+        );
+        Render::write_info(current_screen, self.width, self.height, message);
+    }
+
+    // Resets the finished game on the current screen and moves on to the victory screen
+    fn end_game(&mut self, game_state: GameState, white_won: bool) {
+        let current_screen = &mut self.screens[self.current_screen];
+        let variant = current_screen
+            .game
+            .as_ref()
+            .map_or(Variant::Standard, |board| board.variant);
+        let new_game = Board::with_variant(variant);
+        for row in new_game.display_board() {
+            for piece in row {
+                current_screen.screen_rows.edit_single_row(piece);
+            }
+        }
+        self.finished_game = current_screen.game.replace(new_game);
+        self.pending_drop = None;
+        Render::draw_pockets(current_screen, self.width, self.height, None);
+        Render::draw_book(current_screen, self.width, self.height, self.book.as_ref());
+        Render::draw_analysis(
+            current_screen,
+            self.width,
+            self.height,
+            self.tablebase.as_deref().filter(|_| self.analysis),
+        );
+        Render::write_status(current_screen, self.width, self.height, true, false);
+        current_screen
+            .screen_rows
+            .clear_row(InsertVerticalPosition::Exact(INFO_ROW));
+
+        self.current_screen += 1;
+        self.show_game_result(game_state, white_won);
+    }
+
+    // Saves the game on the current screen, or the game that just finished, as a PGN file
+    fn save_pgn(&mut self) {
+        let current_screen = &mut self.screens[self.current_screen];
+        let (board, message_row) = match (&current_screen.game, &self.finished_game) {
+            (Some(board), _) => (board, INFO_ROW),
+            (None, Some(board)) => (board, self.height / 2 + 6),
+            (None, None) => return,
+        };
+
+        let message = match pgn::save_game(board) {
+            Ok(file_name) => format!("Saved the game to {}", file_name),
+            Err(error) => format!("Could not save the game: {}", error),
+        };
+        let horizontal_position = if message_row == INFO_ROW {
+            InsertHorizontalPosition::Exact(0)
+        } else {
+            InsertHorizontalPosition::Center
+        };
+        current_screen
+            .screen_rows
+            .clear_row(InsertVerticalPosition::Exact(message_row));
+        current_screen
+            .screen_rows
+            .edit_single_row(Text::Plain(PlainText::new(
+                message,
+                self.width,
+                self.height,
+                horizontal_position,
+                InsertVerticalPosition::Exact(message_row),
+            )));
+    }
+
+    // Shows the row of buttons used to pick what a pawn promotes into, which includes the king in Antichess
+    fn show_promotion_picker(screen: &mut Screen, width: usize, height: usize, variant: Variant) {
+        screen
+            .screen_rows
+            .clear_row(InsertVerticalPosition::Exact(PROMOTION_ROW));
+        screen
+            .screen_rows
+            .edit_single_row(Text::Plain(PlainText::new(
+                "Promote to:".to_string(),
+                width,
+                height,
+                InsertHorizontalPosition::Exact(0),
+                InsertVerticalPosition::Exact(PROMOTION_ROW),
+            )));
+
+        let choices = [
+            (ChessPieces::Queen, "Queen", "promote_queen"),
+            (ChessPieces::Rook, "Rook", "promote_rook"),
+            (ChessPieces::Bishop, "Bishop", "promote_bishop"),
+            (ChessPieces::Knight, "Knight", "promote_knight"),
+            (ChessPieces::King, "King", "promote_king"),
+        ];
+        let mut position_x = 12;
+        for (piece, name, on_click) in choices {
+            if !variant.promotion_pieces().contains(&piece) {
+                continue;
+            }
+            let label = format!("{} {}", piece.to_symbol(), name);
+            let button = ButtonText::new(
+                label.clone().on_dark_grey().to_string(),
+                width,
+                height,
+                InsertHorizontalPosition::Exact(position_x),
+                InsertVerticalPosition::Exact(PROMOTION_ROW),
+                on_click,
+            );
+            position_x += button.length + 1;
+            screen.screen_rows.edit_single_row(Text::Button(button));
+        }
+    }
+
+    // Maps the promotion picker's button IDs to the piece they promote into
+    fn promotion_choice(on_click: &str) -> Option<ChessPieces> {
+        match on_click {
+            "promote_queen" => Some(ChessPieces::Queen),
+            "promote_rook" => Some(ChessPieces::Rook),
+            "promote_bishop" => Some(ChessPieces::Bishop),
+            "promote_knight" => Some(ChessPieces::Knight),
+            "promote_king" => Some(ChessPieces::King),
+            _ => None,
+        }
+    }
+
+    // Rewrites the status row under the board with whose turn it is
+    fn write_status(
+        screen: &mut Screen,
+        width: usize,
+        height: usize,
+        white_move: bool,
+        check: bool,
+    ) {
+        screen
+            .screen_rows
+            .clear_row(InsertVerticalPosition::Exact(STATUS_ROW));
+
+        screen
+            .screen_rows
+            .edit_single_row(Text::Plain(PlainText::new(
+                format!(
+                    "{}'s turn{}",
+                    if white_move { "White" } else { "Red" },
+                    if check { " - Check!" } else { "" }
+                ),
+                width,
+                height,
+                InsertHorizontalPosition::Exact(0),
+                InsertVerticalPosition::Exact(STATUS_ROW),
+            )));
+    }
+
+    // Replaces the info row under the status row with a message
+    fn write_info(screen: &mut Screen, width: usize, height: usize, message: String) {
+        screen
+            .screen_rows
+            .clear_row(InsertVerticalPosition::Exact(INFO_ROW));
+        screen
+            .screen_rows
+            .edit_single_row(Text::Plain(PlainText::new(
+                message,
+                width,
+                height,
+                InsertHorizontalPosition::Exact(0),
+                InsertVerticalPosition::Exact(INFO_ROW),
+            )));
+    }
+
+    // Writes how the game finished onto the victory screen, which must be the current screen
+    fn show_game_result(&mut self, game_state: GameState, white_won: bool) {
+        let colour_name = |white: bool| if white { "White" } else { "Red" };
+        let winner = colour_name(white_won);
+        let message = match game_state {
+            GameState::Checkmate => format!("Checkmate! {} wins", winner),
+            GameState::Won { white, reason } => {
+                format!("{} wins by {}", colour_name(white), reason.description())
+            }
+            GameState::Stalemate => "Stalemate! The game is a draw".to_string(),
+            GameState::Draw(reason) => format!("The game is drawn by {}", reason.description()),
+            _ => "The game is still going".to_string(),
+        };
+
+        let victory_screen = &mut self.screens[self.current_screen];
+        victory_screen
+            .screen_rows
+            .clear_row(InsertVerticalPosition::Center);
+        victory_screen
+            .screen_rows
+            .edit_single_row(Text::Plain(PlainText::new(
+                message,
+                self.width,
+                self.height,
+                InsertHorizontalPosition::Center,
+                InsertVerticalPosition::Center,
+            )));
+    }
+
     // Re-renders the full screen by compiling the rows into a string and flushing it
     pub fn refresh_screen(&mut self) -> crossterm::Result<()> {
         queue!(
@@ -178,6 +1355,116 @@ impl Render {
     pub fn new_screen(&mut self, screen: Screen) {
         self.screens.push(screen);
     }
+
+    // Adds the screen used to step through games loaded from a PGN file
+    pub fn new_replay_screen(&mut self, screen: Screen, games: Vec<PgnGame>) {
+        self.replay = Some(Replay {
+            games,
+            game_index: 0,
+            ply: 0,
+            screen_index: self.screens.len(),
+        });
+        self.screens.push(screen);
+        self.draw_replay();
+    }
+
+    // Whether the replay screen is the one being shown
+    pub fn is_replaying(&self) -> bool {
+        matches!(&self.replay, Some(replay) if replay.screen_index == self.current_screen)
+    }
+
+    // Steps through the replay: left and right move by one ply, home and end jump, up and down change game
+    pub fn step_replay(&mut self, key: KeyCode) {
+        let replay = match &mut self.replay {
+            Some(replay) => replay,
+            None => return,
+        };
+        let game_count = replay.games.len();
+        let move_count = replay.games[replay.game_index].board.moves.len();
+        match key {
+            KeyCode::Left => replay.ply = replay.ply.saturating_sub(1),
+            KeyCode::Right => replay.ply = (replay.ply + 1).min(move_count),
+            KeyCode::Home => replay.ply = 0,
+            KeyCode::End => replay.ply = move_count,
+            KeyCode::Up if replay.game_index > 0 => {
+                replay.game_index -= 1;
+                replay.ply = 0;
+            }
+            KeyCode::Down if replay.game_index + 1 < game_count => {
+                replay.game_index += 1;
+                replay.ply = 0;
+            }
+            KeyCode::Esc => {
+                self.current_screen = 0;
+                return;
+            }
+            _ => {}
+        }
+        self.draw_replay();
+    }
+
+    // Redraws the replay screen with the position reached after the current ply
+    fn draw_replay(&mut self) {
+        let replay = match &self.replay {
+            Some(replay) => replay,
+            None => return,
+        };
+        let game = &replay.games[replay.game_index];
+        let mut board = game.board.starting_board();
+        let mut last_move = "start position".to_string();
+        for chess_move in &game.board.moves[..replay.ply] {
+            let move_number = if board.white_move {
+                format!("{}.", board.fullmove_number)
+            } else {
+                format!("{}...", board.fullmove_number)
+            };
+            last_move = format!("{} {}", move_number, board.to_san(chess_move));
+            board.play_move(*chess_move);
+        }
+
+        let lines = [
+            format!(
+                "{} vs {} - {}, {}",
+                game.tag("White").unwrap_or("?"),
+                game.tag("Black").unwrap_or("?"),
+                game.tag("Event").unwrap_or("?"),
+                game.tag("Date").unwrap_or("?")
+            ),
+            format!(
+                "Move {} of {}: {}",
+                replay.ply,
+                game.board.moves.len(),
+                last_move
+            ),
+            format!(
+                "Game {} of {}, result {}",
+                replay.game_index + 1,
+                replay.games.len(),
+                game.result
+            ),
+        ];
+
+        let replay_screen = &mut self.screens[replay.screen_index];
+        for row in board.display_board() {
+            for piece in row {
+                replay_screen.screen_rows.edit_single_row(piece);
+            }
+        }
+        for (i, line) in lines.into_iter().enumerate() {
+            replay_screen
+                .screen_rows
+                .clear_row(InsertVerticalPosition::Exact(PROMOTION_ROW + i));
+            replay_screen
+                .screen_rows
+                .edit_single_row(Text::Plain(PlainText::new(
+                    line,
+                    self.width,
+                    self.height,
+                    InsertHorizontalPosition::Exact(0),
+                    InsertVerticalPosition::Exact(PROMOTION_ROW + i),
+                )));
+        }
+    }
 }
 
 #[derive(Debug)]
@@ -187,7 +1474,9 @@ pub enum RenderError {
 
 impl std::fmt::Display for RenderError {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
-        write!(f, "Render error")
+        match self {
+            RenderError::TerminalSizeError(message) => write!(f, "Render error: {}", message),
+        }
     }
 }
 
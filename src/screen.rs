@@ -9,7 +9,9 @@ use regex::Regex;
 pub struct Screen {
     content: String,
     pub screen_rows: ScreenRows,
+    #[allow(dead_code)]
     pub width: usize,
+    #[allow(dead_code)]
     pub height: usize,
     pub button_map: HashMap<&'static str, Box<dyn Fn()>>,
     pub game: Option<Board>,
@@ -89,16 +91,16 @@ impl ScreenRows {
 
         if escape_indices.len() > 1 {
             let back_escape_index = escape_indices[escape_indices.len() / 2];
-            let front_ansi: String = (&text.chars().collect::<Vec<char>>()
-                [..back_escape_index - text_len])
+            let front_ansi: String = text.chars().collect::<Vec<char>>()
+                [..back_escape_index - text_len]
                 .iter()
                 .collect();
-            let back_ansi: String = (&text.chars().collect::<Vec<char>>()[back_escape_index..])
+            let back_ansi: String = text.chars().collect::<Vec<char>>()[back_escape_index..]
                 .iter()
                 .collect();
 
-            let chars: Vec<char> = (&text.chars().collect::<Vec<char>>()
-                [back_escape_index - text_len..back_escape_index])
+            let chars: Vec<char> = text.chars().collect::<Vec<char>>()
+                [back_escape_index - text_len..back_escape_index]
                 .to_vec();
             let mut strings: Vec<String> = chars.iter().map(|c| c.to_string()).collect();
             let last_index = &strings.len() - 1;
@@ -126,13 +128,12 @@ impl ScreenRows {
             self.rows[text.position_y()][i + text.position_x()] = str.to_string();
         }
 
-        match text {
-            Text::Button(button) => self.buttons[button.position_y].push(button),
-            _ => {}
-        };
+        if let Text::Button(button) = text {
+            self.buttons[button.position_y].push(button);
+        }
     }
 
-    // Clears the content of a given row
+    // Clears the content of a given row, along with any buttons on it
     pub fn clear_row(&mut self, row: InsertVerticalPosition) {
         let row_num = match row {
             InsertVerticalPosition::Bottom => self.height - 1,
@@ -140,6 +141,7 @@ impl ScreenRows {
             InsertVerticalPosition::Exact(num) => num,
         };
         self.rows[row_num] = vec![" ".to_string(); self._width];
+        self.buttons[row_num].clear();
     }
 
     // Bulk edit rows, can specify the gap between rows and its vertical position
@@ -0,0 +1,1663 @@
+// Probes Syzygy endgame tablebases: the .rtbw files hold whether each position is won, drawn or lost
+// and the .rtbz files how many plies it is until the next capture or pawn move (distance to zeroing) on the way there
+// Each position is turned into an index the way the generator did it, mirroring the board and grouping like pieces,
+// and the value at that index is unpacked from blocks of Huffman coded symbols that each stand for a run of values
+
+use std::collections::{HashMap, HashSet};
+use std::path::PathBuf;
+use std::sync::{Arc, Mutex};
+
+use crate::bitboard::squares;
+use crate::chess::{Board, CastlingRights, ChessPieces, Move, Variant};
+
+// The most pieces a Syzygy table covers
+const MAX_PIECES: usize = 7;
+
+// The first four bytes of every table file
+const WDL_MAGIC: [u8; 4] = [0x71, 0xe8, 0x23, 0x5d];
+const DTZ_MAGIC: [u8; 4] = [0xd7, 0x66, 0x0c, 0xa5];
+
+// Flags stored with each part of a table
+// Which side to move a DTZ table holds
+const FLAG_STM: u8 = 1;
+// DTZ values are stored as indices into a map of the real values
+const FLAG_MAPPED: u8 = 2;
+// Wins and losses are counted in plies rather than in moves
+const FLAG_WIN_PLIES: u8 = 4;
+const FLAG_LOSS_PLIES: u8 = 8;
+// The DTZ map holds 16 bit values
+const FLAG_WIDE: u8 = 16;
+// Every position has the same value, which is stored instead of any compressed data
+const FLAG_SINGLE_VALUE: u8 = 128;
+
+// How many ways three unique pieces can stand once the first is mirrored into the a1-d1-d4 triangle
+const UNIQUE_TRIPLES: u64 = 31_332;
+// How many ways the two kings can stand next to no other piece once mirrored the same way
+const KING_PAIRS: u64 = 462;
+
+// The letters table names use, in the order the pieces are listed in them
+const NAME_LETTERS: [(ChessPieces, char); 6] = [
+    (ChessPieces::King, 'K'),
+    (ChessPieces::Queen, 'Q'),
+    (ChessPieces::Rook, 'R'),
+    (ChessPieces::Bishop, 'B'),
+    (ChessPieces::Knight, 'N'),
+    (ChessPieces::Pawn, 'P'),
+];
+
+// A position's result for the side to move, a cursed win or blessed loss is one the fifty move rule turns into a draw
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord)]
+pub enum Wdl {
+    Loss,
+    BlessedLoss,
+    Draw,
+    CursedWin,
+    Win,
+}
+
+impl Wdl {
+    pub fn name(self) -> &'static str {
+        match self {
+            Wdl::Loss => "loss",
+            Wdl::BlessedLoss => "blessed loss",
+            Wdl::Draw => "draw",
+            Wdl::CursedWin => "cursed win",
+            Wdl::Win => "win",
+        }
+    }
+
+    // Tables store results as -2 for a loss up to 2 for a win
+    fn from_score(score: i32) -> Self {
+        match score {
+            i32::MIN..=-2 => Wdl::Loss,
+            -1 => Wdl::BlessedLoss,
+            0 => Wdl::Draw,
+            1 => Wdl::CursedWin,
+            _ => Wdl::Win,
+        }
+    }
+}
+
+// The tablebases in a local directory, tables are only read once a position needs them
+pub struct Tablebase {
+    directory: PathBuf,
+    // The names of the table files in the directory, such as KQvK.rtbw
+    files: HashSet<String>,
+    // The most pieces any of the tables covers
+    max_pieces: usize,
+    indices: Indices,
+    // Every table read so far by file name, None for one that could not be read
+    loaded: Mutex<HashMap<String, Option<Arc<Table>>>>,
+}
+
+impl Tablebase {
+    // Finds the table files in a directory, failing when it can't be read or holds none
+    pub fn open(directory: &str) -> Result<Self, TablebaseError> {
+        let mut files = HashSet::new();
+        for entry in std::fs::read_dir(directory).map_err(TablebaseError::Io)? {
+            let name = entry.map_err(TablebaseError::Io)?.file_name();
+            let name = name.to_string_lossy();
+            if name.ends_with(".rtbw") || name.ends_with(".rtbz") {
+                files.insert(name.to_string());
+            }
+        }
+        if files.is_empty() {
+            return Err(TablebaseError::NoTables(directory.to_string()));
+        }
+
+        let max_pieces = files
+            .iter()
+            .map(|name| name.len() - ".rtbw".len() - "v".len())
+            .max()
+            .unwrap_or(0)
+            .min(MAX_PIECES);
+        Ok(Self {
+            directory: PathBuf::from(directory),
+            files,
+            max_pieces,
+            indices: Indices::new(),
+            loaded: Mutex::new(HashMap::new()),
+        })
+    }
+
+    // Whether the position is small enough to look up and plays by the rules the tables were built for
+    fn covers(&self, board: &Board) -> bool {
+        board.variant == Variant::Standard
+            && board.castling == CastlingRights::none()
+            && board.occupied().count_ones() as usize <= self.max_pieces
+    }
+
+    // Whether the side to move wins, draws or loses with perfect play, or None when the tables don't cover the position
+    pub fn probe_wdl(&self, board: &Board) -> Option<Wdl> {
+        if !self.covers(board) {
+            return None;
+        }
+        let (score, _) = self.search(&mut board.clone(), false)?;
+        Some(Wdl::from_score(score))
+    }
+
+    // How many plies the side to move is from the capture or pawn move that wins, positive when winning and negative when losing
+    // 0 for a drawn position, and beyond 100 either way for a cursed win or blessed loss
+    pub fn probe_dtz(&self, board: &Board) -> Option<i32> {
+        if !self.covers(board) {
+            return None;
+        }
+        self.dtz(&mut board.clone())
+    }
+
+    // Picks the move that keeps the position's result with the shortest distance to zeroing, or puts off a loss the longest
+    // Returns the move with the position's distance to zeroing when playing it, None when the DTZ tables don't cover every move
+    pub fn best_move(&self, board: &Board) -> Option<(Move, i32)> {
+        if !self.covers(board) {
+            return None;
+        }
+        let mut board = board.clone();
+        let mut best: Option<(Move, i32)> = None;
+        for chess_move in board.generate_legal_moves() {
+            let zeroing = chess_move.capture.is_some() || chess_move.piece == ChessPieces::Pawn;
+            let undo = board.make_move(chess_move);
+            let dtz = if zeroing {
+                // The move itself zeroes, so only its result counts
+                self.search(&mut board, false)
+                    .map(|(score, _)| -dtz_before_zeroing(score))
+            } else {
+                self.dtz(&mut board).map(|dtz| -dtz - dtz.signum())
+            };
+            let mates = board.is_in_check(board.white_move) && !board.has_legal_moves();
+            board.unmake_move(chess_move, undo);
+
+            let dtz = if mates { 1 } else { dtz? };
+            if best.is_none_or(|(_, best_dtz)| dtz_rank(dtz) > dtz_rank(best_dtz)) {
+                best = Some((chess_move, dtz));
+            }
+        }
+        best
+    }
+
+    // The position's result as -2 to 2, with whether the best move is a capture or pawn move, whose distances DTZ tables don't store
+    // Captures have to be tried as well as probing the table, as the generator stored whatever compressed best for
+    // positions where a capture is at least as good as the stored result; with zeroing set pawn moves are tried too
+    fn search(&self, board: &mut Board, zeroing: bool) -> Option<(i32, bool)> {
+        let moves = board.generate_legal_moves();
+        let mut best = -2;
+        let mut tried = 0;
+        for chess_move in &moves {
+            if chess_move.capture.is_none() && (!zeroing || chess_move.piece != ChessPieces::Pawn) {
+                continue;
+            }
+            tried += 1;
+            let undo = board.make_move(*chess_move);
+            let result = self.search(board, false);
+            board.unmake_move(*chess_move, undo);
+            let score = -result?.0;
+            if score > best {
+                best = score;
+                if score >= 2 {
+                    return Some((score, true));
+                }
+            }
+        }
+
+        // With every move already tried the table isn't needed, and it may be wrong when en passant is possible
+        let no_more_moves = tried > 0 && tried == moves.len();
+        let score = if no_more_moves {
+            best
+        } else {
+            self.probe_wdl_table(board)?
+        };
+        if best >= score {
+            return Some((best, best > 0 || no_more_moves));
+        }
+        Some((score, false))
+    }
+
+    // The distance to zeroing of a position the tables cover, see probe_dtz
+    fn dtz(&self, board: &mut Board) -> Option<i32> {
+        let (score, zeroing) = self.search(board, true)?;
+        if score == 0 {
+            return Some(0);
+        }
+        if zeroing {
+            return Some(dtz_before_zeroing(score));
+        }
+
+        let table = self.find_table(board, true)?;
+        match self.probe_table(&table, board)? {
+            Lookup::Stored(value, file) => {
+                let dtz = table.map_dtz(value, file, score)?;
+                let cursed = if score.abs() == 1 { 100 } else { 0 };
+                Some((dtz + cursed) * score.signum())
+            }
+            // The table only holds the other side to move, so look one move ahead for the best distance
+            Lookup::OtherSide => {
+                let mut best = None;
+                for chess_move in board.generate_legal_moves() {
+                    let zeroing =
+                        chess_move.capture.is_some() || chess_move.piece == ChessPieces::Pawn;
+                    let undo = board.make_move(chess_move);
+                    let dtz = if zeroing {
+                        self.search(board, false)
+                            .map(|(score, _)| -dtz_before_zeroing(score))
+                    } else {
+                        self.dtz(board).map(|dtz| -dtz - dtz.signum())
+                    };
+                    let mates = board.is_in_check(board.white_move) && !board.has_legal_moves();
+                    board.unmake_move(chess_move, undo);
+
+                    let dtz = if mates { 1 } else { dtz? };
+                    if dtz.signum() == score.signum() && best.is_none_or(|best| dtz < best) {
+                        best = Some(dtz);
+                    }
+                }
+                // With no legal moves the side to move has been mated
+                Some(best.unwrap_or(-1))
+            }
+        }
+    }
+
+    // The WDL table's score for the position, -2 to 2, without looking at any captures
+    fn probe_wdl_table(&self, board: &Board) -> Option<i32> {
+        // Two bare kings need no table
+        if board.occupied().count_ones() == 2 {
+            return Some(0);
+        }
+        let table = self.find_table(board, false)?;
+        match self.probe_table(&table, board)? {
+            Lookup::Stored(value, _) => Some(value - 2),
+            Lookup::OtherSide => None,
+        }
+    }
+
+    // The table for the position's material, named with either colour's pieces first
+    fn find_table(&self, board: &Board, dtz: bool) -> Option<Arc<Table>> {
+        let white = material(board, true);
+        let black = material(board, false);
+        self.table(&format!("{}v{}", white, black), dtz)
+            .or_else(|| self.table(&format!("{}v{}", black, white), dtz))
+    }
+
+    // Reads the table with the given name the first time it is asked for
+    fn table(&self, name: &str, dtz: bool) -> Option<Arc<Table>> {
+        let file_name = format!("{}.{}", name, if dtz { "rtbz" } else { "rtbw" });
+        if !self.files.contains(&file_name) {
+            return None;
+        }
+        let mut loaded = self.loaded.lock().unwrap();
+        loaded
+            .entry(file_name)
+            .or_insert_with_key(|file_name| {
+                let bytes = std::fs::read(self.directory.join(file_name)).ok()?;
+                Table::parse(name, bytes, dtz, &self.indices).map(Arc::new)
+            })
+            .clone()
+    }
+
+    // Works out the position's index in the table and reads the value stored there
+    fn probe_table(&self, table: &Table, board: &Board) -> Option<Lookup> {
+        match self.place(table, board)? {
+            Place::At { stm, file, index } => Some(Lookup::Stored(
+                table.decompress(table.pairs(stm, file), index)?,
+                file,
+            )),
+            Place::OtherSide => Some(Lookup::OtherSide),
+        }
+    }
+
+    // Works out which part of the table holds the position and the position's index in it
+    fn place(&self, table: &Table, board: &Board) -> Option<Place> {
+        let indices = &self.indices;
+        // Tables are built with the side listed first as white and, when both sides have the same pieces, with white to move
+        // Other positions are looked up with the colours swapped and the board turned round
+        let symmetric_black_to_move = table.symmetric && !board.white_move;
+        let black_stronger = material(board, true) != table.first_side;
+        let flip = symmetric_black_to_move || black_stronger;
+        let (flip_colour, flip_squares) = if flip { (8, 56) } else { (0, 0) };
+        let stm = (flip == board.white_move) as usize;
+
+        let mut squares_of = [0; MAX_PIECES];
+        let mut pieces = [0; MAX_PIECES];
+        let mut size = 0;
+        let mut lead_pawns = 0;
+        let mut file = 0;
+        // Tables with pawns are split by the file of the leading pawn, the one furthest from the centre and then the lowest
+        if table.has_pawns {
+            let lead_white = (table.pairs[0][0].pieces[0] ^ flip_colour) < 8;
+            lead_pawns = board.pieces_of(lead_white, ChessPieces::Pawn);
+            for square in squares(lead_pawns) {
+                squares_of[size] = tb_square(square) ^ flip_squares;
+                size += 1;
+            }
+            let leading = (0..size).max_by_key(|index| indices.map_pawns[squares_of[*index]])?;
+            squares_of.swap(0, leading);
+            file = (squares_of[0] % 8).min(7 - squares_of[0] % 8);
+        }
+        let lead_count = size;
+
+        if table.dtz {
+            // A pawnless table with the same pieces on both sides holds every position either way round
+            let flags = table.pairs(stm, file).flags;
+            let either_side = table.symmetric && !table.has_pawns;
+            if (flags & FLAG_STM) as usize != stm && !either_side {
+                return Some(Place::OtherSide);
+            }
+        }
+
+        for white in [true, false] {
+            for (symbol, _) in NAME_LETTERS {
+                for square in squares(board.pieces_of(white, symbol) & !lead_pawns) {
+                    if size == MAX_PIECES {
+                        return None;
+                    }
+                    squares_of[size] = tb_square(square) ^ flip_squares;
+                    pieces[size] = piece_code(white, symbol) ^ flip_colour;
+                    size += 1;
+                }
+            }
+        }
+
+        // Put the pieces in the order the generator listed them in
+        let pairs = table.pairs(stm, file);
+        for i in lead_count..size.saturating_sub(1) {
+            if let Some(j) = (i + 1..size).find(|j| pairs.pieces[i] == pieces[*j]) {
+                pieces.swap(i, j);
+                squares_of.swap(i, j);
+            }
+        }
+
+        // Mirror the board so the leading piece stands on the queen side
+        if squares_of[0] % 8 > 3 {
+            for square in &mut squares_of[..size] {
+                *square ^= 7;
+            }
+        }
+
+        let mut index = if table.has_pawns {
+            let mut index = indices.lead_pawn_index[lead_count][squares_of[0]];
+            squares_of[1..lead_count].sort_by_key(|square| indices.map_pawns[*square]);
+            for (i, square) in squares_of.iter().enumerate().take(lead_count).skip(1) {
+                index += indices.binomial[i][indices.map_pawns[*square]];
+            }
+            index
+        } else {
+            // Without pawns the board can also be mirrored so the leading piece is on the first four ranks
+            if squares_of[0] / 8 > 3 {
+                for square in &mut squares_of[..size] {
+                    *square ^= 56;
+                }
+            }
+            // and then along the a1-h8 diagonal so the first leading piece off it is below it
+            for i in 0..pairs.group_len[0] {
+                let diagonal = off_diagonal(squares_of[i]);
+                if diagonal == 0 {
+                    continue;
+                }
+                if diagonal > 0 {
+                    for square in &mut squares_of[i..size] {
+                        *square = flip_diagonal(*square);
+                    }
+                }
+                break;
+            }
+            if table.has_unique_pieces {
+                unique_triple_index(indices, &squares_of)
+            } else {
+                indices.map_kk[indices.map_a1d1d4[squares_of[0]]][squares_of[1]] as u64
+            }
+        };
+
+        // Every other group is placed on the squares the groups before it left free, like pieces in either order count once
+        index *= pairs.group_idx[0];
+        let mut start = pairs.group_len[0];
+        let mut remaining_pawns = table.has_pawns && table.pawn_count[1] > 0;
+        let mut group = 1;
+        while pairs.group_len[group] != 0 {
+            let length = pairs.group_len[group];
+            if start + length > size {
+                return None;
+            }
+            squares_of[start..start + length].sort_unstable();
+            let mut group_index = 0;
+            for i in 0..length {
+                let square = squares_of[start + i];
+                let below = squares_of[..start]
+                    .iter()
+                    .filter(|other| square > **other)
+                    .count();
+                let skipped = if remaining_pawns { 8 } else { 0 };
+                group_index += indices.binomial[i + 1][square.checked_sub(below + skipped)?];
+            }
+            remaining_pawns = false;
+            index += group_index * pairs.group_idx[group];
+            start += length;
+            group += 1;
+        }
+
+        Some(Place::At { stm, file, index })
+    }
+}
+
+// The index of the first three pieces when each kind of them is unique, the first in the b1-d1-d3 triangle or on the diagonal
+fn unique_triple_index(indices: &Indices, squares_of: &[usize]) -> u64 {
+    let (first, second, third) = (squares_of[0], squares_of[1], squares_of[2]);
+    let adjust_second = (second > first) as usize;
+    let adjust_third = (third > first) as usize + (third > second) as usize;
+    let index = if off_diagonal(first) != 0 {
+        (indices.map_a1d1d4[first] * 63 + second - adjust_second) * 62 + third - adjust_third
+    } else if off_diagonal(second) != 0 {
+        (6 * 63 + (first / 8) * 28 + indices.map_b1h1h7[second]) * 62 + third - adjust_third
+    } else if off_diagonal(third) != 0 {
+        6 * 63 * 62
+            + 4 * 28 * 62
+            + (first / 8) * 7 * 28
+            + (second / 8 - adjust_second) * 28
+            + indices.map_b1h1h7[third]
+    } else {
+        6 * 63 * 62
+            + 4 * 28 * 62
+            + 4 * 7 * 28
+            + (first / 8) * 7 * 6
+            + (second / 8 - adjust_second) * 6
+            + (third / 8 - adjust_third)
+    };
+    index as u64
+}
+
+// Where a position is kept in a table
+enum Place {
+    // The part of the table by side to move and leading pawn file, and the index in that part
+    At { stm: usize, file: usize, index: u64 },
+    // A DTZ table only holds positions with the other side to move
+    OtherSide,
+}
+
+// What looking a position up in a table found
+enum Lookup {
+    // The stored value, with the file of the leading pawn the table part was picked by
+    Stored(i32, usize),
+    // A DTZ table only holds positions with the other side to move
+    OtherSide,
+}
+
+// The distance to zeroing of the move before a capture or pawn move, given the result after it
+fn dtz_before_zeroing(score: i32) -> i32 {
+    match score {
+        2 => 1,
+        1 => 101,
+        -1 => -101,
+        -2 => -1,
+        _ => 0,
+    }
+}
+
+// Orders distances to zeroing from the quickest win down to the slowest loss
+fn dtz_rank(dtz: i32) -> i32 {
+    match dtz.signum() {
+        1 => 10_000 - dtz,
+        -1 => -10_000 - dtz,
+        _ => 0,
+    }
+}
+
+// The pieces one side has, written the way table names write them, such as KRB
+fn material(board: &Board, white: bool) -> String {
+    NAME_LETTERS
+        .iter()
+        .map(|(symbol, letter)| {
+            letter
+                .to_string()
+                .repeat(board.pieces_of(white, *symbol).count_ones() as usize)
+        })
+        .collect()
+}
+
+// The generator's code for a piece, 1 to 6 for a white pawn, knight, bishop, rook, queen and king, and 8 more for black
+fn piece_code(white: bool, symbol: ChessPieces) -> u8 {
+    let code = match symbol {
+        ChessPieces::Pawn => 1,
+        ChessPieces::Knight => 2,
+        ChessPieces::Bishop => 3,
+        ChessPieces::Rook => 4,
+        ChessPieces::Queen => 5,
+        ChessPieces::King => 6,
+        ChessPieces::None => 0,
+    };
+    if white {
+        code
+    } else {
+        code + 8
+    }
+}
+
+// The generator numbers squares from a1 up to h8, the other way up to our bitboards
+fn tb_square(square: usize) -> usize {
+    square ^ 56
+}
+
+// How far above the a1-h8 diagonal a square is, negative below it
+fn off_diagonal(square: usize) -> i32 {
+    (square / 8) as i32 - (square % 8) as i32
+}
+
+// Mirrors a square in the a1-h8 diagonal
+fn flip_diagonal(square: usize) -> usize {
+    ((square >> 3) | (square << 3)) & 63
+}
+
+// The lookup tables used to turn positions into indices, all in the generator's square numbering
+struct Indices {
+    // Numbers the squares below the a1-h8 diagonal from 0 to 27
+    map_b1h1h7: [usize; 64],
+    // Numbers the a1-d1-d4 triangle from 0 to 9, the squares below the diagonal first
+    map_a1d1d4: [usize; 64],
+    // Numbers the 462 ways to place both kings, by the first king's triangle number and the second king's square
+    map_kk: [[usize; 64]; 10],
+    // Numbers the squares a pawn may stand on from 47 down, so the leading pawn is the one with the highest number
+    map_pawns: [usize; 64],
+    // binomial[k][n] is how many ways there are to choose k of n things
+    binomial: [[u64; 64]; MAX_PIECES],
+    // Where the indices for each leading pawn square start, and how many each file has, by the number of leading pawns
+    lead_pawn_index: [[u64; 64]; 6],
+    lead_pawns_size: [[u64; 4]; 6],
+}
+
+impl Indices {
+    fn new() -> Self {
+        let mut indices = Indices {
+            map_b1h1h7: [0; 64],
+            map_a1d1d4: [0; 64],
+            map_kk: [[0; 64]; 10],
+            map_pawns: [0; 64],
+            binomial: [[0; 64]; MAX_PIECES],
+            lead_pawn_index: [[0; 64]; 6],
+            lead_pawns_size: [[0; 4]; 6],
+        };
+
+        let mut code = 0;
+        for square in 0..64 {
+            if off_diagonal(square) < 0 {
+                indices.map_b1h1h7[square] = code;
+                code += 1;
+            }
+        }
+
+        let mut code = 0;
+        let mut diagonal = Vec::new();
+        for square in 0..=27 {
+            if square % 8 > 3 {
+                continue;
+            }
+            if off_diagonal(square) < 0 {
+                indices.map_a1d1d4[square] = code;
+                code += 1;
+            } else if off_diagonal(square) == 0 {
+                diagonal.push(square);
+            }
+        }
+        for square in diagonal {
+            indices.map_a1d1d4[square] = code;
+            code += 1;
+        }
+
+        // Kings may not stand next to each other, and with the first on the diagonal the second may not be above it
+        // Positions with both kings on the diagonal come last
+        let mut code = 0;
+        let mut both_on_diagonal = Vec::new();
+        for triangle in 0..10 {
+            for first in 0..=27 {
+                // Every square outside the triangle maps to 0 as well, b1 is the one that really does
+                if indices.map_a1d1d4[first] != triangle || (triangle == 0 && first != 1) {
+                    continue;
+                }
+                for second in 0..64 {
+                    let touching = (first / 8).abs_diff(second / 8) <= 1
+                        && (first % 8).abs_diff(second % 8) <= 1;
+                    if touching || (off_diagonal(first) == 0 && off_diagonal(second) > 0) {
+                        continue;
+                    }
+                    if off_diagonal(first) == 0 && off_diagonal(second) == 0 {
+                        both_on_diagonal.push((triangle, second));
+                    } else {
+                        indices.map_kk[triangle][second] = code;
+                        code += 1;
+                    }
+                }
+            }
+        }
+        for (triangle, second) in both_on_diagonal {
+            indices.map_kk[triangle][second] = code;
+            code += 1;
+        }
+
+        indices.binomial[0][0] = 1;
+        for n in 1..64 {
+            for k in 0..MAX_PIECES.min(n + 1) {
+                indices.binomial[k][n] = if k > 0 {
+                    indices.binomial[k - 1][n - 1]
+                } else {
+                    0
+                } + if k < n { indices.binomial[k][n - 1] } else { 0 };
+            }
+        }
+
+        let mut available = 47;
+        for lead_count in 1..=5 {
+            for file in 0..4 {
+                let mut index = 0;
+                for rank in 1..=6 {
+                    let square = rank * 8 + file;
+                    if lead_count == 1 {
+                        indices.map_pawns[square] = available;
+                        indices.map_pawns[square ^ 7] = available - 1;
+                        available = available.saturating_sub(2);
+                    }
+                    indices.lead_pawn_index[lead_count][square] = index;
+                    index += indices.binomial[lead_count - 1][indices.map_pawns[square]];
+                }
+                indices.lead_pawns_size[lead_count][file] = index;
+            }
+        }
+        indices
+    }
+}
+
+// How one part of a table is laid out, a table has one part per side to move it holds and per leading pawn file
+#[derive(Clone, Default)]
+struct PairsData {
+    flags: u8,
+    // The pieces in the order the generator placed them, which sets how they are grouped
+    pieces: [u8; MAX_PIECES],
+    // How many pieces are in each group, ending with 0, and what each group's index is multiplied by
+    // The entry after the last group holds the number of positions in the part
+    group_len: [usize; MAX_PIECES + 1],
+    group_idx: [u64; MAX_PIECES + 1],
+    // Bytes per block of compressed data
+    block_size: usize,
+    // A sparse index entry is kept for about every span values
+    span: u64,
+    sparse_index: usize,
+    sparse_index_size: usize,
+    // How many values each block holds, minus one
+    block_lengths: usize,
+    block_length_size: usize,
+    num_blocks: usize,
+    data: usize,
+    // Huffman code lengths in bits, for a part that stores a single value the value is kept in min_sym_len
+    min_sym_len: u8,
+    max_sym_len: u8,
+    // The lowest symbol of each length, and the lowest code of each length padded to 64 bits
+    lowest_sym: usize,
+    base64: Vec<u64>,
+    // The pair of symbols each symbol stands for, 3 bytes each, and how many values minus one it stands for
+    btree: usize,
+    symlen: Vec<u8>,
+    // Where each result's DTZ values start in the table's map
+    map_idx: [usize; 4],
+}
+
+// One table file read into memory, all positions are offsets into its bytes
+struct Table {
+    bytes: Vec<u8>,
+    dtz: bool,
+    // The pieces of the side named first, which is white in the positions the table holds
+    first_side: String,
+    // Whether both sides have the same pieces
+    symmetric: bool,
+    piece_count: usize,
+    has_pawns: bool,
+    // Whether any piece other than a king is the only one of its kind and colour
+    has_unique_pieces: bool,
+    // The pawns of the leading colour, the side with fewer of them that has any, then of the other
+    pawn_count: [usize; 2],
+    // The table's parts by side to move and leading pawn file
+    pairs: Vec<Vec<PairsData>>,
+    // Where a DTZ table's value maps start
+    map: usize,
+}
+
+impl Table {
+    // Reads a table's layout from its file, None when the file isn't a table for the named material
+    fn parse(name: &str, bytes: Vec<u8>, dtz: bool, indices: &Indices) -> Option<Self> {
+        let (first_side, second_side) = name.split_once('v')?;
+        let magic = if dtz { DTZ_MAGIC } else { WDL_MAGIC };
+        if bytes.get(..4)? != magic {
+            return None;
+        }
+        let piece_count = first_side.len() + second_side.len();
+        if piece_count > MAX_PIECES {
+            return None;
+        }
+        let count = |side: &str, letter: char| side.chars().filter(|char| *char == letter).count();
+        let pawns = [count(first_side, 'P'), count(second_side, 'P')];
+        let white_leads = pawns[1] == 0 || (pawns[0] > 0 && pawns[1] >= pawns[0]);
+
+        let mut table = Table {
+            bytes,
+            dtz,
+            first_side: first_side.to_string(),
+            symmetric: first_side == second_side,
+            piece_count,
+            has_pawns: pawns[0] + pawns[1] > 0,
+            has_unique_pieces: [first_side, second_side]
+                .iter()
+                .any(|side| "QRBNP".chars().any(|letter| count(side, letter) == 1)),
+            pawn_count: if white_leads {
+                pawns
+            } else {
+                [pawns[1], pawns[0]]
+            },
+            pairs: Vec::new(),
+            map: 0,
+        };
+        let (pairs, map) = table.layout(indices)?;
+        table.pairs = pairs;
+        table.map = map;
+        Some(table)
+    }
+
+    // The part of the table for the side to move, after any colour swap, and the leading pawn's file
+    fn pairs(&self, stm: usize, file: usize) -> &PairsData {
+        let file = if self.has_pawns { file } else { 0 };
+        &self.pairs[stm % self.pairs.len()][file]
+    }
+
+    // Reads the header of every part of the table, then where each part's indices and data are
+    fn layout(&self, indices: &Indices) -> Option<(Vec<Vec<PairsData>>, usize)> {
+        let bytes = &self.bytes;
+        let flags = *bytes.get(4)?;
+        if (flags & 2 != 0) != self.has_pawns || (flags & 1 != 0) == self.symmetric {
+            return None;
+        }
+
+        let sides = if !self.dtz && !self.symmetric { 2 } else { 1 };
+        let files = if self.has_pawns { 4 } else { 1 };
+        let pawns_on_both_sides = self.has_pawns && self.pawn_count[1] > 0;
+        let mut pairs = vec![vec![PairsData::default(); files]; sides];
+
+        let mut at = 5;
+        for file in 0..files {
+            let first = *bytes.get(at)?;
+            let second = if pawns_on_both_sides {
+                *bytes.get(at + 1)?
+            } else {
+                0xff
+            };
+            let order = [[first & 0xf, second & 0xf], [first >> 4, second >> 4]];
+            at += 1 + pawns_on_both_sides as usize;
+            for k in 0..self.piece_count {
+                let byte = *bytes.get(at)?;
+                for (side, side_pairs) in pairs.iter_mut().enumerate() {
+                    side_pairs[file].pieces[k] = if side == 1 { byte >> 4 } else { byte & 0xf };
+                }
+                at += 1;
+            }
+            for side in 0..sides {
+                self.set_groups(&mut pairs[side][file], order[side], file, indices);
+            }
+        }
+        at += at & 1;
+
+        for file in 0..files {
+            for side_pairs in pairs.iter_mut() {
+                at = self.set_sizes(&mut side_pairs[file], at)?;
+            }
+        }
+
+        let map = at;
+        if self.dtz {
+            for file in 0..files {
+                let pairs = &mut pairs[0][file];
+                if pairs.flags & FLAG_MAPPED == 0 {
+                    continue;
+                }
+                if pairs.flags & FLAG_WIDE != 0 {
+                    at += at & 1;
+                    for result in 0..4 {
+                        pairs.map_idx[result] = (at - map) / 2 + 1;
+                        at += 2 * read_u16(bytes, at)? as usize + 2;
+                    }
+                } else {
+                    for result in 0..4 {
+                        pairs.map_idx[result] = at - map + 1;
+                        at += *bytes.get(at)? as usize + 1;
+                    }
+                }
+            }
+            at += at & 1;
+        }
+
+        for file in 0..files {
+            for side_pairs in pairs.iter_mut() {
+                side_pairs[file].sparse_index = at;
+                at += side_pairs[file].sparse_index_size * 6;
+            }
+        }
+        for file in 0..files {
+            for side_pairs in pairs.iter_mut() {
+                side_pairs[file].block_lengths = at;
+                at += side_pairs[file].block_length_size * 2;
+            }
+        }
+        for file in 0..files {
+            for side_pairs in pairs.iter_mut() {
+                at = (at + 0x3f) & !0x3f;
+                side_pairs[file].data = at;
+                at += side_pairs[file].num_blocks * side_pairs[file].block_size;
+            }
+        }
+        // A file whose parts all store a single value ends before the alignment of the data that isn't there
+        let complete = pairs.iter().flatten().all(|part| {
+            part.num_blocks == 0 || part.data + part.num_blocks * part.block_size <= bytes.len()
+        });
+        complete.then_some((pairs, map))
+    }
+
+    // Splits the pieces into the groups the generator encoded together and works out each group's multiplier
+    // The leading group is the leading pawns, or without pawns three unique pieces or else the two kings,
+    // and every other group is the pieces of one kind and colour; the order byte says which order the groups were encoded in
+    fn set_groups(&self, pairs: &mut PairsData, order: [u8; 2], file: usize, indices: &Indices) {
+        let mut first_length: i32 = if self.has_pawns {
+            0
+        } else if self.has_unique_pieces {
+            3
+        } else {
+            2
+        };
+        let mut groups = 0;
+        pairs.group_len[0] = 1;
+        for i in 1..self.piece_count {
+            first_length -= 1;
+            if first_length > 0 || pairs.pieces[i] == pairs.pieces[i - 1] {
+                pairs.group_len[groups] += 1;
+            } else {
+                groups += 1;
+                pairs.group_len[groups] = 1;
+            }
+        }
+        groups += 1;
+        pairs.group_len[groups] = 0;
+
+        let pawns_on_both_sides = self.has_pawns && self.pawn_count[1] > 0;
+        let mut next = if pawns_on_both_sides { 2 } else { 1 };
+        let mut free_squares = 64
+            - pairs.group_len[0]
+            - if pawns_on_both_sides {
+                pairs.group_len[1]
+            } else {
+                0
+            };
+        let mut index: u64 = 1;
+        let mut k = 0;
+        while next < groups || k == order[0] || k == order[1] {
+            if k == order[0] {
+                pairs.group_idx[0] = index;
+                index *= if self.has_pawns {
+                    indices.lead_pawns_size[pairs.group_len[0]][file]
+                } else if self.has_unique_pieces {
+                    UNIQUE_TRIPLES
+                } else {
+                    KING_PAIRS
+                };
+            } else if k == order[1] {
+                pairs.group_idx[1] = index;
+                index *= indices.binomial[pairs.group_len[1]][48 - pairs.group_len[0]];
+            } else {
+                pairs.group_idx[next] = index;
+                index *= indices.binomial[pairs.group_len[next]][free_squares];
+                free_squares -= pairs.group_len[next];
+                next += 1;
+            }
+            k += 1;
+        }
+        pairs.group_idx[groups] = index;
+    }
+
+    // Reads a part's block sizes and Huffman code from the header, returning where the next part's header starts
+    fn set_sizes(&self, pairs: &mut PairsData, mut at: usize) -> Option<usize> {
+        let bytes = &self.bytes;
+        pairs.flags = *bytes.get(at)?;
+        at += 1;
+        if pairs.flags & FLAG_SINGLE_VALUE != 0 {
+            pairs.min_sym_len = *bytes.get(at)?;
+            return Some(at + 1);
+        }
+
+        let groups = pairs.group_len.iter().position(|length| *length == 0)?;
+        let positions = pairs.group_idx[groups];
+        let block_bits = *bytes.get(at)?;
+        let span_bits = *bytes.get(at + 1)?;
+        if block_bits > 31 || span_bits > 63 {
+            return None;
+        }
+        pairs.block_size = 1 << block_bits;
+        pairs.span = 1 << span_bits;
+        pairs.sparse_index_size = positions.div_ceil(pairs.span) as usize;
+        let padding = *bytes.get(at + 2)? as usize;
+        pairs.num_blocks = read_u32(bytes, at + 3)? as usize;
+        pairs.block_length_size = pairs.num_blocks + padding;
+        pairs.max_sym_len = *bytes.get(at + 7)?;
+        pairs.min_sym_len = *bytes.get(at + 8)?;
+        at += 9;
+        if pairs.min_sym_len == 0 || pairs.max_sym_len < pairs.min_sym_len || pairs.max_sym_len > 32
+        {
+            return None;
+        }
+
+        // Longer codes have lower values, so base64 runs downwards and a code's length is found by walking it
+        pairs.lowest_sym = at;
+        let lengths = (pairs.max_sym_len - pairs.min_sym_len + 1) as usize;
+        pairs.base64 = vec![0; lengths];
+        for i in (0..lengths - 1).rev() {
+            let lowest = read_u16(bytes, at + 2 * i)? as u64;
+            let next_lowest = read_u16(bytes, at + 2 * i + 2)? as u64;
+            pairs.base64[i] = (pairs.base64[i + 1] + lowest).wrapping_sub(next_lowest) / 2;
+        }
+        for (i, base) in pairs.base64.iter_mut().enumerate() {
+            *base <<= 64 - i - pairs.min_sym_len as usize;
+        }
+        at += lengths * 2;
+
+        // Each symbol stands for a pair of symbols, down to symbols that stand for a single value
+        let symbols = read_u16(bytes, at)? as usize;
+        at += 2;
+        pairs.btree = at;
+        if bytes.len() < at + symbols * 3 {
+            return None;
+        }
+        pairs.symlen = vec![0; symbols];
+        let mut visited = vec![false; symbols];
+        for symbol in 0..symbols {
+            if !visited[symbol] {
+                pairs.symlen[symbol] = self.set_symlen(pairs, symbol, &mut visited)?;
+            }
+        }
+        Some(at + symbols * 3 + (symbols & 1))
+    }
+
+    // Counts how many values minus one a symbol stands for, counting its pair of symbols first
+    fn set_symlen(&self, pairs: &mut PairsData, symbol: usize, visited: &mut [bool]) -> Option<u8> {
+        visited[symbol] = true;
+        let (left, right) = self.symbol_pair(pairs, symbol)?;
+        if right == 0xfff {
+            return Some(0);
+        }
+        for child in [left, right] {
+            if child >= visited.len() {
+                return None;
+            }
+            if !visited[child] {
+                pairs.symlen[child] = self.set_symlen(pairs, child, visited)?;
+            }
+        }
+        Some(
+            pairs.symlen[left]
+                .wrapping_add(pairs.symlen[right])
+                .wrapping_add(1),
+        )
+    }
+
+    // The two symbols a symbol stands for, 12 bits each; the left one is the value itself for a symbol standing for one
+    fn symbol_pair(&self, pairs: &PairsData, symbol: usize) -> Option<(usize, usize)> {
+        let at = pairs.btree + 3 * symbol;
+        let entry = self.bytes.get(at..at + 3)?;
+        let left = ((entry[1] as usize & 0xf) << 8) | entry[0] as usize;
+        let right = ((entry[2] as usize) << 4) | (entry[1] as usize >> 4);
+        Some((left, right))
+    }
+
+    // Reads the value stored at the index in a part of the table
+    fn decompress(&self, pairs: &PairsData, index: u64) -> Option<i32> {
+        if pairs.flags & FLAG_SINGLE_VALUE != 0 {
+            return Some(pairs.min_sym_len as i32);
+        }
+        let bytes = &self.bytes;
+
+        // The sparse index gives the block and offset of the value in the middle of each span, step from there to our value
+        let k = (index / pairs.span) as usize;
+        if k >= pairs.sparse_index_size {
+            return None;
+        }
+        let entry = pairs.sparse_index + 6 * k;
+        let mut block = read_u32(bytes, entry)? as usize;
+        let mut offset = read_u16(bytes, entry + 4)? as i64;
+        offset += (index % pairs.span) as i64 - (pairs.span / 2) as i64;
+        let block_length = |block: usize| -> Option<i64> {
+            if block >= pairs.block_length_size {
+                return None;
+            }
+            Some(read_u16(bytes, pairs.block_lengths + 2 * block)? as i64)
+        };
+        while offset < 0 {
+            block = block.checked_sub(1)?;
+            offset += block_length(block)? + 1;
+        }
+        while offset > block_length(block)? {
+            offset -= block_length(block)? + 1;
+            block += 1;
+        }
+
+        // Walk the block's symbols until reaching the one whose run of values holds ours
+        let mut at = pairs.data + block * pairs.block_size;
+        let mut buffer = read_u64_be(bytes, at)?;
+        at += 8;
+        let mut buffer_bits = 64;
+        let min_sym_len = pairs.min_sym_len as usize;
+        let mut symbol;
+        loop {
+            let mut length = 0;
+            while buffer < pairs.base64[length] {
+                length += 1;
+                if length == pairs.base64.len() {
+                    return None;
+                }
+            }
+            let code = (buffer - pairs.base64[length]) >> (64 - length - min_sym_len);
+            symbol =
+                (code as usize + read_u16(bytes, pairs.lowest_sym + 2 * length)? as usize) & 0xffff;
+            let run = *pairs.symlen.get(symbol)? as i64 + 1;
+            if offset < run {
+                break;
+            }
+            offset -= run;
+            let bits = length + min_sym_len;
+            buffer <<= bits;
+            buffer_bits -= bits;
+            if buffer_bits <= 32 {
+                buffer_bits += 32;
+                buffer |= (read_u32_be(bytes, at)? as u64) << (64 - buffer_bits);
+                at += 4;
+            }
+        }
+
+        // Then split the symbol into its pair, and that into its pairs, until reaching a single value
+        while *pairs.symlen.get(symbol)? != 0 {
+            let (left, right) = self.symbol_pair(pairs, symbol)?;
+            let left_run = *pairs.symlen.get(left)? as i64 + 1;
+            if offset < left_run {
+                symbol = left;
+            } else {
+                offset -= left_run;
+                symbol = right;
+            }
+        }
+        Some(self.symbol_pair(pairs, symbol)?.0 as i32)
+    }
+
+    // Turns a value read from a DTZ table into plies, for a position with the given score
+    // The values of each result are stored by how often they appear, and the map turns them back into distances
+    fn map_dtz(&self, value: i32, file: usize, score: i32) -> Option<i32> {
+        const RESULT_MAP: [usize; 5] = [1, 3, 0, 2, 0];
+        let pairs = self.pairs(0, file);
+        let mut value = value;
+        if pairs.flags & FLAG_MAPPED != 0 {
+            let index = pairs.map_idx[RESULT_MAP[(score + 2) as usize]] + value as usize;
+            value = if pairs.flags & FLAG_WIDE != 0 {
+                read_u16(&self.bytes, self.map + 2 * index)? as i32
+            } else {
+                *self.bytes.get(self.map + index)? as i32
+            };
+        }
+        let in_plies = match score {
+            2 => pairs.flags & FLAG_WIN_PLIES != 0,
+            -2 => pairs.flags & FLAG_LOSS_PLIES != 0,
+            _ => false,
+        };
+        if !in_plies {
+            value *= 2;
+        }
+        Some(value + 1)
+    }
+}
+
+// Headers are little endian
+fn read_u16(bytes: &[u8], at: usize) -> Option<u16> {
+    Some(u16::from_le_bytes(
+        bytes.get(at..at + 2)?.try_into().unwrap(),
+    ))
+}
+
+fn read_u32(bytes: &[u8], at: usize) -> Option<u32> {
+    Some(u32::from_le_bytes(
+        bytes.get(at..at + 4)?.try_into().unwrap(),
+    ))
+}
+
+// The compressed data is read big endian
+fn read_u32_be(bytes: &[u8], at: usize) -> Option<u32> {
+    Some(u32::from_be_bytes(
+        bytes.get(at..at + 4)?.try_into().unwrap(),
+    ))
+}
+
+fn read_u64_be(bytes: &[u8], at: usize) -> Option<u64> {
+    Some(u64::from_be_bytes(
+        bytes.get(at..at + 8)?.try_into().unwrap(),
+    ))
+}
+
+#[derive(Debug)]
+pub enum TablebaseError {
+    Io(std::io::Error),
+    // The directory that has no .rtbw or .rtbz files in it
+    NoTables(String),
+}
+
+impl std::fmt::Display for TablebaseError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            TablebaseError::Io(error) => {
+                write!(
+                    f,
+                    "Tablebase error: could not read the directory: {}",
+                    error
+                )
+            }
+            TablebaseError::NoTables(directory) => {
+                write!(f, "Tablebase error: no Syzygy tables in {}", directory)
+            }
+        }
+    }
+}
+
+impl std::error::Error for TablebaseError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        None
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::endgame::{Distance, EndgameTables};
+
+    // The header of a KQvK table up to its first part: the flags, the group order and the pieces, white king, queen and black king
+    fn header(magic: [u8; 4]) -> Vec<u8> {
+        let mut bytes = magic.to_vec();
+        bytes.extend([0x01, 0x00, 0x66, 0x55, 0xee, 0x00]);
+        bytes
+    }
+
+    // A directory of table files, named after the test so tests running at once don't share one
+    fn table_directory(test: &str, files: &[(&str, Vec<u8>)]) -> String {
+        let directory = std::env::temp_dir().join(format!(
+            "terminal_chess_syzygy_{}_{}",
+            test,
+            std::process::id()
+        ));
+        std::fs::create_dir_all(&directory).unwrap();
+        for (name, bytes) in files {
+            std::fs::write(directory.join(name), bytes).unwrap();
+        }
+        directory.to_string_lossy().to_string()
+    }
+
+    // KQvK with every position won for white to move and lost for black to move, and 5 moves to zeroing for white to move
+    fn single_value_tables(test: &str) -> Tablebase {
+        let mut wdl = header(WDL_MAGIC);
+        wdl.extend([FLAG_SINGLE_VALUE, 4, FLAG_SINGLE_VALUE, 0]);
+        let mut dtz = header(DTZ_MAGIC);
+        dtz.extend([FLAG_SINGLE_VALUE, 5]);
+        let directory = table_directory(test, &[("KQvK.rtbw", wdl), ("KQvK.rtbz", dtz)]);
+        Tablebase::open(&directory).unwrap()
+    }
+
+    fn board(fen: &str) -> Board {
+        Board::from_fen(fen).unwrap()
+    }
+
+    // Where the KQvK fixture tables are kept
+    const FIXTURES: &str = concat!(env!("CARGO_MANIFEST_DIR"), "/tests/fixtures/syzygy");
+
+    fn kqk_endgame() -> EndgameTables {
+        let mut endgames = EndgameTables::default();
+        endgames.generate("KQK").unwrap();
+        endgames
+    }
+
+    // The KQvK position numbered by the white king's square, then the black king's and then the queen's, None when it can't happen
+    fn kqk_board(placement: usize, white_move: bool, white_queen: bool) -> Option<Board> {
+        let pieces = [
+            (placement % 64, ChessPieces::King, true),
+            (placement / 64 % 64, ChessPieces::King, false),
+            (placement / 4096, ChessPieces::Queen, white_queen),
+        ];
+        let taken =
+            pieces[0].0 == pieces[1].0 || pieces[2].0 == pieces[0].0 || pieces[2].0 == pieces[1].0;
+        if taken {
+            return None;
+        }
+        Board::from_pieces(&pieces, white_move).ok()
+    }
+
+    // Gives the indices no position reaches the most common value, which is what compresses best
+    fn fill_unreachable<T: Copy + Ord + Default>(values: &[Option<T>]) -> Vec<T> {
+        let mut counts = std::collections::BTreeMap::new();
+        for value in values.iter().flatten() {
+            *counts.entry(*value).or_insert(0) += 1;
+        }
+        let common = counts
+            .iter()
+            .max_by_key(|(_, count)| **count)
+            .map(|(value, _)| *value)
+            .unwrap_or_default();
+        values.iter().map(|value| value.unwrap_or(common)).collect()
+    }
+
+    // Huffman code lengths for symbols seen the given number of times, by merging the two rarest nodes until one is left
+    fn code_lengths(counts: &[usize]) -> Vec<u8> {
+        let mut lengths = vec![0u8; counts.len()];
+        let mut nodes: Vec<(usize, Vec<usize>)> = counts
+            .iter()
+            .enumerate()
+            .map(|(symbol, count)| (*count, vec![symbol]))
+            .collect();
+        while nodes.len() > 1 {
+            nodes.sort_by_key(|(count, _)| std::cmp::Reverse(*count));
+            let (first_count, first) = nodes.pop().unwrap();
+            let (second_count, second) = nodes.pop().unwrap();
+            for symbol in first.iter().chain(&second) {
+                lengths[*symbol] += 1;
+            }
+            nodes.push((first_count + second_count, [first, second].concat()));
+        }
+        lengths.iter().map(|length| (*length).max(1)).collect()
+    }
+
+    // One part of a table file: its sizes and Huffman code for the header, its sparse index, block lengths and blocks
+    struct Part {
+        sizes: Vec<u8>,
+        sparse_index: Vec<u8>,
+        block_lengths: Vec<u8>,
+        blocks: Vec<u8>,
+    }
+
+    // Huffman codes the values into blocks of 256 bytes, with a symbol for each value and a sparse index entry every 1024 values
+    // Symbols are numbered from the longest code to the shortest, and longer codes take the lower values
+    fn compress(values: &[u16], flags: u8) -> Part {
+        const BLOCK_BITS: u8 = 8;
+        const SPAN_BITS: u8 = 10;
+        let mut distinct = values.to_vec();
+        distinct.sort_unstable();
+        distinct.dedup();
+        let counts: Vec<usize> = distinct
+            .iter()
+            .map(|value| values.iter().filter(|other| *other == value).count())
+            .collect();
+        let lengths = code_lengths(&counts);
+        let mut symbols: Vec<usize> = (0..distinct.len()).collect();
+        symbols.sort_by_key(|symbol| std::cmp::Reverse(lengths[*symbol]));
+        let min_length = *lengths.iter().min().unwrap();
+        let max_length = *lengths.iter().max().unwrap();
+
+        // The lowest symbol and the lowest code of each length, from the shortest
+        let lengths_used = (max_length - min_length + 1) as usize;
+        let mut lowest_symbol = vec![0; lengths_used];
+        let mut lowest_code = vec![0u64; lengths_used];
+        let mut next_symbol = 0;
+        let mut next_code = 0;
+        for i in (0..lengths_used).rev() {
+            let count = symbols
+                .iter()
+                .filter(|symbol| lengths[**symbol] as usize == min_length as usize + i)
+                .count();
+            lowest_symbol[i] = next_symbol;
+            lowest_code[i] = next_code;
+            next_symbol += count;
+            // A complete code leaves an even number of codes of each length but the shortest
+            let codes = next_code + count as u64;
+            assert!(codes.is_multiple_of(2) || i == 0);
+            next_code = codes / 2;
+        }
+        let code = |value: &u16| {
+            let symbol = symbols
+                .iter()
+                .position(|symbol| distinct[*symbol] == *value)
+                .unwrap();
+            let i = (lengths[symbols[symbol]] - min_length) as usize;
+            let code = lowest_code[i] + (symbol - lowest_symbol[i]) as u64;
+            (code, lengths[symbols[symbol]] as usize)
+        };
+
+        // Fill each block, leaving room for the 64 bits the reader fetches past a block's last code
+        let block_size = 1 << BLOCK_BITS;
+        let mut blocks = Vec::new();
+        let mut block_starts = vec![0];
+        let mut bits = Vec::new();
+        for (index, value) in values.iter().enumerate() {
+            let (code, length) = code(value);
+            if bits.len() + length > block_size * 8 - 64 {
+                block_starts.push(index);
+                blocks.push(std::mem::take(&mut bits));
+            }
+            bits.extend((0..length).rev().map(|bit| code >> bit & 1 == 1));
+        }
+        blocks.push(bits);
+
+        let mut sizes = vec![flags, BLOCK_BITS, SPAN_BITS, 0];
+        sizes.extend((blocks.len() as u32).to_le_bytes());
+        sizes.extend([max_length, min_length]);
+        for symbol in &lowest_symbol {
+            sizes.extend((*symbol as u16).to_le_bytes());
+        }
+        sizes.extend((symbols.len() as u16).to_le_bytes());
+        for symbol in &symbols {
+            let value = distinct[*symbol];
+            sizes.extend([value as u8, (value >> 8) as u8 | 0xf0, 0xff]);
+        }
+        if symbols.len() % 2 == 1 {
+            sizes.push(0);
+        }
+
+        let span = 1 << SPAN_BITS;
+        let mut sparse_index = Vec::new();
+        for k in 0..values.len().div_ceil(span) {
+            let middle = k * span + span / 2;
+            let block = block_starts.partition_point(|start| *start <= middle) - 1;
+            sparse_index.extend((block as u32).to_le_bytes());
+            sparse_index.extend(((middle - block_starts[block]) as u16).to_le_bytes());
+        }
+        let mut block_lengths = Vec::new();
+        block_starts.push(values.len());
+        for block in block_starts.windows(2) {
+            block_lengths.extend(((block[1] - block[0] - 1) as u16).to_le_bytes());
+        }
+        let mut bytes = Vec::new();
+        for bits in blocks {
+            let mut block = vec![0u8; block_size];
+            for (i, bit) in bits.iter().enumerate() {
+                block[i / 8] |= (*bit as u8) << (7 - i % 8);
+            }
+            bytes.extend(block);
+        }
+        Part {
+            sizes,
+            sparse_index,
+            block_lengths,
+            blocks: bytes,
+        }
+    }
+
+    // Lays out a KQvK table file: the header, every part's sizes, a DTZ table's map, then the parts' indices and blocks
+    fn table_file(magic: [u8; 4], parts: &[Part], map: &[u8]) -> Vec<u8> {
+        let mut bytes = header(magic);
+        for part in parts {
+            bytes.extend(&part.sizes);
+        }
+        bytes.extend(map);
+        if magic == DTZ_MAGIC {
+            bytes.resize(bytes.len().next_multiple_of(2), 0);
+        }
+        for part in parts {
+            bytes.extend(&part.sparse_index);
+        }
+        for part in parts {
+            bytes.extend(&part.block_lengths);
+        }
+        for part in parts {
+            bytes.resize(bytes.len().next_multiple_of(64), 0);
+            bytes.extend(&part.blocks);
+        }
+        bytes
+    }
+
+    #[test]
+    fn indices_number_every_king_placement() {
+        let indices = Indices::new();
+        let codes: HashSet<usize> = indices.map_kk.iter().flatten().copied().collect();
+        assert_eq!(codes, (0..KING_PAIRS as usize).collect());
+        assert_eq!(indices.binomial[2][5], 10);
+        // a2 leads every other pawn, and one leading pawn has six ranks to stand on in each file
+        assert_eq!(indices.map_pawns[8], 47);
+        assert_eq!(indices.lead_pawns_size[1], [6; 4]);
+    }
+
+    #[test]
+    fn probes_win_draw_and_loss() {
+        let tablebase = single_value_tables("wdl");
+        assert_eq!(
+            tablebase.probe_wdl(&board("8/8/8/8/8/2k5/8/KQ6 w - - 0 1")),
+            Some(Wdl::Win)
+        );
+        assert_eq!(
+            tablebase.probe_wdl(&board("8/8/8/8/8/2k5/8/KQ6 b - - 0 1")),
+            Some(Wdl::Loss)
+        );
+        // Black to move takes the queen
+        assert_eq!(
+            tablebase.probe_wdl(&board("8/8/8/8/8/2kQ4/8/K7 b - - 0 1")),
+            Some(Wdl::Draw)
+        );
+        // With black holding the queen the colours are swapped
+        assert_eq!(
+            tablebase.probe_wdl(&board("kq6/8/8/8/8/8/8/7K w - - 0 1")),
+            Some(Wdl::Loss)
+        );
+        assert_eq!(
+            tablebase.probe_wdl(&board("4k3/8/8/8/8/8/8/4K3 w - - 0 1")),
+            Some(Wdl::Draw)
+        );
+        assert_eq!(
+            tablebase.probe_wdl(&board("8/8/8/8/8/2k5/8/KR6 w - - 0 1")),
+            None
+        );
+        assert_eq!(
+            tablebase.probe_wdl(&board("4k3/8/8/8/8/8/8/4K2R w K - 0 1")),
+            None
+        );
+    }
+
+    #[test]
+    fn probes_distance_to_zeroing() {
+        let tablebase = single_value_tables("dtz");
+        assert_eq!(
+            tablebase.probe_dtz(&board("8/8/8/8/8/2k5/8/KQ6 w - - 0 1")),
+            Some(11)
+        );
+        // The table only holds white to move, so black's distance is found a move ahead
+        assert_eq!(
+            tablebase.probe_dtz(&board("8/8/8/8/8/2k5/8/KQ6 b - - 0 1")),
+            Some(-12)
+        );
+        assert_eq!(
+            tablebase.probe_dtz(&board("8/8/8/8/8/2kQ4/8/K7 b - - 0 1")),
+            Some(0)
+        );
+    }
+
+    #[test]
+    fn best_move_mates_when_it_can() {
+        let tablebase = single_value_tables("best_move");
+        let mut board = board("k7/8/1K6/8/8/8/8/6Q1 w - - 0 1");
+        let (chess_move, dtz) = tablebase.best_move(&board).unwrap();
+        assert_eq!(dtz, 1);
+        board.play_move(chess_move);
+        assert_eq!(board.game_state(), crate::chess::GameState::Checkmate);
+    }
+
+    // KQvK results, with either side to move and either colour holding the queen, agree with the ending worked out from scratch
+    // The tables in tests/fixtures/syzygy are written by writes_the_fixture_tables, and the ones from the Syzygy set can stand in for them
+    #[test]
+    fn probes_the_fixture_tables() {
+        let tablebase = Tablebase::open(FIXTURES).unwrap();
+        let wdl = tablebase.table("KQvK", false).unwrap();
+        let dtz = tablebase.table("KQvK", true).unwrap();
+        assert!(wdl.pairs.iter().flatten().all(|part| part.num_blocks > 1));
+        assert!(dtz.pairs[0][0].num_blocks > 1);
+        assert_ne!(dtz.pairs[0][0].flags & FLAG_MAPPED, 0);
+
+        let mut mating = board("k7/8/1K6/8/8/8/8/6Q1 w - - 0 1");
+        assert_eq!(tablebase.probe_wdl(&mating), Some(Wdl::Win));
+        assert_eq!(tablebase.probe_dtz(&mating), Some(1));
+        let (chess_move, dtz) = tablebase.best_move(&mating).unwrap();
+        assert_eq!(dtz, 1);
+        mating.play_move(chess_move);
+        assert_eq!(mating.game_state(), crate::chess::GameState::Checkmate);
+
+        let stalemate = board("k7/2Q5/1K6/8/8/8/8/8 b - - 0 1");
+        assert_eq!(tablebase.probe_wdl(&stalemate), Some(Wdl::Draw));
+        assert_eq!(tablebase.probe_dtz(&stalemate), Some(0));
+        let mated = board("k7/1Q6/1K6/8/8/8/8/8 b - - 0 1");
+        assert_eq!(tablebase.probe_wdl(&mated), Some(Wdl::Loss));
+
+        let endgames = kqk_endgame();
+        for placement in (0..64 * 64 * 64).step_by(61) {
+            for (white_move, white_queen) in
+                [(true, true), (false, true), (true, false), (false, false)]
+            {
+                let Some(board) = kqk_board(placement, white_move, white_queen) else {
+                    continue;
+                };
+                let (expected_wdl, expected_dtz) = match endgames.probe(&board).unwrap() {
+                    Distance::Win(plies) => (Wdl::Win, plies as i32),
+                    // A side that has been mated is one ply from the mate the table holds
+                    Distance::Loss(plies) => (Wdl::Loss, -(plies.max(1) as i32)),
+                    Distance::Draw => (Wdl::Draw, 0),
+                };
+                assert_eq!(
+                    tablebase.probe_wdl(&board),
+                    Some(expected_wdl),
+                    "{}",
+                    board.to_fen()
+                );
+                assert_eq!(
+                    tablebase.probe_dtz(&board),
+                    Some(expected_dtz),
+                    "{}",
+                    board.to_fen()
+                );
+            }
+        }
+    }
+
+    // Rebuilds the KQvK fixture tables from the ending worked out by the endgame module, laid out the way the Syzygy generator does it:
+    // Huffman coded blocks of 256 bytes for both sides to move in the WDL table, and white to move in the DTZ table with a map of its distances
+    #[test]
+    #[ignore = "rewrites the tables in tests/fixtures/syzygy"]
+    fn writes_the_fixture_tables() {
+        let tablebase = single_value_tables("fixture");
+        let table = tablebase.table("KQvK", false).unwrap();
+        let endgames = kqk_endgame();
+        let positions = UNIQUE_TRIPLES as usize;
+        let mut wdl = vec![vec![None; positions]; 2];
+        let mut plies = vec![None; positions];
+        for placement in 0..64 * 64 * 64 {
+            for white_move in [true, false] {
+                let Some(board) = kqk_board(placement, white_move, true) else {
+                    continue;
+                };
+                let Some(Place::At { stm, index, .. }) = tablebase.place(&table, &board) else {
+                    panic!("{} has no place in the table", board.to_fen());
+                };
+                let index = index as usize;
+                let distance = endgames.probe(&board).unwrap();
+                let value: u16 = match distance {
+                    Distance::Win(_) => 4,
+                    Distance::Loss(_) => 0,
+                    Distance::Draw => 2,
+                };
+                // Positions the board's symmetries turn into each other share an index
+                assert!(wdl[stm][index].is_none_or(|stored| stored == value));
+                wdl[stm][index] = Some(value);
+                if let (0, Distance::Win(win)) = (stm, distance) {
+                    plies[index] = Some(win - 1);
+                }
+            }
+        }
+
+        // Each value a win's distance can take is stored as its place in the map, the most common first
+        let mut map: Vec<u32> = plies.iter().flatten().copied().collect();
+        map.sort_unstable();
+        map.dedup();
+        map.sort_by_key(|value| {
+            std::cmp::Reverse(plies.iter().filter(|other| **other == Some(*value)).count())
+        });
+        let mapped: Vec<Option<u16>> = plies
+            .iter()
+            .map(|value| {
+                value.map(|value| map.iter().position(|other| *other == value).unwrap() as u16)
+            })
+            .collect();
+        let mut dtz_map = vec![map.len() as u8];
+        dtz_map.extend(map.iter().map(|value| *value as u8));
+        dtz_map.extend([0, 0, 0]);
+
+        let wdl = table_file(
+            WDL_MAGIC,
+            &[
+                compress(&fill_unreachable(&wdl[0]), 0),
+                compress(&fill_unreachable(&wdl[1]), 0),
+            ],
+            &[],
+        );
+        let dtz = table_file(
+            DTZ_MAGIC,
+            &[compress(
+                &fill_unreachable(&mapped),
+                FLAG_MAPPED | FLAG_WIN_PLIES,
+            )],
+            &dtz_map,
+        );
+        std::fs::create_dir_all(FIXTURES).unwrap();
+        std::fs::write(format!("{}/KQvK.rtbw", FIXTURES), wdl).unwrap();
+        std::fs::write(format!("{}/KQvK.rtbz", FIXTURES), dtz).unwrap();
+    }
+
+    #[test]
+    fn decompresses_huffman_coded_blocks() {
+        // One bit per position, 1 for a loss and 0 for a win, in blocks of 3000 positions
+        let positions = UNIQUE_TRIPLES as usize;
+        let per_block = 3000;
+        let blocks = positions.div_ceil(per_block);
+        let lost = |index: usize| index.is_multiple_of(3) || index % 7 == 5;
+
+        let mut bytes = header(WDL_MAGIC);
+        bytes.extend([0, 9, 11, 0]);
+        bytes.extend((blocks as u32).to_le_bytes());
+        // One length of code, 1 bit, its lowest symbol 0 and two symbols that each stand for a value
+        bytes.extend([1, 1, 0, 0, 2, 0]);
+        bytes.extend([4, 0xf0, 0xff, 0, 0xf0, 0xff]);
+        bytes.extend([FLAG_SINGLE_VALUE, 0]);
+        for k in 0..positions.div_ceil(1 << 11) {
+            let middle = k * (1 << 11) + (1 << 10);
+            bytes.extend(((middle / per_block) as u32).to_le_bytes());
+            bytes.extend(((middle % per_block) as u16).to_le_bytes());
+        }
+        for block in 0..blocks {
+            let length = per_block.min(positions - block * per_block);
+            bytes.extend(((length - 1) as u16).to_le_bytes());
+        }
+        bytes.resize(bytes.len().next_multiple_of(64), 0);
+        for block in 0..blocks {
+            let mut data = vec![0u8; 512];
+            for offset in 0..per_block.min(positions - block * per_block) {
+                if lost(block * per_block + offset) {
+                    data[offset / 8] |= 0x80 >> (offset % 8);
+                }
+            }
+            bytes.extend(data);
+        }
+
+        let table = Table::parse("KQvK", bytes, false, &Indices::new()).unwrap();
+        for index in [
+            0, 1, 2, 5, 1023, 1024, 2999, 3000, 3001, 6143, 20_000, 31_331,
+        ] {
+            let expected = if lost(index) { 0 } else { 4 };
+            assert_eq!(
+                table.decompress(&table.pairs[0][0], index as u64),
+                Some(expected),
+                "position {}",
+                index
+            );
+        }
+        assert_eq!(table.decompress(&table.pairs[1][0], 12), Some(0));
+    }
+
+    #[test]
+    fn rejects_a_directory_without_tables() {
+        let directory = table_directory("empty", &[("notes.txt", b"KQvK".to_vec())]);
+        assert!(matches!(
+            Tablebase::open(&directory),
+            Err(TablebaseError::NoTables(_))
+        ));
+        assert!(Table::parse("KQvK", header(DTZ_MAGIC), false, &Indices::new()).is_none());
+    }
+}
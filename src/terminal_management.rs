@@ -1,3 +1,5 @@
+use std::time::Duration;
+
 use crossterm::{
     event::{self, Event, KeyCode, KeyEvent, KeyEventKind, KeyModifiers},
     terminal,
@@ -5,6 +7,9 @@ use crossterm::{
 
 use crate::render::Render;
 
+// How often the event loop checks whether the computer has found its move
+const THINKING_POLL_INTERVAL: Duration = Duration::from_millis(50);
+
 pub struct Terminal {
     render: Render,
 }
@@ -13,7 +18,7 @@ impl Terminal {
     pub fn new(renderer: Render) -> Self {
         Self { render: renderer }
     }
-    
+
     // Event loop for reading key presses
     fn read_key(&mut self) -> crossterm::Result<KeyEvent> {
         loop {
@@ -25,7 +30,33 @@ impl Terminal {
 
     // Read the key presses, call corresponding functions
     fn process_keypress(&mut self) -> crossterm::Result<bool> {
-        match self.read_key().unwrap() {
+        let key = self.read_key().unwrap();
+        if self.render.is_entering_move() && key.kind == KeyEventKind::Press {
+            match key.code {
+                KeyCode::Char(char) => self.render.type_move_char(char),
+                KeyCode::Backspace => self.render.erase_move_char(),
+                KeyCode::Enter => self.render.submit_move_entry(),
+                KeyCode::Esc => self.render.cancel_move_entry(),
+                _ => {}
+            }
+            return Ok(true);
+        }
+
+        if self.render.is_replaying() && key.kind == KeyEventKind::Press {
+            if let KeyCode::Left
+            | KeyCode::Right
+            | KeyCode::Up
+            | KeyCode::Down
+            | KeyCode::Home
+            | KeyCode::End
+            | KeyCode::Esc = key.code
+            {
+                self.render.step_replay(key.code);
+                return Ok(true);
+            }
+        }
+
+        match key {
             KeyEvent {
                 code: KeyCode::Char('q'),
                 modifiers: KeyModifiers::CONTROL,
@@ -54,14 +85,60 @@ impl Terminal {
                 kind: KeyEventKind::Press,
                 ..
             } => self.render.press_button(),
+            KeyEvent {
+                code: KeyCode::Char('f'),
+                modifiers: KeyModifiers::NONE,
+                kind: KeyEventKind::Press,
+                ..
+            } => self.render.show_fen(),
+            KeyEvent {
+                code: KeyCode::Char('m'),
+                modifiers: KeyModifiers::NONE,
+                kind: KeyEventKind::Press,
+                ..
+            } => self.render.start_move_entry(),
+            KeyEvent {
+                code: KeyCode::Char('u'),
+                modifiers: KeyModifiers::NONE,
+                kind: KeyEventKind::Press,
+                ..
+            } => self.render.undo_move(),
+            KeyEvent {
+                code: KeyCode::Char('r'),
+                modifiers: KeyModifiers::NONE,
+                kind: KeyEventKind::Press,
+                ..
+            } => self.render.redo_move(),
+            KeyEvent {
+                code: KeyCode::Char('a'),
+                modifiers: KeyModifiers::NONE,
+                kind: KeyEventKind::Press,
+                ..
+            } => self.render.toggle_analysis(),
+            KeyEvent {
+                code: KeyCode::Esc,
+                kind: KeyEventKind::Press,
+                ..
+            } => self.render.move_now(),
 
             _ => {}
         }
         Ok(true)
     }
 
+    // Lets the computer start thinking or play its move, draws the screen, then waits for the next key press
+    // While the computer thinks the wait is broken off as soon as its move is ready, so keys are never blocked by the search
     pub fn run(&mut self) -> crossterm::Result<bool> {
+        self.render.update_computer();
         self.render.refresh_screen().unwrap();
+        while self.render.is_thinking() {
+            if event::poll(THINKING_POLL_INTERVAL)? {
+                return self.process_keypress();
+            }
+            if self.render.search_finished() {
+                return Ok(true);
+            }
+        }
         self.process_keypress()
     }
 }
@@ -0,0 +1,64 @@
+// Random numbers for Zobrist hashing, a position's hash is the XOR of the numbers for everything in it
+// They are generated at compile time from a fixed seed, so a position hashes the same on every run
+
+const PIECE_KEY_COUNT: usize = 2 * 6 * 64;
+const SIDE_KEY_INDEX: usize = PIECE_KEY_COUNT;
+const CASTLING_KEY_INDEX: usize = SIDE_KEY_INDEX + 1;
+const EN_PASSANT_KEY_INDEX: usize = CASTLING_KEY_INDEX + 4;
+const CHECKS_KEY_INDEX: usize = EN_PASSANT_KEY_INDEX + 8;
+const POCKET_KEY_INDEX: usize = CHECKS_KEY_INDEX + 2 * 3;
+const KEY_COUNT: usize = POCKET_KEY_INDEX + 2 * 5 * 16;
+
+const KEYS: [u64; KEY_COUNT] = generate_keys(0x2545_f491_4f6c_dd1d);
+
+// Fills the key table with the SplitMix64 sequence starting from the seed
+const fn generate_keys(seed: u64) -> [u64; KEY_COUNT] {
+    let mut keys = [0; KEY_COUNT];
+    let mut state = seed;
+    let mut i = 0;
+    while i < KEY_COUNT {
+        state = state.wrapping_add(0x9e37_79b9_7f4a_7c15);
+        let mut z = state;
+        z = (z ^ (z >> 30)).wrapping_mul(0xbf58_476d_1ce4_e5b9);
+        z = (z ^ (z >> 27)).wrapping_mul(0x94d0_49bb_1331_11eb);
+        keys[i] = z ^ (z >> 31);
+        i += 1;
+    }
+    keys
+}
+
+// The number for a piece of the given colour index and piece index standing on the square
+pub fn piece_key(colour: usize, piece: usize, square: usize) -> u64 {
+    KEYS[(colour * 6 + piece) * 64 + square]
+}
+
+// The number included whenever black is to move
+pub fn side_key() -> u64 {
+    KEYS[SIDE_KEY_INDEX]
+}
+
+// The number for one castling right, in the order white king side, white queen side, black king side, black queen side
+pub fn castling_key(right: usize) -> u64 {
+    KEYS[CASTLING_KEY_INDEX + right]
+}
+
+// The number for an en passant capture being possible on the given file
+pub fn en_passant_key(file: usize) -> u64 {
+    KEYS[EN_PASSANT_KEY_INDEX + file]
+}
+
+// The number for the side of the given colour index having given from one to three checks in Three-check, no checks has no number
+pub fn checks_key(colour: usize, checks: u32) -> u64 {
+    match checks {
+        0 => 0,
+        _ => KEYS[CHECKS_KEY_INDEX + colour * 3 + checks.min(3) as usize - 1],
+    }
+}
+
+// The number for the side of the given colour index holding from one to sixteen pieces of one pocket index in Crazyhouse
+pub fn pocket_key(colour: usize, piece: usize, count: u8) -> u64 {
+    match count {
+        0 => 0,
+        _ => KEYS[POCKET_KEY_INDEX + (colour * 5 + piece) * 16 + count.min(16) as usize - 1],
+    }
+}